@@ -0,0 +1,251 @@
+use std::{fs, io, path::Path};
+
+use crate::interpreter::Interpreter;
+
+// 按 craftinginterpreters 官方测试的注释格式检查 .lox 文件（conformance 命令）：
+//   print 1; // expect: 1
+//   // expect runtime error: Undefined variable 'a'.
+//   var a = "x" + ; // Error at ';': Expect expression.
+//   // [line 3] Error at 'b': Expect ';' after value.
+// [java line N] 开头的是 jlox 特有的期望，跳过；[c line N] 和 [line N] 一样处理。
+// 这里的编译错误不带 "at 'x'" 部分，比较前把期望中的这部分去掉
+#[derive(Debug, Default)]
+pub struct Expectations {
+    pub output: Vec<String>,
+    // 都是去掉位置后的形式，例如 "[line 3] Error: Expect expression."
+    pub errors: Vec<String>,
+    pub runtime_error: Option<String>,
+}
+
+impl Expectations {
+    pub fn parse(source: &str) -> Self {
+        let mut expected = Self::default();
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let Some(comment) = line.find("//").map(|start| &line[start + 2..]) else {
+                continue;
+            };
+            let comment = comment.trim_start();
+            if let Some(output) = comment.strip_prefix("expect: ") {
+                expected.output.push(output.to_string());
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expected.runtime_error = Some(format!("[line {}] Error: {}", number, message));
+            } else if comment.starts_with("Error") {
+                expected.errors.push(strip_location(&format!("[line {}] {}", number, comment)));
+            } else if let Some(rest) = comment.strip_prefix("[c line ") {
+                expected.errors.push(strip_location(&format!("[line {}", rest)));
+            } else if comment.starts_with("[line ") {
+                expected.errors.push(strip_location(comment));
+            }
+        }
+        expected
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+// "[line 3] Error at 'b': message" 和 "[line 3] Error at end: message" 都变成 "[line 3] Error: message"
+fn strip_location(error: &str) -> String {
+    let Some(start) = error.find("] Error at ") else {
+        return error.to_string();
+    };
+    let rest = &error[start + "] Error at ".len()..];
+    let message = if let Some(message) = rest.strip_prefix("end") {
+        message
+    } else if let Some(quoted) = rest.strip_prefix('\'') {
+        match quoted.find("':") {
+            Some(end) => &quoted[end + 1..],
+            None => return error.to_string(),
+        }
+    } else {
+        return error.to_string();
+    };
+    format!("{}] Error{}", &error[..start], message)
+}
+
+// 运行一个测试，返回和期望不一致的地方，全部一致时为空
+pub fn run_test(source: &str) -> Vec<String> {
+    let expected = Expectations::parse(source);
+    let captured = Interpreter::new().run_and_capture(source);
+    let mut failures = Vec::new();
+
+    let output: Vec<&str> = captured.output.lines().collect();
+    for (index, expected_line) in expected.output.iter().enumerate() {
+        match output.get(index) {
+            Some(actual) if actual == expected_line => {}
+            Some(actual) => failures.push(format!(
+                "Expected output '{}' on line {} and got '{}'.",
+                expected_line,
+                index + 1,
+                actual
+            )),
+            None => failures.push(format!("Missing expected output '{}'.", expected_line)),
+        }
+    }
+    for actual in output.iter().skip(expected.output.len()) {
+        failures.push(format!("Got output '{}' when none was expected.", actual));
+    }
+
+    // 警告不参与比较
+    let errors: Vec<&String> = captured
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| !diagnostic.contains("] Warning: "))
+        .collect();
+    if let Some(runtime_error) = &expected.runtime_error {
+        match errors.first() {
+            Some(actual) if *actual == runtime_error => {}
+            Some(actual) => failures.push(format!(
+                "Expected runtime error '{}' and got '{}'.",
+                runtime_error, actual
+            )),
+            None => failures.push(format!(
+                "Expected runtime error '{}' and got none.",
+                runtime_error
+            )),
+        }
+    } else {
+        for expected_error in &expected.errors {
+            if !errors.contains(&expected_error) {
+                failures.push(format!("Missing expected error '{}'.", expected_error));
+            }
+        }
+        for actual in errors {
+            if !expected.errors.contains(actual) {
+                failures.push(format!("Unexpected error '{}'.", actual));
+            }
+        }
+    }
+
+    if captured.exit_code != expected.exit_code() {
+        failures.push(format!(
+            "Expected exit code {} and got {}.",
+            expected.exit_code(),
+            captured.exit_code
+        ));
+    }
+    failures
+}
+
+// 目录下所有 .lox 文件，包括子目录，按路径排序
+pub fn find_tests(dir: &Path) -> io::Result<Vec<std::path::PathBuf>> {
+    let mut tests = Vec::new();
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            tests.extend(find_tests(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            tests.push(path);
+        }
+    }
+    Ok(tests)
+}
+
+// 跳过列表：每行一个相对测试目录的路径，以 / 结尾时跳过整个子目录；# 开头的行是注释。
+// 用来记录和官方实现有意不同的行为（例如除以零是运行时错误）
+pub fn parse_skip_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn is_skipped(skip: &[String], relative: &str) -> bool {
+    skip.iter().any(|entry| {
+        if entry.ends_with('/') {
+            relative.starts_with(entry.as_str())
+        } else {
+            relative == entry
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expectations_from_comments() {
+        let source = "print 1; // expect: 1\n\
+                      var a = \"x\" + ; // Error at ';': Expect expression.\n\
+                      // [line 4] Error at 'b': Expect ';' after value.\n\
+                      // [c line 5] Error at end: Expect '}' after block.\n\
+                      // [java line 6] Error: ignored.\n\
+                      // expect runtime error: Undefined variable 'a'.\n";
+        let expected = Expectations::parse(source);
+        assert_eq!(expected.output, vec!["1"]);
+        assert_eq!(
+            expected.errors,
+            vec![
+                "[line 2] Error: Expect expression.",
+                "[line 4] Error: Expect ';' after value.",
+                "[line 5] Error: Expect '}' after block.",
+            ]
+        );
+        assert_eq!(
+            expected.runtime_error.as_deref(),
+            Some("[line 6] Error: Undefined variable 'a'.")
+        );
+        assert_eq!(expected.exit_code(), 65);
+    }
+
+    #[test]
+    fn strip_location_keeps_other_errors() {
+        assert_eq!(strip_location("[line 1] Error at 'b': oops"), "[line 1] Error: oops");
+        assert_eq!(strip_location("[line 1] Error at end: oops"), "[line 1] Error: oops");
+        assert_eq!(strip_location("[line 1] Error: oops"), "[line 1] Error: oops");
+        // 没有结束引号时原样保留
+        assert_eq!(strip_location("[line 1] Error at 'b oops"), "[line 1] Error at 'b oops");
+    }
+
+    #[test]
+    fn run_test_reports_mismatches() {
+        assert!(run_test("print 1; // expect: 1\nprint \"a\"; // expect: a\n").is_empty());
+        assert!(run_test("print a; // expect runtime error: Undefined variable 'a'.\n").is_empty());
+        assert!(run_test("print ; // Error at ';': Expect expression.\n").is_empty());
+        assert_eq!(
+            run_test("print 2; // expect: 1\nprint 3;\n"),
+            vec![
+                "Expected output '1' on line 1 and got '2'.",
+                "Got output '3' when none was expected.",
+            ]
+        );
+        assert_eq!(run_test("// expect: 1\n"), vec!["Missing expected output '1'."]);
+        assert_eq!(
+            run_test("print ;\n"),
+            vec![
+                "Unexpected error '[line 1] Error: Expect expression.'.",
+                "Expected exit code 0 and got 65.",
+            ]
+        );
+        assert_eq!(
+            run_test("print 1; // expect runtime error: Undefined variable 'a'.\n"),
+            vec![
+                "Got output '1' when none was expected.",
+                "Expected runtime error '[line 1] Error: Undefined variable 'a'.' and got none.",
+                "Expected exit code 70 and got 0.",
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_list_entries() {
+        let skip = parse_skip_list("# comment\n\noperator/divide_by_zero.lox\n  string/ \n");
+        assert_eq!(skip, vec!["operator/divide_by_zero.lox", "string/"]);
+        assert!(is_skipped(&skip, "operator/divide_by_zero.lox"));
+        assert!(!is_skipped(&skip, "operator/add.lox"));
+        assert!(is_skipped(&skip, "string/unterminated.lox"));
+        assert!(!is_skipped(&skip, "strings.lox"));
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::interpreter::Value;
+
+// Rust 类型和 Value 之间的转换，供嵌入解释器的宿主程序传入、取回数据。
+// 转换得到的值和脚本里写出的同样的值相等，打印结果也相同
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Value::Number(n as f64)
+    }
+}
+
+// 超过 2^53 的整数会丢失精度
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(Rc::new(s.to_string()))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(Rc::new(s))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(elements: Vec<T>) -> Self {
+        Value::list(elements.into_iter().map(Into::into).collect())
+    }
+}
+
+// 映射按插入顺序保存，HashMap 没有顺序，所以按键排序后插入，结果是确定的
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(entries: HashMap<String, T>) -> Self {
+        let mut entries: Vec<(String, Value)> = entries
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Value::map(entries)
+    }
+}
+
+// Value 转换为 Rust 类型失败：期望的类型、实际的类型，以及在嵌套的列表和映射中的位置，
+// 例如 [1].price
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+    pub path: String,
+}
+
+impl ConversionError {
+    fn new(expected: &'static str, value: &Value) -> Self {
+        Self {
+            expected,
+            found: value.type_name(),
+            path: String::new(),
+        }
+    }
+    // 外层容器中的位置写在前面
+    fn within(mut self, segment: String) -> Self {
+        self.path.insert_str(0, &segment);
+        self
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expected {} but got {}", self.expected, self.found)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        write!(f, ".")
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+// 不用写出目标类型的简写：value.try_into_f64()? 等同于 f64::try_from(value)?
+impl Value {
+    pub fn try_into_f64(self) -> Result<f64, ConversionError> {
+        f64::try_from(self)
+    }
+    pub fn try_into_string(self) -> Result<String, ConversionError> {
+        String::try_from(self)
+    }
+    pub fn try_into_bool(self) -> Result<bool, ConversionError> {
+        bool::try_from(self)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ConversionError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(n) => Ok(n),
+            other => Err(ConversionError::new("number", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            // 没有其他引用时直接取出，不复制
+            Value::String(s) => Ok(Rc::try_unwrap(s).unwrap_or_else(|s| (*s).clone())),
+            other => Err(ConversionError::new("string", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ConversionError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            other => Err(ConversionError::new("bool", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for () {
+    type Error = ConversionError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Nil => Ok(()),
+            other => Err(ConversionError::new("nil", &other)),
+        }
+    }
+}
+
+// 列表的元素被复制出来，脚本之后修改列表不会影响转换结果
+impl<T: TryFrom<Value, Error = ConversionError>> TryFrom<Value> for Vec<T> {
+    type Error = ConversionError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::List(elements) => elements
+                .borrow()
+                .iter()
+                .enumerate()
+                .map(|(i, element)| {
+                    T::try_from(element.clone()).map_err(|error| error.within(format!("[{}]", i)))
+                })
+                .collect(),
+            other => Err(ConversionError::new("list", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = ConversionError>> TryFrom<Value> for HashMap<String, T> {
+    type Error = ConversionError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Map(entries) => entries
+                .borrow()
+                .iter()
+                .map(|(key, value)| {
+                    T::try_from(value.clone())
+                        .map(|value| (key.clone(), value))
+                        .map_err(|error| error.within(format!(".{}", key)))
+                })
+                .collect(),
+            other => Err(ConversionError::new("map", &other)),
+        }
+    }
+}
+
+// 类似 serde_json::json! 的字面量写法：
+//   lox_value!({ "name": "apple", "tags": ["red", 1.5, nil], "ok": true })
+// 列表和映射可以嵌套，nil 表示 Nil，其他值通过 Value::from 转换；
+// 负数和其他复杂的表达式要加括号，例如 [(-1), (a + b)]
+#[macro_export]
+macro_rules! lox_value {
+    (nil) => {
+        $crate::interpreter::Value::Nil
+    };
+    ([ $($element:tt),* $(,)? ]) => {
+        $crate::interpreter::Value::list(vec![$($crate::lox_value!($element)),*])
+    };
+    ({ $($key:literal : $value:tt),* $(,)? }) => {
+        $crate::interpreter::Value::map(vec![$(($key.to_string(), $crate::lox_value!($value))),*])
+    };
+    ($other:expr) => {
+        $crate::interpreter::Value::from($other)
+    };
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use crate::parser::stmt::Stmt;
+
+// run --coverage：Interpreter 在 execute/evaluate 中按行计数，
+// 这里根据语法树找出可执行的行，生成覆盖率报告
+
+// 所有语句所在的行，即报告中需要被覆盖的行
+pub fn coverable_lines(stmts: &[Stmt]) -> BTreeSet<usize> {
+    let mut lines = BTreeSet::new();
+    for stmt in stmts {
+        collect(stmt, &mut lines);
+    }
+    lines
+}
+
+fn collect(stmt: &Stmt, lines: &mut BTreeSet<usize>) {
+    if !matches!(stmt, Stmt::Block(_)) {
+        lines.extend(stmt.line());
+    }
+    match stmt {
+        Stmt::Block(stmts) | Stmt::Function(_, _, _, stmts) | Stmt::Class(_, _, stmts) => {
+            for stmt in stmts {
+                collect(stmt, lines);
+            }
+        }
+        Stmt::If(_, _, then_branch, else_branch) => {
+            collect(then_branch, lines);
+            if let Some(else_branch) = else_branch {
+                collect(else_branch, lines);
+            }
+        }
+        Stmt::For(_, initializer, _, _, body) => {
+            if let Some(initializer) = initializer {
+                collect(initializer, lines);
+            }
+            collect(body, lines);
+        }
+        Stmt::While(_, _, body)
+        | Stmt::Loop(_, body)
+        | Stmt::ForIn(_, _, _, body)
+        | Stmt::Labeled(_, body) => collect(body, lines),
+        Stmt::Match(_, _, arms) => {
+            for (_, body) in arms {
+                collect(body, lines);
+            }
+        }
+        _ => {}
+    }
+}
+
+// 摘要（覆盖率和未覆盖的行号）加上逐行标注的源码：
+// 左侧是执行次数，未覆盖的行标记为 #####，不可执行的行标记为 -
+pub fn report(source: &str, coverable: &BTreeSet<usize>, hits: &HashMap<usize, usize>) -> String {
+    let uncovered: Vec<usize> = coverable
+        .iter()
+        .copied()
+        .filter(|line| !hits.contains_key(line))
+        .collect();
+    let covered = coverable.len() - uncovered.len();
+    let percent = if coverable.is_empty() {
+        100.0
+    } else {
+        covered as f64 * 100.0 / coverable.len() as f64
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Coverage: {}/{} lines ({:.1}%)",
+        covered,
+        coverable.len(),
+        percent
+    );
+    let uncovered: Vec<String> = uncovered.iter().map(usize::to_string).collect();
+    let _ = writeln!(out, "Uncovered lines: {}", uncovered.join(", "));
+    let _ = writeln!(out);
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let count = match hits.get(&line) {
+            Some(count) => count.to_string(),
+            None if coverable.contains(&line) => "#####".to_string(),
+            None => "-".to_string(),
+        };
+        let _ = writeln!(out, "{:>7} {:>5} | {}", count, line, text);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_summary_and_annotated_source() {
+        let coverable = BTreeSet::from([1, 2, 4]);
+        let hits = HashMap::from([(1, 1), (2, 12)]);
+        assert_eq!(
+            report("a;\nb;\n\nc;", &coverable, &hits),
+            "Coverage: 2/3 lines (66.7%)\n\
+             Uncovered lines: 4\n\
+             \n      1     1 | a;\n     12     2 | b;\n      -     3 | \n  #####     4 | c;\n"
+        );
+    }
+
+    #[test]
+    fn nothing_to_cover_is_full_coverage() {
+        assert_eq!(
+            report("// only a comment", &BTreeSet::new(), &HashMap::new()),
+            "Coverage: 0/0 lines (100.0%)\nUncovered lines: \n\n      -     1 | // only a comment\n"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    interpreter::RuntimeError,
+    limits::Limits,
+    parser::{self, stmt::Stmt, ParseError, Parser},
+    resolver::{Resolver, Warning},
+    scanner::{token::Error, Scanner},
+};
+
+// 诊断信息的代码，编号一旦发布就不再改变，工具可以按代码过滤
+pub const SCAN_ERROR: &str = "E001-scan-error";
+pub const PARSE_ERROR: &str = "E002-parse-error";
+pub const RUNTIME_ERROR: &str = "E003-runtime-error";
+pub const LIMIT_EXCEEDED: &str = "E004-limit-exceeded";
+pub const UNREACHABLE_CODE: &str = "W001-unreachable-code";
+pub const INFINITE_LOOP: &str = "W002-infinite-loop";
+pub const INEXACT_NUMBER: &str = "W003-inexact-number";
+pub const SHADOW_NATIVE: &str = "W004-shadow-native";
+
+// 错误和警告的输出。plain 为 true 时使用 CodeCrafters 的测试要求的格式 `[line 3] Error: ...`；
+// 否则前面加上来源的名字（文件路径、<stdin> 等），例如 `main.lox:[line 3] Error: ...`
+pub struct Diagnostics {
+    pub name: String,
+    pub plain: bool,
+}
+
+impl Diagnostics {
+    pub fn new(name: &str, plain: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            plain,
+        }
+    }
+
+    pub fn format(&self, message: impl Display) -> String {
+        if self.plain {
+            message.to_string()
+        } else {
+            format!("{}:{}", self.name, message)
+        }
+    }
+
+    // 输出到 stderr
+    pub fn report(&self, message: impl Display) {
+        eprintln!("{}", self.format(message));
+    }
+}
+
+// 源码中第 line 行，下一行在第 column 列标出 ^。行太长时只显示 ^ 附近的一段，省略的部分用 ... 表示；
+// 制表符原样保留，^ 前面用同样的制表符对齐
+pub fn snippet(source: &str, line: usize, column: usize) -> Option<String> {
+    let text: Vec<char> = source.lines().nth(line.checked_sub(1)?)?.chars().collect();
+    if column == 0 || column > text.len() + 1 {
+        return None;
+    }
+    let caret = column - 1;
+    let start = caret.saturating_sub(SNIPPET_WIDTH / 2);
+    let end = (start + SNIPPET_WIDTH).min(text.len());
+    let start = end.saturating_sub(SNIPPET_WIDTH).min(start);
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < text.len() { "..." } else { "" };
+    let shown: String = text[start..end].iter().collect();
+    let padding: String = text[start..caret]
+        .iter()
+        .map(|&c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    Some(format!(
+        "    {}{}{}\n    {}{}^",
+        prefix,
+        shown,
+        suffix,
+        " ".repeat(prefix.len()),
+        padding
+    ))
+}
+
+// snippet 最多显示的字符数
+const SNIPPET_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// 一条错误或警告，按字段保存，run --json 时分别输出行号和信息
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    // 从 1 开始的列号，0 表示没有记录
+    pub column: usize,
+    pub message: String,
+    // 见上面的 SCAN_ERROR、UNREACHABLE_CODE 等常量
+    pub code: &'static str,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        write!(f, "[line {}] {}: {}", self.line, severity, self.message)
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        Self {
+            severity: Severity::Error,
+            line: error.line,
+            column: 0,
+            message: error.message.clone(),
+            code: error.code,
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Self {
+            severity: Severity::Error,
+            line: error.line(),
+            column: 0,
+            message: error.message().to_string(),
+            code: error.code(),
+        }
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line: warning.line,
+            column: 0,
+            message: warning.message.clone(),
+            code: warning.code,
+        }
+    }
+}
+
+// return/break/continue 到达顶层时不是真正的错误，不会走到这里；为了完整，行号记为 0
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        match error {
+            RuntimeError::Error {
+                message,
+                line,
+                column,
+            } => Self {
+                severity: Severity::Error,
+                line: *line,
+                column: *column,
+                message: message.clone(),
+                code: RUNTIME_ERROR,
+            },
+            other => Self {
+                severity: Severity::Error,
+                line: 0,
+                column: 0,
+                message: other.to_string(),
+                code: RUNTIME_ERROR,
+            },
+        }
+    }
+}
+
+// 扫描、解析并通过 Resolver 检查，不输出任何内容。返回全部错误和警告，按出现的顺序排列；
+// 没有错误时还返回可以交给 Interpreter::interpret 执行的语句
+pub fn check_source(source: &str) -> (Vec<Diagnostic>, Option<Vec<Stmt>>) {
+    check_source_with(source, Resolver::new(), Limits::default())
+}
+
+// 和 check_source 相同，使用调用者配置好的 Resolver（例如打开 warn_shadow_native）和规模限制
+pub fn check_source_with(
+    source: &str,
+    mut resolver: Resolver,
+    limits: Limits,
+) -> (Vec<Diagnostic>, Option<Vec<Stmt>>) {
+    if source.len() > limits.max_source_bytes {
+        let error = Diagnostic {
+            severity: Severity::Error,
+            line: 1,
+            column: 0,
+            message: format!("Source too large: limit is {} bytes.", limits.max_source_bytes),
+            code: LIMIT_EXCEEDED,
+        };
+        return (vec![error], None);
+    }
+    let mut scanner = Scanner::new(source);
+    scanner.max_tokens = limits.max_tokens;
+    let (tokens, errors) = scanner.scan_tokens();
+    if !errors.is_empty() {
+        return (errors.iter().map(Diagnostic::from).collect(), None);
+    }
+    let mut source_parser = Parser::new(tokens);
+    source_parser.max_nodes = limits.max_nodes;
+    let mut stmts = match source_parser.parse() {
+        Ok(stmts) => parser::lower::strip_groups_stmts(stmts),
+        Err(errors) => return (errors.iter().map(Diagnostic::from).collect(), None),
+    };
+    let mut diagnostics = scanner_warnings(&scanner);
+    diagnostics.extend(resolver.resolve(&mut stmts).iter().map(Diagnostic::from));
+    // Resolver 的错误（例如未定义的标签）和解析错误一样，语句不能执行
+    if !resolver.errors().is_empty() {
+        return (resolver.errors().iter().map(Diagnostic::from).collect(), None);
+    }
+    (diagnostics, Some(stmts))
+}
+
+// 扫描器的警告只有数字字面量不能精确表示这一种
+pub fn scanner_warnings(scanner: &Scanner) -> Vec<Diagnostic> {
+    scanner
+        .warnings()
+        .iter()
+        .map(|warning| Diagnostic {
+            severity: Severity::Warning,
+            line: warning.line,
+            column: 0,
+            message: warning.message.clone(),
+            code: warning.code,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet_marks_the_column() {
+        let source = "var a = 1;\nprint a + nil;";
+        assert_eq!(snippet(source, 2, 9).unwrap(), "    print a + nil;\n            ^");
+        // 行尾之后一列（例如缺少的 ;）也可以标出
+        assert_eq!(snippet(source, 1, 11).unwrap(), "    var a = 1;\n              ^");
+        // 制表符原样保留，^ 前面用同样的制表符对齐
+        assert_eq!(snippet("\t\tx + 1", 1, 5).unwrap(), "    \t\tx + 1\n    \t\t  ^");
+    }
+
+    #[test]
+    fn snippet_without_a_position() {
+        assert_eq!(snippet("print 1;", 1, 0), None);
+        assert_eq!(snippet("print 1;", 1, 10), None);
+        assert_eq!(snippet("print 1;", 2, 1), None);
+        assert_eq!(snippet("print 1;", 0, 1), None);
+    }
+
+    // 长行只显示 ^ 附近的 80 个字符，省略的部分用 ... 表示
+    #[test]
+    fn snippet_of_a_long_line() {
+        let line = format!("{}nil{}", "a".repeat(100), "b".repeat(100));
+        let snippet = snippet(&line, 1, 101).unwrap();
+        let expected = format!(
+            "    ...{}nil{}...\n    {}^",
+            "a".repeat(40),
+            "b".repeat(37),
+            " ".repeat(43)
+        );
+        assert_eq!(snippet, expected);
+        let snippet = super::snippet(&line, 1, 3).unwrap();
+        assert!(snippet.starts_with(&format!("    {}...\n      ^", "a".repeat(80))), "{}", snippet);
+    }
+
+    // 每种诊断信息都有自己的代码，按出现的顺序返回
+    #[test]
+    fn check_source_reports_codes() {
+        let codes = |source: &str| -> Vec<&'static str> {
+            check_source(source).0.iter().map(|diagnostic| diagnostic.code).collect()
+        };
+        assert_eq!(codes("print @;"), vec![SCAN_ERROR]);
+        assert_eq!(codes("print ;\nvar = 1;"), vec![PARSE_ERROR, PARSE_ERROR]);
+        assert_eq!(
+            codes("var big = 9007199254740993;\nfun f() { return 1; print 2; }\nloop {}"),
+            vec![INEXACT_NUMBER, UNREACHABLE_CODE, INFINITE_LOOP]
+        );
+        assert!(codes("print 1;").is_empty());
+    }
+
+    // 有错误时没有语句；只有警告时语句可以直接执行
+    #[test]
+    fn check_source_returns_statements_without_errors() {
+        assert!(check_source("print ;").1.is_none());
+        let (diagnostics, stmts) = check_source("fun f() { return 1; print 2; }\nprint f();");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(stmts.map(|stmts| stmts.len()), Some(2));
+    }
+
+    // 超出规模限制时报告 LIMIT_EXCEEDED，不返回语句
+    #[test]
+    fn check_source_limits() {
+        let check = |source: &str, limits: Limits| {
+            let (diagnostics, stmts) = check_source_with(source, Resolver::new(), limits);
+            assert!(stmts.is_none(), "{:?}", source);
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.code, diagnostic.message.clone()))
+                .collect::<Vec<_>>()
+        };
+        let defaults = Limits::default();
+        let limits = Limits { max_source_bytes: 8, ..defaults };
+        assert_eq!(
+            check("print 12345;", limits),
+            vec![(LIMIT_EXCEEDED, "Source too large: limit is 8 bytes.".to_string())]
+        );
+        let limits = Limits { max_tokens: 3, ..defaults };
+        assert_eq!(
+            check("print 1 + 2;", limits),
+            vec![(LIMIT_EXCEEDED, "Too many tokens: limit is 3.".to_string())]
+        );
+        // 限制之前的扫描错误照常报告
+        assert_eq!(check("@ print 1 + 2;", limits)[0].0, SCAN_ERROR);
+        let limits = Limits { max_nodes: 3, ..defaults };
+        assert_eq!(
+            check("print 1 + 2 + 3;\nprint ;", limits),
+            vec![(LIMIT_EXCEEDED, "Too many syntax tree nodes: limit is 3.".to_string())]
+        );
+        let limits = Limits { max_nodes: 4, ..defaults };
+        assert!(check_source_with("print 1 + 2 + 3;", Resolver::new(), limits).1.is_some());
+    }
+}
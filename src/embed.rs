@@ -0,0 +1,184 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    mem,
+    rc::Rc,
+};
+
+use crate::{
+    diagnostics,
+    environment::Environment,
+    interpreter::{Interpreter, RuntimeError, Value},
+    parser::{self, expr::Expr, ParseError, Parser},
+    scanner::{
+        self,
+        token::{Token, TokenType},
+        Scanner,
+    },
+};
+
+// 嵌入 API 的错误：扫描、解析或运行时出错
+#[derive(Debug)]
+pub enum LoxError {
+    Scan(Vec<scanner::token::Error>),
+    Parse(ParseError),
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxError::Scan(errors) => {
+                let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            LoxError::Parse(error) => write!(f, "{}", error),
+            LoxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+// 用宿主提供的变量计算一个表达式，例如 "price * qty * (1 + taxRate)"
+// 只能是单个表达式，不能调用函数，环境中也没有内置函数
+pub fn evaluate_with_vars(source: &str, vars: &HashMap<String, Value>) -> Result<Value, LoxError> {
+    evaluate_expression(source, vars, false)
+}
+
+// 和 evaluate_with_vars 相同，但可以调用内置函数
+pub fn evaluate_with_vars_and_natives(
+    source: &str,
+    vars: &HashMap<String, Value>,
+) -> Result<Value, LoxError> {
+    evaluate_expression(source, vars, true)
+}
+
+fn evaluate_expression(
+    source: &str,
+    vars: &HashMap<String, Value>,
+    allow_calls: bool,
+) -> Result<Value, LoxError> {
+    let mut scanner = Scanner::new(source);
+    let (tokens, errors) = scanner.scan_tokens();
+    if !errors.is_empty() {
+        return Err(LoxError::Scan(errors.clone()));
+    }
+    let expr = Parser::new(tokens).parse_expr().map_err(LoxError::Parse)?;
+    if !allow_calls {
+        if let Some(line) = find_call(&expr) {
+            return Err(LoxError::Parse(ParseError::new(
+                "Function calls are not allowed in this expression.",
+                line,
+            )));
+        }
+    }
+    let env = Rc::new(Environment::new(None));
+    if allow_calls {
+        env.define_natives();
+    }
+    for (name, value) in vars {
+        env.define(name.clone(), Some(value.clone()));
+    }
+    let mut interpreter = Interpreter::with_env(env);
+    let expr = parser::lower::strip_groups(expr);
+    let env = Rc::clone(&interpreter.env);
+    interpreter.evaluate(&expr, &env).map_err(LoxError::Runtime)
+}
+
+// 表达式中第一个函数调用所在的行
+fn find_call(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Call(callee, paren, ..) => callee.line().or(Some(paren.line)),
+        Expr::Literal(_)
+        | Expr::Variable(..)
+        | Expr::This(..)
+        | Expr::Super(..)
+        | Expr::Lambda(..) => None,
+        Expr::Grouping(inner) | Expr::Unary(_, inner) | Expr::Get(inner, _, _) => find_call(inner),
+        Expr::Assign(_, value, _) => find_call(value),
+        Expr::Binary(left, _, right)
+        | Expr::Logical(left, _, right)
+        | Expr::Index(left, _, right, _)
+        | Expr::Set(left, _, right) => find_call(left).or_else(|| find_call(right)),
+        Expr::List(elements) => elements.iter().find_map(find_call),
+    }
+}
+
+// run_and_capture 的结果
+pub struct Captured {
+    // print 和 printf 输出的内容
+    pub output: String,
+    // 警告和错误，格式和命令行默认的相同，例如 "[line 3] Error: ..."
+    pub diagnostics: Vec<String>,
+    // 和 run 命令相同的退出码：0 成功，65 扫描或解析错误，70 运行时错误
+    pub exit_code: i32,
+}
+
+// 运行期间替换 Interpreter::output，结束后取出写入的内容
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Interpreter {
+    // 和 run 命令一样扫描、解析、检查并执行一段程序，但不写 stdout 和 stderr：
+    // 输出和诊断信息都在返回值里，便于测试和宿主程序检查结果。全局环境在多次调用之间保留
+    pub fn run_and_capture(&mut self, source: &str) -> Captured {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let output = mem::replace(&mut self.output, Box::new(SharedBuffer(Rc::clone(&buffer))));
+        let mut diagnostics = Vec::new();
+        let exit_code = self.check_and_run(source, &mut diagnostics);
+        self.output = output;
+        let output = String::from_utf8_lossy(&buffer.borrow()).into_owned();
+        Captured {
+            output,
+            diagnostics,
+            exit_code,
+        }
+    }
+
+    // 按名字调用全局环境中的函数（脚本定义的函数、内置函数或类），例如脚本运行后调用其中的
+    // fun transform(x)。和脚本中的调用走同一条路径，参数个数不对时的错误也相同；
+    // 名字不存在或不是函数时返回运行时错误。这些错误没有对应的源码位置，行号为 0
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> Result<Value, LoxError> {
+        let token = Token::new(TokenType::Identifier, name.to_string(), None, 0);
+        let callee = self.env.get(&token).map_err(LoxError::Runtime)?;
+        match callee {
+            Some(
+                callee @ (Value::NativeFunction(_)
+                | Value::Function(_)
+                | Value::Memoized(_)
+                | Value::Class(_)),
+            ) => self.call(callee, args, 0).map_err(LoxError::Runtime),
+            _ => Err(LoxError::Runtime(RuntimeError::new(
+                format!("'{}' is not a function.", name),
+                0,
+            ))),
+        }
+    }
+
+    fn check_and_run(&mut self, source: &str, diagnostics: &mut Vec<String>) -> i32 {
+        let (found, stmts) = diagnostics::check_source(source);
+        diagnostics.extend(found.iter().map(ToString::to_string));
+        let Some(stmts) = stmts else {
+            return 65;
+        };
+        match self.interpret(stmts) {
+            Ok(()) => 0,
+            Err(error) => {
+                diagnostics.push(error.to_string());
+                70
+            }
+        }
+    }
+}
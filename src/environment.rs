@@ -1,39 +1,119 @@
-use std::{borrow::Borrow, cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
 
 use crate::{
     interpreter::{RuntimeError, Value},
+    natives,
     scanner::token::Token,
+    stats::{Live, ENVIRONMENTS},
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Environment {
     pub values: RefCell<HashMap<String, Option<Value>>>,
+    // 带类型标注的变量名 -> 类型名，只在 --check-types 时记录，赋值时据此检查
+    annotations: RefCell<HashMap<String, Token>>,
     enclosing: Option<Rc<Environment>>,
+    _live: Live,
 }
 
 impl Environment {
     pub fn new(enclosing: Option<Rc<Environment>>) -> Self {
         Self {
             values: RefCell::new(HashMap::new()),
-            enclosing: enclosing.map(|env| env),
+            annotations: RefCell::new(HashMap::new()),
+            enclosing,
+            _live: Live::new(ENVIRONMENTS),
         }
     }
+    // 以当前环境为外层的新环境；在子环境中定义变量不影响当前环境，赋值已有变量仍会写到当前环境
+    pub fn child(self: &Rc<Self>) -> Rc<Environment> {
+        Rc::new(Environment::new(Some(Rc::clone(self))))
+    }
     pub fn define(&self, name: String, value: Option<Value>) {
         self.values.borrow_mut().insert(name, value);
     }
+    // 重新声明同名变量时 annotation 为 None，会清掉之前的标注
+    pub fn annotate(&self, name: &str, annotation: Option<&Token>) {
+        match annotation {
+            Some(annotation) => {
+                self.annotations
+                    .borrow_mut()
+                    .insert(name.to_string(), annotation.clone());
+            }
+            None => {
+                self.annotations.borrow_mut().remove(name);
+            }
+        }
+    }
+    // 查找变量的类型标注；depth 为 None 时沿环境链找到第一个定义了该变量的作用域
+    pub fn annotation(&self, depth: Option<usize>, name: &str) -> Option<Token> {
+        match depth {
+            Some(0) => self.annotations.borrow().get(name).cloned(),
+            Some(depth) => self.enclosing.as_ref()?.annotation(Some(depth - 1), name),
+            None if self.values.borrow().contains_key(name) => {
+                self.annotations.borrow().get(name).cloned()
+            }
+            None => self.enclosing.as_ref()?.annotation(None, name),
+        }
+    }
     pub fn assign(&self, name: &Token, value: Option<Value>) -> Result<(), RuntimeError> {
         if self.values.borrow().contains_key(&name.lexeme) {
             self.values.borrow_mut().insert(name.lexeme.clone(), value);
-            return Ok(());
+            Ok(())
         } else if let Some(enclosing) = &self.enclosing {
-            return enclosing.assign(name, value);
+            enclosing.assign(name, value)
         } else {
-            Err(RuntimeError::new(
+            Err(RuntimeError::at(
                 format!("Undefined variable '{}'.", &name.lexeme),
-                name.line,
+                name,
             ))
         }
     }
+    // 和 get_at 对应：只在向上 depth 层的作用域里赋值
+    pub fn assign_at(
+        &self,
+        depth: usize,
+        name: &Token,
+        value: Option<Value>,
+    ) -> Result<(), RuntimeError> {
+        if depth == 0 {
+            let mut values = self.values.borrow_mut();
+            if let Some(slot) = values.get_mut(&name.lexeme) {
+                *slot = value;
+                return Ok(());
+            }
+            return Err(RuntimeError::at(
+                format!("Undefined variable '{}'.", &name.lexeme),
+                name,
+            ));
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.assign_at(depth - 1, name, value),
+            None => Err(RuntimeError::at(
+                format!("Undefined variable '{}'.", &name.lexeme),
+                name,
+            )),
+        }
+    }
+    // 沿着环境链向上走 depth 层，只在那一层查找
+    pub fn get_at(&self, depth: usize, name: &Token) -> Result<Option<Value>, RuntimeError> {
+        if depth == 0 {
+            if let Some(value) = self.values.borrow().get(&name.lexeme) {
+                return Ok(value.clone());
+            }
+            return Err(RuntimeError::at(
+                format!("Undefined variable '{}'.", &name.lexeme),
+                name,
+            ));
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.get_at(depth - 1, name),
+            None => Err(RuntimeError::at(
+                format!("Undefined variable '{}'.", &name.lexeme),
+                name,
+            )),
+        }
+    }
     pub fn get(&self, name: &Token) -> Result<Option<Value>, RuntimeError> {
         if let Some(value) = self.values.borrow().get(&name.lexeme) {
             return Ok(value.clone());
@@ -42,21 +122,72 @@ impl Environment {
             return enclosing.get(name); // 递归查找父作用域
         }
 
-        Err(RuntimeError::new(
+        Err(RuntimeError::at(
             format!("Undefined variable '{}'.", &name.lexeme),
-            name.line,
+            name,
         ))
     }
 
+    // 当前作用域绑定的快照，按名字排序；未初始化的变量视为 nil
+    pub fn snapshot(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self
+            .values
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone().unwrap_or(Value::Nil)))
+            .collect();
+        bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+        bindings
+    }
+
     pub fn define_natives(&self) {
-        self.define(
-            "clock".to_string(),
-            Some(Value::NativeFunction(|| {
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap();
-                Value::Number(now.as_secs_f64())
-            })),
-        );
+        for native in natives::all() {
+            self.define(native.name.to_string(), Some(Value::NativeFunction(native)));
+        }
+    }
+}
+
+// 调试输出每层作用域的变量，按名字排序，结果在多次运行之间相同。depth 0 是当前作用域，
+// 向外依次加一，和 Resolver 计算的深度一致。值用 Display 输出而不是 Debug：
+// 函数值的 Debug 会输出闭包环境，而闭包可能就是正在输出的环境，用 Display 不会循环
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut env = Some(self);
+        let mut depth = 0;
+        while let Some(current) = env {
+            writeln!(f, "Environment (depth {})", depth)?;
+            let values = current.values.borrow();
+            let mut names: Vec<&String> = values.keys().collect();
+            names.sort();
+            for name in names {
+                let value = match &values[name] {
+                    Some(Value::String(s)) => format!("\"{}\"", s),
+                    Some(value) => value.to_string(),
+                    None => "<uninitialized>".to_string(),
+                };
+                writeln!(f, "  {} = {}", name, truncate(&value, MAX_VALUE_WIDTH))?;
+            }
+            env = current.enclosing.as_deref();
+            depth += 1;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// 调试输出中一个值最多显示的字符数
+const MAX_VALUE_WIDTH: usize = 60;
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
     }
+    let mut truncated: String = text.chars().take(width).collect();
+    truncated.push_str("...");
+    truncated
 }
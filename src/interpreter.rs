@@ -1,171 +1,882 @@
 use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display, Formatter},
+    io::{self, Write},
+    ops::RangeInclusive,
     rc::Rc,
+    time::Duration,
 };
 
 use crate::{
     environment::Environment,
+    location::{self, Phase},
+    natives::{
+        memo::{self, Memoized},
+        time::{SystemClock, TimeSource},
+    },
+    number::format_number,
+    ops,
     parser::{
         expr::{Expr, Literal},
-        stmt::Stmt,
+        printer,
+        stmt::{Param, Pattern, Stmt},
     },
     scanner::token::{Token, TokenType},
+    stats::{self, Live, Stats, Tracked, FUNCTIONS, LISTS, MAPS},
 };
 
 #[derive(Debug, Clone)]
 pub enum RuntimeError {
-    Error { message: String, line: usize },
-    Return(Value),
+    // column 是出错的运算符或名字所在的列，从 1 开始；0 表示没有记录
+    Error { message: String, line: usize, column: usize },
+    // return 的第一个值和其余的值（return a, b; 时不为空）
+    Return(Value, Vec<Value>),
+    // 带标签的 break/continue 要先跳出的内层循环数，见 Stmt::Break
+    Break(usize),
+    Continue(usize),
 }
 
 impl RuntimeError {
     pub fn new(message: String, line: usize) -> Self {
-        Self::Error { message, line }
+        Self::Error {
+            message,
+            line,
+            column: 0,
+        }
+    }
+    // 指向出错的 token，报告错误时可以在源码行下面标出位置
+    pub fn at(message: String, token: &Token) -> Self {
+        Self::Error {
+            message,
+            line: token.line,
+            column: token.column,
+        }
     }
 }
 
 impl Display for RuntimeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
-            RuntimeError::Error { message, line } => {
+            RuntimeError::Error { message, line, .. } => {
                 write!(f, "[line {}] Error: {}", line, message)
             }
-            RuntimeError::Return(value) => {
+            RuntimeError::Return(value, _) => {
                 write!(f, "Return {}", value)
             }
+            RuntimeError::Break(_) => write!(f, "Break"),
+            RuntimeError::Continue(_) => write!(f, "Continue"),
         }
     }
 }
 
+impl std::error::Error for RuntimeError {}
+
+// 内置函数：参数已经求值，line 是调用处的行号，用于报告运行时错误
+pub type NativeFn = fn(&mut Interpreter, &[Value], usize) -> Result<Value, RuntimeError>;
+
+#[derive(Clone, Debug)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    // 接受的参数个数范围，可选参数用 1..=2 这样的范围表示
+    pub arity: RangeInclusive<usize>,
+    pub func: NativeFn,
+}
+
+// 用户定义的函数。Value 里只保存 Rc，复制函数值不会复制函数体
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Rc<[Param]>,
+    pub return_type: Option<Token>,
+    pub body: Rc<[Stmt]>,
+    // 定义函数时所在的环境本身（共享引用），不是变量的快照：函数体中的名字在调用时才查找，
+    // 所以能看到定义之后的赋值和声明，全局函数也通过这个环境按名字找到自己实现递归。
+    // 改成快照会破坏递归和互相递归，conformance/function 和 conformance/closure 下的样例覆盖了这些行为
+    pub closure: Rc<Environment>,
+    // 类的 init 方法：调用结果总是 this
+    pub is_initializer: bool,
+    _live: Live,
+}
+
+impl Function {
+    pub fn new(
+        name: String,
+        params: Rc<[Param]>,
+        return_type: Option<Token>,
+        body: Rc<[Stmt]>,
+        closure: Rc<Environment>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            return_type,
+            body,
+            closure,
+            is_initializer,
+            _live: Live::new(FUNCTIONS),
+        }
+    }
+    // 把方法绑定到实例：在闭包外再套一层只定义了 this 的环境
+    pub fn bind(&self, instance: Value) -> Rc<Function> {
+        let env = Rc::new(Environment::new(Some(Rc::clone(&self.closure))));
+        env.define("this".to_string(), Some(instance));
+        Rc::new(Function::new(
+            self.name.clone(),
+            Rc::clone(&self.params),
+            self.return_type.clone(),
+            Rc::clone(&self.body),
+            env,
+            self.is_initializer,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    // 未绑定的方法，通过实例访问时才绑定 this
+    pub methods: HashMap<String, Rc<Function>>,
+    // 字段的默认值（没有初始值的是 nil），创建实例时在定义类的环境 closure 中求值
+    pub defaults: Vec<(String, Option<Expr>)>,
+    pub closure: Rc<Environment>,
+}
+
+impl Class {
+    // 先找自己的方法，再沿着父类链向上找
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+    // 是否是 name 类或它的子类
+    pub fn is_a(&self, name: &str) -> bool {
+        self.name == name || self.superclass.as_ref().is_some_and(|class| class.is_a(name))
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    // 字段按第一次赋值的顺序保存，遍历顺序是确定的
+    pub fields: RefCell<Vec<(String, Value)>>,
+}
+
+// 列表和映射的共享存储，Tracked 用于统计存活数量
+pub type ListRef = Rc<Tracked<RefCell<Vec<Value>>>>;
+pub type MapRef = Rc<Tracked<RefCell<Vec<(String, Value)>>>>;
+
+// 复制 Value 的开销：Number、Bool、Nil 只是复制几个字节；字符串、函数、列表、映射、类和实例
+// 只增加 Rc 的引用计数；内置函数是名字、参数范围和函数指针
 #[derive(Clone, Debug)]
 pub enum Value {
     Number(f64),
-    String(String),
+    String(Rc<String>),
     Bool(bool),
     Nil,
-    NativeFunction(fn() -> Value),
-    Function(String, Vec<Token>, Vec<Stmt>, Rc<Environment>),
+    NativeFunction(NativeFunction),
+    Function(Rc<Function>),
+    // 列表是引用类型，复制 Value 只复制引用
+    List(ListRef),
+    // 字符串键的映射，按插入顺序保存
+    Map(MapRef),
+    // memoize(f) 返回的带缓存的函数
+    Memoized(Rc<Memoized>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+}
+
+impl Value {
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(Tracked::new(RefCell::new(elements), LISTS)))
+    }
+    pub fn map(entries: Vec<(String, Value)>) -> Self {
+        Value::Map(Rc::new(Tracked::new(RefCell::new(entries), MAPS)))
+    }
+    // 类型名，与类型模式和类型标注使用的名字一致
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::NativeFunction(_) | Value::Function(..) | Value::Memoized(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Number(n) => write!(f, "{}", n),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Nil => write!(f, "nil"),
-            Value::NativeFunction(_) => write!(f, "<fn>"),
-            Value::Function(name, _, _, _) => {
-                write!(f, "<fn {}>", name)
+        write_value(f, self, &mut Vec::new())
+    }
+}
+
+// visiting 是正在输出的外层容器；容器直接或间接包含自己时，
+// 回到外层容器的引用输出为 [...] 或 {...}，不会无限递归
+fn write_value(
+    f: &mut Formatter<'_>,
+    value: &Value,
+    visiting: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::Number(n) => write!(f, "{}", format_number(*n)),
+        Value::String(s) => write!(f, "{}", s),
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Nil => write!(f, "nil"),
+        Value::NativeFunction(_) => write!(f, "<fn>"),
+        Value::Function(function) => {
+            write!(f, "<fn {}>", function.name)
+        }
+        Value::Memoized(memo) => write!(f, "{}", memo.function),
+        Value::Class(class) => write!(f, "{}", class.name),
+        Value::Instance(instance) => write!(f, "{} instance", instance.class.name),
+        Value::List(elements) => {
+            let id = Rc::as_ptr(elements) as *const ();
+            if visiting.contains(&id) {
+                return write!(f, "[...]");
             }
+            visiting.push(id);
+            write!(f, "[")?;
+            for (i, element) in elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_element(f, element, visiting)?;
+            }
+            visiting.pop();
+            write!(f, "]")
         }
+        Value::Map(entries) => {
+            let id = Rc::as_ptr(entries) as *const ();
+            if visiting.contains(&id) {
+                return write!(f, "{{...}}");
+            }
+            visiting.push(id);
+            write!(f, "{{")?;
+            for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "\"{}\": ", key)?;
+                write_element(f, value, visiting)?;
+            }
+            visiting.pop();
+            write!(f, "}}")
+        }
+    }
+}
+
+// 容器里的字符串带引号显示，以区分 "1" 和 1
+fn write_element(
+    f: &mut Formatter<'_>,
+    value: &Value,
+    visiting: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::String(s) => write!(f, "\"{}\"", s),
+        _ => write_value(f, value, visiting),
     }
 }
+// 内置函数可以使用的宿主能力，运行不受信任的脚本时可以关闭
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    // getenv/setenv
+    pub env: bool,
+    // exec
+    pub process: bool,
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Self {
+            env: true,
+            process: true,
+        }
+    }
+    pub fn none() -> Self {
+        Self {
+            env: false,
+            process: false,
+        }
+    }
+}
+
 pub struct Interpreter {
     pub env: Rc<Environment>,
+    pub capabilities: Capabilities,
+    // exec 的超时时间，None 表示不限制
+    pub exec_timeout: Option<Duration>,
+    // setenv 只写入这里，getenv 先查这里再查进程环境变量，不修改真实的进程环境
+    pub(crate) env_overlay: HashMap<String, String>,
+    // 为 true 时检查类型标注（run --check-types），否则标注被忽略
+    pub check_types: bool,
+    // 内存预算（字节，近似值），None 表示不限制（run --max-memory-kb）。创建字符串、列表和环境时
+    // 按大小累计，不扣除已经释放的部分，所以限制的是脚本一共分配了多少内存
+    pub max_memory: Option<usize>,
+    // 已经计入预算的字节数
+    memory_used: usize,
+    // 输出预算（字节），print 和 printf 一共能写出多少，None 表示不限制（run --max-output-kb）
+    pub max_output: Option<usize>,
+    // 已经写出的字节数
+    output_used: usize,
+    // 是否允许 eval/evalStatements 执行字符串形式的代码，默认关闭（run --allow-eval）
+    pub allow_eval: bool,
+    // run --coverage 时为 Some：每行被执行的语句和表达式的次数
+    pub coverage: Option<HashMap<usize, usize>>,
+    // clock/timeNow/timeFormat 等内置函数的时间来源，可以替换为固定时间
+    pub time_source: Box<dyn TimeSource>,
+    // print 和 printf 的输出，默认是标准输出；替换为缓冲区可以捕获脚本的输出
+    pub output: Box<dyn Write>,
+    // explain 命令使用：为 Some 时按求值完成的顺序记录每个表达式和它的值
+    pub trace: Option<Vec<TraceStep>>,
+    // 为 Some 时字符串字面量和拼接结果先在这里查找，内容相同的字符串共用一份存储
+    // （run --intern-strings）。池中的字符串在解释器销毁前不会释放
+    pub strings: Option<HashSet<Rc<String>>>,
+    // 当前正在求值的表达式的嵌套层数，记录到 TraceStep::depth
+    trace_depth: usize,
+}
+
+// 一步求值：depth 是表达式的嵌套层数（最外层为 0），source 是表达式的源码形式
+pub struct TraceStep {
+    pub depth: usize,
+    pub source: String,
+    pub value: Value,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let env = Rc::new(Environment::new(None));
         env.define_natives();
-        Self { env: env }
+        Self::with_env(env)
     }
-    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), RuntimeError> {
-        for stmt in stmts {
-            self.execute(&stmt, &Rc::clone(&self.env))?
+    // 使用宿主准备好的全局环境，不再注册内置函数（需要时由宿主调用 define_natives）
+    // 环境是共享的：多个解释器使用同一个环境时，任何一个对它的修改其他的都能看到；
+    // 不想互相影响时，为每个解释器传入 env.child()，全局 var 定义在子环境中
+    pub fn with_env(env: Rc<Environment>) -> Self {
+        Self {
+            env,
+            capabilities: Capabilities::all(),
+            exec_timeout: None,
+            env_overlay: HashMap::new(),
+            check_types: false,
+            allow_eval: false,
+            max_memory: None,
+            memory_used: 0,
+            max_output: None,
+            output_used: 0,
+            coverage: None,
+            time_source: Box::new(SystemClock),
+            output: Box::new(io::stdout()),
+            trace: None,
+            strings: None,
+            trace_depth: 0,
         }
-        Ok(())
+    }
+    // 当前线程中存活的环境、函数、列表和映射的数量，同一线程中的解释器共享这些计数
+    pub fn stats(&self) -> Stats {
+        stats::current()
+    }
+    // 全局变量的只读快照（按名字排序），供宿主程序和 REPL 列出已定义的名字
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.env.snapshot()
+    }
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), RuntimeError> {
+        let env = Rc::clone(&self.env);
+        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt, &env));
+        location::clear();
+        result
     }
     // 执行语句
-    fn execute(&mut self, stmt: &Stmt, env: &Rc<Environment>) -> Result<(), RuntimeError> {
+    pub(crate) fn execute(&mut self, stmt: &Stmt, env: &Rc<Environment>) -> Result<(), RuntimeError> {
+        let line = stmt.line();
+        if let Some(line) = line {
+            location::enter(Phase::Executing, line);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            if let Some(line) = line {
+                *coverage.entry(line).or_default() += 1;
+            }
+        }
         match stmt {
-            Stmt::Print(expr) => {
+            Stmt::Print(keyword, expr) => {
                 let value = self.evaluate(expr, env)?;
-                println!("{}", value);
-                Ok(())
+                let text = format!("{}\n", value);
+                if !self.charge_output(text.len()) {
+                    return Err(RuntimeError::at("Output limit exceeded.".to_string(), keyword));
+                }
+                // 每次输出后立即 flush，output 是带缓冲的 writer 时也能和读取输入的提示正确交错
+                self.output
+                    .write_all(text.as_bytes())
+                    .and_then(|()| self.output.flush())
+                    .map_err(|error| {
+                        RuntimeError::at(format!("Failed to write output: {}.", error), keyword)
+                    })
             }
+            // 表达式语句只为副作用求值，结果直接丢弃；回显结果是 REPL 的事，不在这里做
             Stmt::Expression(expr) => {
                 let _ = self.evaluate(expr, env)?;
                 Ok(())
             }
-            Stmt::Var(name, initializer) => {
+            Stmt::Var(name, annotation, initializer) => {
                 let val = match initializer {
                     Some(expr) => self.evaluate(expr, env)?,
                     None => Value::Nil,
                 };
+                if self.check_types {
+                    if let Some(annotation) = annotation {
+                        self.check_type(annotation, &val, name.line, || {
+                            format!("variable '{}'", name.lexeme)
+                        })?;
+                    }
+                    env.annotate(&name.lexeme, annotation.as_ref());
+                }
                 env.define(name.lexeme.clone(), Some(val));
                 Ok(())
             }
+            Stmt::Destructure(targets, value) => {
+                let line = targets[0].name.line;
+                let values = self.destructured_values(value, targets.len(), line, env)?;
+                if values.len() != targets.len() {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "Expected {} values to destructure but got {}.",
+                            targets.len(),
+                            values.len()
+                        ),
+                        line,
+                    ));
+                }
+                for (target, value) in targets.iter().zip(values) {
+                    if self.check_types {
+                        if let Some(annotation) = &target.annotation {
+                            self.check_type(annotation, &value, target.name.line, || {
+                                format!("variable '{}'", target.name.lexeme)
+                            })?;
+                        }
+                        env.annotate(&target.name.lexeme, target.annotation.as_ref());
+                    }
+                    env.define(target.name.lexeme.clone(), Some(value));
+                }
+                Ok(())
+            }
             Stmt::Block(stmts) => {
                 self.execute_block(stmts, env)?;
                 Ok(())
             }
-            Stmt::If(condition, then_branch, else_branch) => {
+            Stmt::If(_, condition, then_branch, else_branch) => {
                 let condition = self.evaluate(condition, env)?;
-                if self.is_truthy(&condition) {
+                if ops::is_truthy(&condition) {
                     self.execute(then_branch, env)?;
                 } else if let Some(else_branch) = else_branch {
                     self.execute(else_branch, env)?;
                 }
                 Ok(())
             }
-            Stmt::While(condition, body) => {
+            Stmt::While(_, condition, body) => {
                 let mut condi = self.evaluate(condition, env)?;
-                while self.is_truthy(&condi) {
-                    self.execute(body, env)?;
+                while ops::is_truthy(&condi) {
+                    if !self.execute_loop_body(body, env)? {
+                        break;
+                    }
                     condi = self.evaluate(condition, env)?;
                 }
                 Ok(())
             }
-            Stmt::For(initializer, condition, increment, body) => {
-                match initializer {
-                    Some(stmt) => self.execute(stmt, env)?,
-                    None => (),
+            Stmt::Loop(_, body) => {
+                while self.execute_loop_body(body, env)? {}
+                Ok(())
+            }
+            Stmt::Labeled(_, body) => self.execute(body, env),
+            // 列表按下标遍历元素，字符串遍历字符，映射遍历键；
+            // 实例要实现两种迭代协议之一：
+            // iterator() 返回迭代器，has_next() 为真时调用 next() 取下一个值；
+            // iter() 返回迭代器，反复调用迭代器的 next()，返回 nil 时结束
+            Stmt::ForIn(keyword, name, iterable, body) => match self.evaluate(iterable, env)? {
+                Value::List(elements) => {
+                    let mut index = 0;
+                    loop {
+                        let element = match elements.borrow().get(index) {
+                            Some(element) => element.clone(),
+                            None => break,
+                        };
+                        index += 1;
+                        if !self.execute_for_in_body(name, element, body, env)? {
+                            break;
+                        }
+                    }
+                    Ok(())
+                }
+                Value::String(s) => {
+                    for c in s.chars() {
+                        if !self.execute_for_in_body(name, Value::from(c.to_string()), body, env)? {
+                            break;
+                        }
+                    }
+                    Ok(())
+                }
+                Value::Map(entries) => {
+                    let keys: Vec<String> = entries.borrow().iter().map(|(key, _)| key.clone()).collect();
+                    for key in keys {
+                        if !self.execute_for_in_body(name, Value::from(key), body, env)? {
+                            break;
+                        }
+                    }
+                    Ok(())
+                }
+                Value::Instance(instance) if instance.class.find_method("iterator").is_some() => {
+                    let Value::Instance(iterator) =
+                        self.call_method(&instance, "iterator", keyword.line)?
+                    else {
+                        return Err(RuntimeError::at(
+                            "'iterator' must return an instance with 'has_next' and 'next' methods."
+                                .to_string(),
+                            keyword,
+                        ));
+                    };
+                    loop {
+                        let has_next = self.call_method(&iterator, "has_next", keyword.line)?;
+                        if !ops::is_truthy(&has_next) {
+                            break;
+                        }
+                        let value = self.call_method(&iterator, "next", keyword.line)?;
+                        if !self.execute_for_in_body(name, value, body, env)? {
+                            break;
+                        }
+                    }
+                    Ok(())
+                }
+                Value::Instance(instance) if instance.class.find_method("iter").is_none() => {
+                    Err(RuntimeError::at(
+                        format!(
+                            "{} instance is not iterable: it needs an 'iterator' or 'iter' method.",
+                            instance.class.name
+                        ),
+                        keyword,
+                    ))
+                }
+                Value::Instance(instance) => {
+                    let Value::Instance(iterator) = self.call_method(&instance, "iter", keyword.line)?
+                    else {
+                        return Err(RuntimeError::at(
+                            "'iter' must return an instance with a 'next' method.".to_string(),
+                            keyword,
+                        ));
+                    };
+                    loop {
+                        let value = self.call_method(&iterator, "next", keyword.line)?;
+                        if matches!(value, Value::Nil)
+                            || !self.execute_for_in_body(name, value, body, env)?
+                        {
+                            break;
+                        }
+                    }
+                    Ok(())
+                }
+                other => Err(RuntimeError::at(
+                    format!(
+                        "Can only iterate over lists, strings, maps and instances, got {}.",
+                        other.type_name()
+                    ),
+                    keyword,
+                )),
+            },
+            Stmt::Match(_, scrutinee, arms) => {
+                // 被匹配的值只计算一次，执行第一个匹配的分支
+                let value = self.evaluate(scrutinee, env)?;
+                for (pattern, body) in arms {
+                    if self.matches_pattern(pattern, &value) {
+                        let arm_env = self.new_env(env)?;
+                        if let Pattern::Type(_, Some(name)) = pattern {
+                            arm_env.define(name.lexeme.clone(), Some(value));
+                        }
+                        return self.execute(body, &arm_env);
+                    }
+                }
+                Ok(())
+            }
+            Stmt::Break(_, _, outer) => Err(RuntimeError::Break(*outer)),
+            Stmt::Continue(_, _, outer) => Err(RuntimeError::Continue(*outer)),
+            // 初始化语句在循环自己的作用域中执行，循环变量在循环结束后不可见。
+            // 初始化语句声明了变量时，每次迭代都在新的环境中执行，其中是循环变量的副本，
+            // 闭包捕获的是当次迭代的值；增量在复制到下一次迭代的环境之后执行
+            Stmt::For(_, initializer, condition, increment, body) => {
+                let loop_env = self.new_env(env)?;
+                if let Some(stmt) = initializer {
+                    self.execute(stmt, &loop_env)?;
                 }
+                let names = initializer.as_ref().map_or(vec![], |stmt| stmt.declared_names());
+                let mut env = self.next_iteration(&loop_env, &loop_env, &names)?;
                 match condition {
                     Some(expr) => {
-                        let mut condi = self.evaluate(expr, env)?;
-                        while self.is_truthy(&condi) {
-                            self.execute(body, env)?;
+                        let mut condi = self.evaluate(expr, &env)?;
+                        while ops::is_truthy(&condi) {
+                            if !self.execute_loop_body(body, &env)? {
+                                break;
+                            }
+                            env = self.next_iteration(&loop_env, &env, &names)?;
                             if let Some(increment) = increment {
-                                self.evaluate(increment, env)?;
+                                self.evaluate(increment, &env)?;
                             }
-                            condi = self.evaluate(expr, env)?;
+                            condi = self.evaluate(expr, &env)?;
                         }
                     }
                     None => {
-                        self.execute(body, env)?;
+                        self.execute_loop_body(body, &env)?;
                     }
                 }
                 Ok(())
             }
-            Stmt::Function(name, params, body) => {
-                let function = Value::Function(
+            // 函数声明按源码顺序执行，不做提升：声明之前调用会报 Undefined variable。
+            // 函数体在调用时才读取其中引用的变量，Resolver 让同一个块中的函数名在整个块中可见，
+            // 所以只要调用发生在所有声明之后，互相递归的函数（isEven/isOdd）在全局和块中都可以正常工作
+            Stmt::Function(name, params, return_type, body) => {
+                let function = Value::Function(Rc::new(Function::new(
                     name.lexeme.clone(),
-                    params.clone(),
-                    body.to_vec(),
-                    Rc::clone(&env),
-                );
+                    params.as_slice().into(),
+                    return_type.clone(),
+                    body.as_slice().into(),
+                    Rc::clone(env),
+                    false,
+                )));
                 env.define(name.lexeme.clone(), Some(function));
                 Ok(())
             }
-            Stmt::Return(expr) => {
-                let value = match expr {
+            Stmt::Class(name, superclass, methods) => {
+                let superclass = match superclass {
+                    Some(expr) => match self.evaluate(expr, env)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            return Err(RuntimeError::new(
+                                "Superclass must be a class.".to_string(),
+                                expr.line().unwrap_or(name.line),
+                            ))
+                        }
+                    },
+                    None => None,
+                };
+                // 有父类时，方法的闭包外多一层定义 super 的环境
+                let method_env = match &superclass {
+                    Some(superclass) => {
+                        let env = self.new_env(env)?;
+                        env.define("super".to_string(), Some(Value::Class(Rc::clone(superclass))));
+                        env
+                    }
+                    None => Rc::clone(env),
+                };
+                let mut table = HashMap::new();
+                let mut defaults = Vec::new();
+                for method in methods {
+                    if let Stmt::Var(field, _, initializer) = method {
+                        defaults.push((field.lexeme.clone(), initializer.clone()));
+                    }
+                    if let Stmt::Function(method_name, params, return_type, body) = method {
+                        let function = Function::new(
+                            method_name.lexeme.clone(),
+                            params.as_slice().into(),
+                            return_type.clone(),
+                            body.as_slice().into(),
+                            Rc::clone(&method_env),
+                            method_name.lexeme == "init",
+                        );
+                        table.insert(method_name.lexeme.clone(), Rc::new(function));
+                    }
+                }
+                let class = Class {
+                    name: name.lexeme.clone(),
+                    superclass,
+                    methods: table,
+                    defaults,
+                    closure: Rc::clone(env),
+                };
+                env.define(name.lexeme.clone(), Some(Value::Class(Rc::new(class))));
+                Ok(())
+            }
+            Stmt::Return(_, values) => {
+                let value = match values.first() {
                     Some(expr) => self.evaluate(expr, env)?,
                     None => Value::Nil,
                 };
-                Err(RuntimeError::Return(value))
+                let mut rest = Vec::new();
+                for expr in values.iter().skip(1) {
+                    rest.push(self.evaluate(expr, env)?);
+                }
+                Err(RuntimeError::Return(value, rest))
+            }
+        }
+    }
+    // for-in 的一次迭代：循环变量定义在每次迭代新建的环境中
+    fn execute_for_in_body(
+        &mut self,
+        name: &Token,
+        value: Value,
+        body: &Stmt,
+        env: &Rc<Environment>,
+    ) -> Result<bool, RuntimeError> {
+        let iteration_env = self.new_env(env)?;
+        iteration_env.define(name.lexeme.clone(), Some(value));
+        self.execute_loop_body(body, &iteration_env)
+    }
+    // 调用实例上的无参方法，用于 for-in 的迭代协议
+    fn call_method(
+        &mut self,
+        instance: &Rc<Instance>,
+        name: &str,
+        line: usize,
+    ) -> Result<Value, RuntimeError> {
+        match instance.class.find_method(name) {
+            Some(method) => {
+                let method = method.bind(Value::Instance(Rc::clone(instance)));
+                self.call(Value::Function(method), vec![], line)
             }
-            _ => Err(RuntimeError::new("Not implemented".to_string(), 0)),
+            None => Err(RuntimeError::new(
+                format!("{} instance has no '{}' method.", instance.class.name, name),
+                line,
+            )),
+        }
+    }
+    // 新建 parent 的子环境，计入内存预算
+    fn new_env(&mut self, parent: &Rc<Environment>) -> Result<Rc<Environment>, RuntimeError> {
+        self.charge(std::mem::size_of::<Environment>())?;
+        Ok(parent.child())
+    }
+    // for 循环下一次迭代的环境：loop_env 的子环境，循环变量的值和类型标注从 previous 复制过来。
+    // 没有循环变量时不创建新环境，一直使用 loop_env
+    fn next_iteration(
+        &mut self,
+        loop_env: &Rc<Environment>,
+        previous: &Rc<Environment>,
+        names: &[&Token],
+    ) -> Result<Rc<Environment>, RuntimeError> {
+        if names.is_empty() {
+            return Ok(Rc::clone(loop_env));
+        }
+        let env = self.new_env(loop_env)?;
+        for name in names {
+            env.define(name.lexeme.clone(), previous.get_at(0, name)?);
+            env.annotate(&name.lexeme, previous.annotation(Some(0), &name.lexeme).as_ref());
+        }
+        Ok(env)
+    }
+    // 把一次分配计入内存预算，超出 max_memory 时报错，行号是当前执行的语句
+    fn charge(&mut self, bytes: usize) -> Result<(), RuntimeError> {
+        self.memory_used = self.memory_used.saturating_add(bytes);
+        match self.max_memory {
+            Some(max) if self.memory_used > max => {
+                let line = location::current().map_or(0, |(_, line)| line);
+                Err(RuntimeError::new("Memory limit exceeded.".to_string(), line))
+            }
+            _ => Ok(()),
+        }
+    }
+    // 把一次输出计入输出预算，超出 max_output 时返回 false，这次的输出整个不写，
+    // 之前已经写出的部分保留
+    pub(crate) fn charge_output(&mut self, bytes: usize) -> bool {
+        let used = self.output_used.saturating_add(bytes);
+        if self.max_output.is_some_and(|max| used > max) {
+            return false;
+        }
+        self.output_used = used;
+        true
+    }
+    fn intern(&mut self, s: String) -> Rc<String> {
+        let Some(pool) = &mut self.strings else {
+            return Rc::new(s);
+        };
+        if let Some(existing) = pool.get(&s) {
+            return Rc::clone(existing);
+        }
+        let string = Rc::new(s);
+        pool.insert(Rc::clone(&string));
+        string
+    }
+    // 先设置父类声明的字段，子类声明的同名字段覆盖父类的默认值
+    fn initialize_fields(&mut self, class: &Class, instance: &Instance) -> Result<(), RuntimeError> {
+        if let Some(superclass) = &class.superclass {
+            self.initialize_fields(superclass, instance)?;
+        }
+        for (name, default) in &class.defaults {
+            let value = match default {
+                Some(expr) => self.evaluate(expr, &class.closure)?,
+                None => Value::Nil,
+            };
+            let mut fields = instance.fields.borrow_mut();
+            match fields.iter_mut().find(|(field, _)| field == name) {
+                Some(field) => field.1 = value,
+                None => fields.push((name.clone(), value)),
+            }
+        }
+        Ok(())
+    }
+    // s = s + piece 的快速路径：先把 s 从环境中取出来，字符串没有其他引用时直接在原来的缓冲区
+    // 后面追加，循环拼接字符串不再每次复制整个字符串。只处理 piece 是字面量或变量的情况，
+    // 它们求值没有副作用，先取出 s 不会改变结果。不适用时返回 None，按一般的赋值求值
+    fn append_in_place(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        depth: Option<usize>,
+        env: &Rc<Environment>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let (Expr::Binary(left, op, right), Some(depth)) = (value, depth) else {
+            return Ok(None);
+        };
+        // 驻留的字符串被池引用，类型检查和 explain 的求值记录需要走一般的路径
+        if op.token_type != TokenType::Plus
+            || self.strings.is_some()
+            || self.check_types
+            || self.trace.is_some()
+        {
+            return Ok(None);
+        }
+        match (left.as_ref(), right.as_ref()) {
+            (Expr::Variable(target, Some(target_depth)), Expr::Literal(_) | Expr::Variable(..))
+                if target.lexeme == name.lexeme && *target_depth == depth => {}
+            _ => return Ok(None),
+        }
+        let Ok(Some(Value::String(mut string))) = env.get_at(depth, name) else {
+            return Ok(None);
+        };
+        let Value::String(piece) = self.evaluate(right, env)? else {
+            return Ok(None);
+        };
+        self.charge(piece.len())?;
+        // 去掉环境中的引用；还有其他变量引用同一个字符串时 make_mut 会复制一份，它们不受影响
+        env.assign_at(depth, name, None)?;
+        Rc::make_mut(&mut string).push_str(&piece);
+        let value = Value::String(string);
+        env.assign_at(depth, name, Some(value.clone()))?;
+        Ok(Some(value))
+    }
+    // 执行一次循环体，遇到 break 返回 false；continue 只是提前结束本次循环。
+    // 目标是外层循环时结束当前循环，层数减一后继续向外传递
+    fn execute_loop_body(&mut self, body: &Stmt, env: &Rc<Environment>) -> Result<bool, RuntimeError> {
+        match self.execute(body, env) {
+            Ok(()) | Err(RuntimeError::Continue(0)) => Ok(true),
+            Err(RuntimeError::Break(0)) => Ok(false),
+            Err(RuntimeError::Break(outer)) => Err(RuntimeError::Break(outer - 1)),
+            Err(RuntimeError::Continue(outer)) => Err(RuntimeError::Continue(outer - 1)),
+            Err(error) => Err(error),
         }
     }
     fn execute_block(
         &mut self,
-        stmts: &Vec<Stmt>,
+        stmts: &[Stmt],
         env: &Rc<Environment>,
     ) -> Result<(), RuntimeError> {
-        let env = Rc::new(Environment::new(Some(Rc::clone(env))));
+        let env = self.new_env(env)?;
         for stmt in stmts {
             self.execute(stmt, &env)?;
         }
@@ -173,118 +884,157 @@ impl Interpreter {
     }
     // 计算表达式
     pub fn evaluate(&mut self, expr: &Expr, env: &Rc<Environment>) -> Result<Value, RuntimeError> {
+        if self.trace.is_none() {
+            return self.evaluate_expr(expr, env);
+        }
+        let depth = self.trace_depth;
+        self.trace_depth += 1;
+        let result = self.evaluate_expr(expr, env);
+        self.trace_depth = depth;
+        if let (Some(trace), Ok(value)) = (&mut self.trace, &result) {
+            trace.push(TraceStep {
+                depth,
+                source: printer::pretty(expr),
+                value: value.clone(),
+            });
+        }
+        result
+    }
+    fn evaluate_expr(&mut self, expr: &Expr, env: &Rc<Environment>) -> Result<Value, RuntimeError> {
+        if let Some(coverage) = &mut self.coverage {
+            if let Some(line) = expr.line() {
+                *coverage.entry(line).or_default() += 1;
+            }
+        }
         match expr {
-            Expr::Literal(lit) => {
-                let val = match lit {
-                    Literal::Number(n) => Value::Number(*n),
-                    Literal::String(s) => Value::String(s.to_string()),
-                    Literal::Bool(b) => Value::Bool(*b),
-                    Literal::Nil => Value::Nil,
-                };
-                Ok(val)
+            // 已经驻留的字面量直接共用，不再复制
+            Expr::Literal(Literal::String(s)) => {
+                let existing = self.strings.as_ref().and_then(|pool| pool.get(s).cloned());
+                Ok(Value::String(existing.unwrap_or_else(|| self.intern(s.clone()))))
             }
-            Expr::Grouping(expr) => self.evaluate(expr, env),
-            Expr::Unary(op, expr) => {
-                let right = self.evaluate(expr, env)?;
-                match op.token_type {
-                    TokenType::Minus => {
-                        if let Value::Number(n) = right {
-                            Ok(Value::Number(-n))
-                        } else {
-                            Err(RuntimeError::new(
-                                "Invalid operand for unary operator".to_string(),
-                                op.line,
-                            ))
+            Expr::Literal(lit) => Ok(self.literal_value(lit)),
+            Expr::Grouping(_) | Expr::Unary(_, _) => {
+                // 括号和一元运算符组成的链用循环求值：先剥开到最内层，再从里向外应用运算符
+                let mut operators = vec![];
+                let mut inner = expr;
+                loop {
+                    match inner {
+                        Expr::Grouping(next) => inner = next,
+                        Expr::Unary(op, next) => {
+                            operators.push(op);
+                            inner = next;
                         }
+                        _ => break,
                     }
-                    TokenType::Bang => Ok(Value::Bool(!self.is_truthy(&right))),
-                    _ => Ok(Value::String("Not implemented".to_string())),
                 }
+                let mut value = self.evaluate(inner, env)?;
+                while let Some(op) = operators.pop() {
+                    value = ops::unary(op.token_type, &value)
+                        .map_err(|error| RuntimeError::at(error.message, op))?;
+                }
+                Ok(value)
             }
             Expr::Binary(left, op, right) => {
                 let left = self.evaluate(left, env)?;
-
                 let right = self.evaluate(right, env)?;
-
-                match op.token_type {
-                    TokenType::Plus => {
-                        if self.is_number(&left) && self.is_number(&right) {
-                            Ok(Value::Number(
-                                self.get_number(&left) + self.get_number(&right),
-                            ))
-                        } else if self.is_string(&left) && self.is_string(&right) {
-                            Ok(Value::String(format!(
-                                "{}{}",
-                                self.get_string(&left),
-                                self.get_string(&right)
-                            )))
-                        } else {
-                            Err(RuntimeError::new(
-                                "Operands must be two numbers or two strings.".to_string(),
-                                op.line,
-                            ))
-                        }
+                // 字符串拼接的结果计入内存预算，需要时放进驻留池
+                if let (Value::String(l), Value::String(r)) = (&left, &right) {
+                    if op.token_type == TokenType::Plus {
+                        self.charge(l.len() + r.len())?;
                     }
-                    TokenType::Minus => {
-                        if self.is_number(&left) && self.is_number(&right) {
-                            Ok(Value::Number(
-                                self.get_number(&left) - self.get_number(&right),
-                            ))
-                        } else {
-                            Err(RuntimeError::new(
-                                "Operands must be numbers.".to_string(),
-                                op.line,
-                            ))
-                        }
-                    }
-                    TokenType::Star => {
-                        if self.is_number(&left) && self.is_number(&right) {
-                            Ok(Value::Number(
-                                self.get_number(&left) * self.get_number(&right),
-                            ))
-                        } else {
-                            Err(RuntimeError::new(
-                                "Operands must be numbers.".to_string(),
-                                op.line,
-                            ))
-                        }
+                }
+                match ops::binary(op.token_type, &left, &right) {
+                    Ok(Value::String(joined)) if self.strings.is_some() => {
+                        Ok(Value::String(self.intern(Rc::unwrap_or_clone(joined))))
                     }
-                    TokenType::Slash => {
-                        if self.is_number(&left) && self.is_number(&right) {
-                            let right_number = self.get_number(&right);
-                            if right_number == 0.0 {
-                                Err(RuntimeError::new("Division by zero.".to_string(), op.line))
-                            } else {
-                                Ok(Value::Number(
-                                    self.get_number(&left) / self.get_number(&right),
-                                ))
-                            }
-                        } else {
-                            Err(RuntimeError::new(
-                                "Operands must be numbers.".to_string(),
-                                op.line,
-                            ))
+                    Ok(value) => Ok(value),
+                    Err(error) => Err(RuntimeError::at(error.message, op)),
+                }
+            }
+            Expr::Variable(name, depth) => {
+                // 已解析的变量直接跳到对应的作用域，不再沿着环境链逐层查找；每个名字只有一个静态绑定，
+                // 函数体引用块中之后才声明的变量时解析到全局，全局没有就报错。没有经过 Resolver 的
+                // 语法树（depth 为 None）沿环境链按名字查找
+                let value = match depth {
+                    Some(depth) => env.get_at(*depth, name)?,
+                    None => env.get(name)?,
+                };
+                Ok(value.unwrap())
+            }
+            Expr::This(keyword, depth) => {
+                let value = match depth {
+                    Some(depth) => env.get_at(*depth, keyword)?,
+                    None => env.get(keyword)?,
+                };
+                Ok(value.unwrap_or(Value::Nil))
+            }
+            // super 所在环境的内层就是绑定 this 的环境
+            Expr::Super(keyword, method, depth) => {
+                let depth = depth.unwrap_or_default();
+                let superclass = env.get_at(depth, keyword)?;
+                let this = Token::new(TokenType::This, "this".to_string(), None, keyword.line);
+                let instance = env.get_at(depth.saturating_sub(1), &this)?;
+                match (superclass, instance) {
+                    (Some(Value::Class(superclass)), Some(instance)) => {
+                        match superclass.find_method(&method.lexeme) {
+                            Some(function) => Ok(Value::Function(function.bind(instance))),
+                            None => Err(RuntimeError::at(
+                                format!("Undefined property '{}'.", method.lexeme),
+                                method,
+                            )),
                         }
                     }
-                    TokenType::Greater => self.compare_values(&left, &right, |l, r| l > r),
-                    TokenType::GreaterEqual => self.compare_values(&left, &right, |l, r| l >= r),
-                    TokenType::Less => self.compare_values(&left, &right, |l, r| l < r),
-                    TokenType::LessEqual => self.compare_values(&left, &right, |l, r| l <= r),
-                    TokenType::EqualEqual => {
-                        let result = self.compare_equality(&left, &right);
-                        Ok(Value::Bool(result))
-                    }
-                    TokenType::BangEqual => {
-                        let result = self.compare_equality(&left, &right);
-                        Ok(Value::Bool(!result))
-                    }
-                    _ => Err(RuntimeError::new("Unimplemented".to_string(), op.line)),
+                    _ => Err(RuntimeError::at(
+                        "Can't use 'super' outside of a class.".to_string(),
+                        keyword,
+                    )),
+                }
+            }
+            // 匿名函数在求值时捕获当前环境，和函数声明一样形成闭包
+            Expr::Lambda(_, params, return_type, body) => {
+                Ok(Value::Function(Rc::new(Function::new(
+                    "anonymous".to_string(),
+                    params.as_slice().into(),
+                    return_type.clone(),
+                    body.as_slice().into(),
+                    Rc::clone(env),
+                    false,
+                ))))
+            }
+            Expr::Set(object, name, value) => {
+                let Value::Instance(instance) = self.evaluate(object, env)? else {
+                    return Err(RuntimeError::at(
+                        "Only instances have fields.".to_string(),
+                        name,
+                    ));
+                };
+                let value = self.evaluate(value, env)?;
+                let mut fields = instance.fields.borrow_mut();
+                match fields.iter_mut().find(|(field, _)| *field == name.lexeme) {
+                    Some(field) => field.1 = value.clone(),
+                    None => fields.push((name.lexeme.clone(), value.clone())),
                 }
+                Ok(value)
             }
-            Expr::Variable(name) => Ok(env.get(name)?.unwrap()),
-            Expr::Assign(name, expr) => {
+            // 赋值表达式的值就是赋给变量的值；赋值右结合，a = b = 1 先完成 b = 1 再赋给 a
+            // 变量不存在时由 Environment::assign 报 "Undefined variable"，行号是变量所在行
+            Expr::Assign(name, expr, depth) => {
+                if let Some(value) = self.append_in_place(name, expr, *depth, env)? {
+                    return Ok(value);
+                }
                 let value = self.evaluate(expr, env)?;
-                env.assign(name, Some(value.clone()))?;
+                if self.check_types {
+                    if let Some(annotation) = env.annotation(*depth, &name.lexeme) {
+                        self.check_type(&annotation, &value, name.line, || {
+                            format!("variable '{}'", name.lexeme)
+                        })?;
+                    }
+                }
+                // 和 Expr::Variable 一样：已解析的赋值只写 depth 那一层，没有经过 Resolver 时按名字查找
+                match depth {
+                    Some(depth) => env.assign_at(*depth, name, Some(value.clone()))?,
+                    None => env.assign(name, Some(value.clone()))?,
+                }
                 Ok(value)
             }
             Expr::Logical(left, op, right) => {
@@ -294,119 +1044,355 @@ impl Interpreter {
                 match op.token_type {
                     // right  不能提前计算，可能包含Assign 表达式， 只有在left 是false时，才计算right
                     TokenType::Or => {
-                        if self.is_truthy(&left_expr) {
+                        if ops::is_truthy(&left_expr) {
                             return Ok(left_expr);
                         }
                         Ok(self.evaluate(right, env)?)
                     }
                     // right  不能提前计算，可能包含Assign 表达式， 只有在left 是true时，才计算right
                     TokenType::And => {
-                        if !self.is_truthy(&left_expr) {
+                        if !ops::is_truthy(&left_expr) {
                             return Ok(left_expr);
                         }
                         Ok(self.evaluate(right, env)?)
                     }
-                    _ => Err(RuntimeError::new("Not implemented".to_string(), op.line)),
+                    // 不短路：两边总是都求值，结果是布尔值
+                    TokenType::Xor => {
+                        let right_expr = self.evaluate(right, env)?;
+                        Ok(Value::Bool(
+                            ops::is_truthy(&left_expr) != ops::is_truthy(&right_expr),
+                        ))
+                    }
+                    _ => Err(RuntimeError::at("Not implemented".to_string(), op)),
                 }
             }
-            Expr::Call(callee, paren, arguments) => {
-                let val = self.evaluate(callee, &env)?;
-                match val {
-                    Value::NativeFunction(func) => {
-                        if !arguments.is_empty() {
-                            return Err(RuntimeError::new(
-                                "Native function Expected 0 arguments.".to_string(),
-                                paren.line,
-                            ));
-                        }
-                        Ok(func())
-                    }
-                    Value::Function(_, params, body, closure) => {
-                        if arguments.len() != params.len() {
-                            return Err(RuntimeError::new(
-                                format!(
-                                    "Expected {} arguments but got {}. ",
-                                    params.len(),
-                                    arguments.len()
-                                ),
-                                paren.line,
-                            ));
-                        }
-                        let func_env = Rc::new(Environment::new(Some(closure.clone())));
-                        for (param, arg) in params.iter().zip(arguments) {
-                            // 这里花费了很多时间。。。
-                            // 实参的值 必须先计算（基于函数调用时的环境），才能赋值给函数的环境
-                            let value = self.evaluate(arg, &env)?;
-                            func_env.define(param.lexeme.clone(), Some(value));
-                        }
-                        let result = self.execute_block(&body, &func_env);
+            Expr::List(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element, env)?);
+                }
+                self.charge(values.len() * std::mem::size_of::<Value>())?;
+                Ok(Value::list(values))
+            }
+            // 属性读取、下标和调用组成的链在 evaluate_link 中求值，?. 遇到 nil 时整条链的结果是 nil
+            Expr::Get(..) | Expr::Index(..) | Expr::Call(..) => {
+                Ok(self.evaluate_link(expr, env)?.unwrap_or(Value::Nil))
+            }
+        }
+    }
 
-                        match result {
-                            Ok(_) => Ok(Value::Nil),
-                            Err(RuntimeError::Return(val)) => Ok(val),
-                            Err(e) => Err(e),
+    // 求值链中的一环；返回 None 表示前面的 ?.、?[ 或 ?( 遇到了 nil，
+    // 后面的环节（包括下标和实参）都不再求值
+    fn evaluate_link(
+        &mut self,
+        expr: &Expr,
+        env: &Rc<Environment>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let value = match expr {
+            Expr::Get(object, name, optional) => match self.evaluate_link(object, env)? {
+                None => return Ok(None),
+                Some(Value::Nil) if *optional => return Ok(None),
+                Some(Value::Instance(instance)) => self.get_property(&instance, name),
+                _ => Err(RuntimeError::at(
+                    "Only instances have properties.".to_string(),
+                    name,
+                )),
+            },
+            Expr::Index(container, bracket, index, optional) => {
+                let container = match self.evaluate_link(container, env)? {
+                    None => return Ok(None),
+                    Some(Value::Nil) if *optional => return Ok(None),
+                    Some(container) => container,
+                };
+                let index = self.evaluate(index, env)?;
+                match (&container, index) {
+                    (Value::List(elements), Value::Number(i)) if i.fract() == 0.0 => {
+                        let elements = elements.borrow();
+                        if i < 0.0 || i as usize >= elements.len() {
+                            return Err(RuntimeError::at(
+                                "Index out of bounds.".to_string(),
+                                bracket,
+                            ));
                         }
+                        Ok(elements[i as usize].clone())
                     }
-                    _ => Err(RuntimeError::new(
-                        "Can only call functions.".to_string(),
-                        paren.line,
+                    (Value::List(_), _) => Err(RuntimeError::at(
+                        "Index must be an integer.".to_string(),
+                        bracket,
+                    )),
+                    // 不存在的键返回 nil
+                    (Value::Map(entries), Value::String(key)) => Ok(entries
+                        .borrow()
+                        .iter()
+                        .find(|(k, _)| **k == *key)
+                        .map_or(Value::Nil, |(_, value)| value.clone())),
+                    (Value::Map(_), _) => Err(RuntimeError::at(
+                        "Map keys must be strings.".to_string(),
+                        bracket,
+                    )),
+                    _ => Err(RuntimeError::at(
+                        "Only lists and maps can be indexed.".to_string(),
+                        bracket,
                     )),
                 }
             }
-            _ => {
-                panic!("Not implemented")
+            Expr::Call(callee, paren, arguments, optional) => {
+                match self.callee_and_args(callee, arguments, *optional, env)? {
+                    None => return Ok(None),
+                    Some((callee, args)) => self.call(callee, args, paren.line),
+                }
             }
-        }
+            _ => self.evaluate(expr, env),
+        };
+        value.map(Some)
     }
 
-    fn is_truthy(&self, val: &Value) -> bool {
-        match val {
-            Value::Bool(b) => *b,
-            Value::Nil => false,
-            _ => true,
+    // 调用表达式的被调用者和实参；?( 遇到 nil 时返回 None
+    fn callee_and_args(
+        &mut self,
+        callee: &Expr,
+        arguments: &[Expr],
+        optional: bool,
+        env: &Rc<Environment>,
+    ) -> Result<Option<(Value, Vec<Value>)>, RuntimeError> {
+        let callee = match self.evaluate_link(callee, env)? {
+            None => return Ok(None),
+            Some(Value::Nil) if optional => return Ok(None),
+            Some(callee) => callee,
+        };
+        // 实参的值必须先计算（基于函数调用时的环境），才能赋值给函数的环境
+        let mut args = Vec::with_capacity(arguments.len());
+        for arg in arguments {
+            args.push(self.evaluate(arg, env)?);
         }
+        Ok(Some((callee, args)))
     }
-    fn is_number(&self, val: &Value) -> bool {
-        matches!(val, Value::Number(_))
-    }
-    fn is_string(&self, val: &Value) -> bool {
-        matches!(val, Value::String(_))
+
+    // var q, r = ...; 右边的各个值：直接调用的用户函数用 return q, r; 返回多个值时就是这些值，
+    // 否则右边的值必须是列表，值就是列表的元素
+    fn destructured_values(
+        &mut self,
+        expr: &Expr,
+        count: usize,
+        line: usize,
+        env: &Rc<Environment>,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let (value, rest) = match expr {
+            Expr::Call(callee, paren, arguments, optional) => {
+                match self.callee_and_args(callee, arguments, *optional, env)? {
+                    None => (Value::Nil, Vec::new()),
+                    Some((Value::Function(function), args)) => {
+                        self.call_user_function(&function, args, paren.line)?
+                    }
+                    Some((callee, args)) => (self.call(callee, args, paren.line)?, Vec::new()),
+                }
+            }
+            _ => (self.evaluate(expr, env)?, Vec::new()),
+        };
+        if !rest.is_empty() {
+            let mut values = vec![value];
+            values.extend(rest);
+            return Ok(values);
+        }
+        match value {
+            Value::List(values) => Ok(values.borrow().clone()),
+            other => Err(RuntimeError::new(
+                format!(
+                    "Can only destructure a list into {} variables, got {}.",
+                    count,
+                    other.type_name()
+                ),
+                line,
+            )),
+        }
     }
 
-    fn get_number(&self, val: &Value) -> f64 {
-        match val {
-            Value::Number(n) => *n,
-            _ => panic!("Not a number"),
+    pub(crate) fn call(&mut self, callee: Value, args: Vec<Value>, line: usize) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::NativeFunction(native) => {
+                if !native.arity.contains(&args.len()) {
+                    let expected = if native.arity.start() == native.arity.end() {
+                        native.arity.start().to_string()
+                    } else {
+                        format!("{} to {}", native.arity.start(), native.arity.end())
+                    };
+                    return Err(RuntimeError::new(
+                        format!("Expected {} arguments but got {}.", expected, args.len()),
+                        line,
+                    ));
+                }
+                (native.func)(self, &args, line)
+            }
+            Value::Function(function) => {
+                self.call_user_function(&function, args, line).map(|(value, _)| value)
+            }
+            Value::Class(class) => {
+                let instance = Rc::new(Instance {
+                    class: Rc::clone(&class),
+                    fields: RefCell::new(vec![]),
+                });
+                self.initialize_fields(&class, &instance)?;
+                let instance = Value::Instance(instance);
+                match class.find_method("init") {
+                    Some(init) => {
+                        self.call(Value::Function(init.bind(instance.clone())), args, line)?;
+                    }
+                    None if !args.is_empty() => {
+                        return Err(RuntimeError::new(
+                            format!("Expected 0 arguments but got {}.", args.len()),
+                            line,
+                        ))
+                    }
+                    None => {}
+                }
+                Ok(instance)
+            }
+            Value::Memoized(memo) => {
+                let key = memo::key(&args).map_err(|type_name| {
+                    RuntimeError::new(
+                        format!("memoize: can't use a {} as a cache key.", type_name),
+                        line,
+                    )
+                })?;
+                if let Some(value) = memo.cache.borrow().get(&key) {
+                    memo.hits.set(memo.hits.get() + 1);
+                    return Ok(value.clone());
+                }
+                memo.misses.set(memo.misses.get() + 1);
+                let value = self.call(memo.function.clone(), args, line)?;
+                memo.cache.borrow_mut().insert(key, value.clone());
+                Ok(value)
+            }
+            _ => Err(RuntimeError::new(
+                "Can only call functions.".to_string(),
+                line,
+            )),
         }
     }
-    fn get_string(&self, val: &Value) -> String {
-        match val {
-            Value::String(s) => s.to_string(),
-            _ => panic!("Not a string"),
+
+    // 调用用户定义的函数，返回 return 的第一个值和其余的值；返回值类型只检查第一个值
+    fn call_user_function(
+        &mut self,
+        function: &Function,
+        args: Vec<Value>,
+        line: usize,
+    ) -> Result<(Value, Vec<Value>), RuntimeError> {
+        let Function {
+            name,
+            params,
+            return_type,
+            body,
+            closure,
+            is_initializer,
+            ..
+        } = function;
+        if args.len() != params.len() {
+            return Err(RuntimeError::new(
+                format!(
+                    "Expected {} arguments but got {}. ",
+                    params.len(),
+                    args.len()
+                ),
+                line,
+            ));
+        }
+        let func_env = self.new_env(closure)?;
+        for (param, value) in params.iter().zip(args) {
+            if self.check_types {
+                if let Some(annotation) = &param.annotation {
+                    self.check_type(annotation, &value, line, || {
+                        format!("argument '{}' of '{}'", param.name.lexeme, name)
+                    })?;
+                }
+                func_env.annotate(&param.name.lexeme, param.annotation.as_ref());
+            }
+            func_env.define(param.name.lexeme.clone(), Some(value));
+        }
+        let (result, rest) = match self.execute_block(body, &func_env) {
+            Ok(_) => (Value::Nil, Vec::new()),
+            Err(RuntimeError::Return(value, rest)) => (value, rest),
+            Err(e) => return Err(e),
+        };
+        // init 总是返回实例本身，包括其中提前的 return;
+        let result = if *is_initializer {
+            closure.values.borrow().get("this").cloned().flatten().unwrap_or(result)
+        } else {
+            result
+        };
+        if self.check_types {
+            if let Some(annotation) = return_type {
+                self.check_type(annotation, &result, line, || {
+                    format!("return value of '{}'", name)
+                })?;
+            }
         }
+        Ok((result, rest))
     }
-    fn compare_values<F: Fn(f64, f64) -> bool>(
-        &self,
-        left: &Value,
-        right: &Value,
-        compare: F,
-    ) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(compare(*l, *r))),
-            _ => Err(RuntimeError::new(
-                "Operands must be numbers.".to_string(),
-                0,
+
+    // 先找字段，再找方法；方法取出时绑定到这个实例
+    fn get_property(&self, instance: &Rc<Instance>, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some((_, value)) = instance
+            .fields
+            .borrow()
+            .iter()
+            .find(|(field, _)| *field == name.lexeme)
+        {
+            return Ok(value.clone());
+        }
+        match instance.class.find_method(&name.lexeme) {
+            Some(method) => Ok(Value::Function(
+                method.bind(Value::Instance(Rc::clone(instance))),
+            )),
+            None => Err(RuntimeError::at(
+                format!("Undefined property '{}'.", name.lexeme),
+                name,
             )),
         }
     }
 
-    fn compare_equality(&self, left: &Value, right: &Value) -> bool {
-        match (left, right) {
-            (Value::Number(l), Value::Number(r)) => (l - r).abs() < f64::EPSILON,
-            (Value::String(l), Value::String(r)) => l == r,
-            (Value::Bool(l), Value::Bool(r)) => l == r,
-            (Value::Nil, Value::Nil) => true,
-            _ => false,
+    fn literal_value(&self, lit: &Literal) -> Value {
+        match lit {
+            Literal::Number(n) => Value::Number(*n),
+            Literal::String(s) => Value::String(Rc::new(s.clone())),
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Nil => Value::Nil,
+        }
+    }
+    fn matches_pattern(&self, pattern: &Pattern, value: &Value) -> bool {
+        match pattern {
+            Pattern::Wildcard => true,
+            Pattern::Literal(lit) => ops::equals(&self.literal_value(lit), value),
+            Pattern::Type(type_name, _) => value.type_name() == type_name.lexeme,
+        }
+    }
+    // what 描述被检查的对象，例如 "variable 'x'"，只在出错时才生成
+    fn check_type(
+        &self,
+        annotation: &Token,
+        value: &Value,
+        line: usize,
+        what: impl FnOnce() -> String,
+    ) -> Result<(), RuntimeError> {
+        let expected = annotation.lexeme.as_str();
+        let matches = match expected {
+            "any" => true,
+            "number" | "string" | "bool" | "nil" | "function" | "list" | "map" | "class" => {
+                value.type_name() == expected
+            }
+            // 其他名字都当作类名：值必须是这个类或其子类的实例
+            _ => matches!(value, Value::Instance(instance) if instance.class.is_a(expected)),
+        };
+        if matches {
+            return Ok(());
         }
+        Err(RuntimeError::new(
+            format!(
+                "Expected {} to be {} but got {}.",
+                what(),
+                expected,
+                value.type_name()
+            ),
+            line,
+        ))
     }
 }
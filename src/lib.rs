@@ -0,0 +1,16 @@
+pub mod conformance;
+pub mod convert;
+pub mod coverage;
+pub mod diagnostics;
+pub mod embed;
+pub mod environment;
+pub mod interpreter;
+pub mod limits;
+pub mod location;
+pub mod natives;
+pub mod number;
+pub mod ops;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod stats;
@@ -0,0 +1,22 @@
+// 扫描和解析的规模限制。几百 MB 的 1+1+1+... 在生成 token 和语法树时会耗尽内存，
+// 超过限制时报告 diagnostics::LIMIT_EXCEEDED 错误并停止，而不是继续分配。
+// 默认值足够宽松，正常的程序不会碰到（run/check 的 --max-source-kb、--max-tokens、--max-nodes）
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // 源码的字节数
+    pub max_source_bytes: usize,
+    // token 数，不含最后的 EOF
+    pub max_tokens: usize,
+    // 语法树的节点数，按解析的语句和操作数计算，是近似值
+    pub max_nodes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_source_bytes: 64 * 1024 * 1024,
+            max_tokens: 16 * 1024 * 1024,
+            max_nodes: 16 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::cell::{Cell, RefCell};
+
+// 记录当前线程正在处理的脚本位置，内部 panic 时由 main 中的 panic hook 读取，
+// 给出出错时对应的 Lox 源码位置。只是设置 Cell，开销很小
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Parsing,
+    Executing,
+}
+
+thread_local! {
+    static SCRIPT: RefCell<Option<String>> = const { RefCell::new(None) };
+    static CURRENT: Cell<Option<(Phase, usize)>> = const { Cell::new(None) };
+}
+
+pub fn set_script(name: &str) {
+    SCRIPT.with(|script| *script.borrow_mut() = Some(name.to_string()));
+}
+
+pub fn script() -> Option<String> {
+    SCRIPT.with(|script| script.borrow().clone())
+}
+
+pub fn enter(phase: Phase, line: usize) {
+    CURRENT.with(|current| current.set(Some((phase, line))));
+}
+
+pub fn current() -> Option<(Phase, usize)> {
+    CURRENT.with(Cell::get)
+}
+
+// 一次解析或执行结束后清除，避免把上一次的位置报告给下一次
+pub fn clear() {
+    CURRENT.with(|current| current.set(None));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_is_per_thread_and_cleared() {
+        set_script("main.lox");
+        enter(Phase::Parsing, 3);
+        enter(Phase::Executing, 7);
+        assert_eq!(current(), Some((Phase::Executing, 7)));
+        assert_eq!(script().as_deref(), Some("main.lox"));
+        std::thread::spawn(|| assert_eq!((current(), script()), (None, None)))
+            .join()
+            .unwrap();
+        clear();
+        assert_eq!(current(), None);
+        assert_eq!(script().as_deref(), Some("main.lox"));
+    }
+}
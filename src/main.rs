@@ -1,76 +1,620 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::io::{self, IsTerminal, Write};
 use std::fs;
-use std::io::{self, Write};
 use std::rc::Rc;
+use std::time::Instant;
 
-mod interpreter;
-mod parser;
-mod scanner;
-mod environment;
+use codecrafters_interpreter::{
+    conformance, coverage,
+    diagnostics::{self, snippet, Diagnostic, Diagnostics, Severity},
+    interpreter::{self, Value},
+    limits::Limits,
+    location::{self, Phase},
+    natives::json,
+    parser, resolver, scanner, stats,
+};
 
 fn read_file_contents(filename: &str) -> String {
+    read_source(filename, Limits::default().max_source_bytes)
+}
+
+// 超过 max_bytes 的文件不读入内存，报错并退出（run/check 按 --max-source-kb，其他命令使用默认限制）
+fn read_source(filename: &str, max_bytes: usize) -> String {
+    if fs::metadata(filename).is_ok_and(|metadata| metadata.len() > max_bytes as u64) {
+        eprintln!("{}: Source too large: limit is {} bytes.", filename, max_bytes);
+        std::process::exit(Status::LimitExceeded.exit_code());
+    }
     fs::read_to_string(filename).unwrap_or_else(|_| {
-        writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
+        eprintln!("Failed to read file {}", filename);
         String::new()
     })
 }
 
+// 解析器和解释器都是递归实现的，在栈更大的线程里运行，深层嵌套的表达式才不会栈溢出
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    install_panic_hook();
+    let worker = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_command)
+        .expect("Failed to spawn interpreter thread");
+    if worker.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+// 带值的参数，例如 --coverage report.txt；缺少值时报错退出
+fn flag_value<'a>(flags: &'a [String], name: &str) -> Option<&'a str> {
+    let index = flags.iter().position(|flag| flag == name)?;
+    match flags.get(index + 1) {
+        Some(value) => Some(value),
+        None => {
+            eprintln!("Missing value for {}", name);
+            std::process::exit(64);
+        }
+    }
+}
+
+// run/check 的 --max-source-kb、--max-tokens、--max-nodes，没有给出的使用默认值
+fn limits(flags: &[String]) -> Limits {
+    let value = |name: &str, default: usize| match flag_value(flags, name) {
+        None => default,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid value for {}: {}", name, value);
+            std::process::exit(64);
+        }),
+    };
+    let defaults = Limits::default();
+    let source_kb = value("--max-source-kb", defaults.max_source_bytes / 1024);
+    Limits {
+        max_source_bytes: source_kb.saturating_mul(1024),
+        max_tokens: value("--max-tokens", defaults.max_tokens),
+        max_nodes: value("--max-nodes", defaults.max_nodes),
+    }
+}
+
+// 一次检查或运行的结果，决定退出码和 run --json 输出的 status
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    ScanError,
+    ParseError,
+    // 有警告且指定了 --deny-warnings
+    WarningsDenied,
+    RuntimeError,
+    // 源码、token 数或语法树超出规模限制
+    LimitExceeded,
+}
+
+impl Status {
+    fn exit_code(self) -> i32 {
+        match self {
+            Status::Ok => 0,
+            Status::ScanError | Status::ParseError | Status::WarningsDenied => 65,
+            Status::RuntimeError => 70,
+            Status::LimitExceeded => 66,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::ScanError => "scan_error",
+            Status::ParseError => "parse_error",
+            Status::WarningsDenied => "warnings_denied",
+            Status::RuntimeError => "runtime_error",
+            Status::LimitExceeded => "limit_exceeded",
+        }
+    }
+}
+
+// 解析并通过 Resolver 检查，输出警告；解析失败，或者有警告且指定了 --deny-warnings 时以 65 退出
+fn check_file(filename: &str, flags: &[String], plain: bool) -> Vec<parser::stmt::Stmt> {
+    let diagnostics = Diagnostics::new(filename, plain);
+    let source = read_source(filename, limits(flags).max_source_bytes);
+    let (result, messages) = check_source(&source, flags);
+    for message in &messages {
+        diagnostics.report(message);
+    }
+    result.unwrap_or_else(|status| std::process::exit(status.exit_code()))
+}
+
+// 在 diagnostics::check_source 的基础上按 --deny-warnings 决定是否通过，失败时给出状态；
+// 不输出也不退出，由调用者决定怎样报告
+fn check_source(
+    source: &str,
+    flags: &[String],
+) -> (Result<Vec<parser::stmt::Stmt>, Status>, Vec<Diagnostic>) {
+    let mut resolver = resolver::Resolver::new();
+    resolver.warn_shadow_native = flags.iter().any(|flag| flag == "--warn-shadow-native");
+    let (diagnostics, stmts) = diagnostics::check_source_with(source, resolver, limits(flags));
+    let Some(stmts) = stmts else {
+        // 超出规模限制的错误可能排在其他扫描错误之后
+        let has = |code| diagnostics.iter().any(|diagnostic| diagnostic.code == code);
+        let status = if has(diagnostics::LIMIT_EXCEEDED) {
+            Status::LimitExceeded
+        } else if diagnostics.first().is_some_and(|d| d.code == diagnostics::SCAN_ERROR) {
+            Status::ScanError
+        } else {
+            Status::ParseError
+        };
+        return (Err(status), diagnostics);
+    };
+    if !diagnostics.is_empty() && flags.iter().any(|flag| flag == "--deny-warnings") {
+        return (Err(Status::WarningsDenied), diagnostics);
+    }
+    (Ok(stmts), diagnostics)
+}
+
+// 交互模式：逐行读入并执行，出错时输出错误后继续，全局环境在各行之间保留
+// 只有一个表达式语句的行会输出表达式的值
+fn repl(plain: bool) {
+    location::set_script("<repl>");
+    let diagnostics = Diagnostics::new("<stdin>", plain);
+    let mut interpreter = interpreter::Interpreter::new();
+    let interactive = io::stdin().is_terminal();
+    let mut line = String::new();
+    loop {
+        if interactive {
+            print!("> ");
+            let _ = io::stdout().flush();
+        }
+        line.clear();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let mut s = scanner::Scanner::new(&line);
+        let (tokens, errors) = s.scan_tokens();
+        if !errors.is_empty() {
+            for error in errors {
+                diagnostics.report(error);
+            }
+            continue;
+        }
+        let mut stmts = match parser::Parser::new(tokens).parse() {
+            Ok(stmts) => parser::lower::strip_groups_stmts(stmts),
+            Err(errors) => {
+                for error in errors {
+                    diagnostics.report(error);
+                }
+                continue;
+            }
+        };
+        let mut resolver = resolver::Resolver::new();
+        for warning in resolver.resolve(&mut stmts) {
+            diagnostics.report(warning);
+        }
+        if !resolver.errors().is_empty() {
+            for error in resolver.errors() {
+                diagnostics.report(error);
+            }
+            continue;
+        }
+        let result = match stmts.as_slice() {
+            [parser::stmt::Stmt::Expression(expr)] => {
+                let env = Rc::clone(&interpreter.env);
+                interpreter.evaluate(expr, &env).map(|value| println!("{}", value))
+            }
+            _ => interpreter.interpret(stmts),
+        };
+        if let Err(error) = result {
+            diagnostics.report(error);
+        }
+    }
+}
+
+// 输出表达式的语法树，再按求值完成的顺序逐步列出每个子表达式和它的值，
+// 缩进表示嵌套层数，最后一行是结果；短路求值中没有计算的部分不会出现
+fn explain(source: &str, plain: bool) {
+    location::set_script("<explain>");
+    let diagnostics = Diagnostics::new("<explain>", plain);
+    let mut s = scanner::Scanner::new(source);
+    let (tokens, errors) = s.scan_tokens();
+    if !errors.is_empty() {
+        for error in errors {
+            diagnostics.report(error);
+        }
+        std::process::exit(65);
+    }
+    let expr = match parser::Parser::new(tokens).parse_expr() {
+        Ok(expr) => expr,
+        Err(error) => {
+            diagnostics.report(error);
+            std::process::exit(65);
+        }
+    };
+    println!("{}", expr);
+    let ast = parser::lower::strip_groups(expr);
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.trace = Some(vec![]);
+    let result = interpreter.evaluate(&ast, &Rc::clone(&interpreter.env));
+    for step in interpreter.trace.take().unwrap_or_default() {
+        println!("{}{} => {}", "  ".repeat(step.depth + 1), step.source, step.value);
+    }
+    match result {
+        Ok(value) => println!("Result: {}", value),
+        Err(error) => {
+            diagnostics.report(error);
+            std::process::exit(70);
+        }
+    }
+}
+
+// 每次运行使用新的解释器，和 run 一样扫描、解析、检查并执行，程序的输出被丢弃。
+// 先运行 --warmup 次（默认 1）不计时，再计时运行 --runs 次（默认 10），输出最短、中位数和平均耗时
+fn bench(filename: &str, flags: &[String], plain: bool) {
+    let count = |name: &str, default: usize| match flag_value(flags, name) {
+        None => default,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid value for {}: {}", name, value);
+            std::process::exit(64);
+        }),
+    };
+    let runs = count("--runs", 10).max(1);
+    let warmup = count("--warmup", 1);
+    let source = read_file_contents(filename);
+    let diagnostics = Diagnostics::new(filename, plain);
+    let mut times = Vec::with_capacity(runs);
+    for run in 0..warmup + runs {
+        let started = Instant::now();
+        let captured = interpreter::Interpreter::new().run_and_capture(&source);
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+        // 程序出错时报告诊断信息，以和 run 相同的退出码结束
+        if captured.exit_code != 0 {
+            for message in &captured.diagnostics {
+                diagnostics.report(message);
+            }
+            std::process::exit(captured.exit_code);
+        }
+        if run >= warmup {
+            times.push(elapsed);
+        }
+    }
+    times.sort_by(f64::total_cmp);
+    let median = if runs % 2 == 1 {
+        times[runs / 2]
+    } else {
+        (times[runs / 2 - 1] + times[runs / 2]) / 2.0
+    };
+    let mean = times.iter().sum::<f64>() / runs as f64;
+    println!(
+        "runs: {}, min: {:.3} ms, median: {:.3} ms, mean: {:.3} ms",
+        runs, times[0], median, mean
+    );
+}
+
+// 每个失败的测试输出路径和不一致的地方，最后输出通过、失败和跳过的数量；有失败时以 1 退出
+fn conformance(dir: &str, skip_file: Option<&str>) {
+    let skip = match skip_file.map(fs::read_to_string) {
+        Some(Ok(text)) => conformance::parse_skip_list(&text),
+        Some(Err(error)) => {
+            eprintln!("Failed to read skip list: {}", error);
+            std::process::exit(64);
+        }
+        None => Vec::new(),
+    };
+    let root = std::path::Path::new(dir);
+    let tests = conformance::find_tests(root).unwrap_or_else(|error| {
+        eprintln!("Failed to read directory {}: {}", dir, error);
+        std::process::exit(64);
+    });
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for path in tests {
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy();
+        let relative = relative.replace('\\', "/");
+        if conformance::is_skipped(&skip, &relative) {
+            skipped += 1;
+            continue;
+        }
+        location::set_script(&relative);
+        let failures = conformance::run_test(&read_file_contents(&path.to_string_lossy()));
+        if failures.is_empty() {
+            passed += 1;
+        } else {
+            failed += 1;
+            println!("FAIL {}", relative);
+            for failure in failures {
+                println!("    {}", failure);
+            }
+        }
+    }
+    println!("Passed: {}, failed: {}, skipped: {}.", passed, failed, skipped);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// 解释器自身的 bug 导致 panic 时，先指出脚本中出错的位置，再输出默认的 panic 信息
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some((phase, line)) = location::current() {
+            let phase = match phase {
+                Phase::Parsing => "parsing",
+                Phase::Executing => "executing",
+            };
+            let script = location::script().unwrap_or_else(|| "<script>".to_string());
+            eprintln!(
+                "internal error while {} line {} of {} — please report this bug",
+                phase, line, script
+            );
+        }
+        default_hook(info);
+    }));
+}
+
+// run 命令的诊断信息：默认在出现时输出到 stderr；--quiet 时不输出，只看退出码；
+// --json 时在结束时向 stderr 输出一个 JSON 对象，stdout 仍然只有程序自己的输出。
+// --stats 输出运行时间和结束时存活的对象数，--json 时作为 JSON 的字段
+struct RunReport {
+    plain: bool,
+    quiet: bool,
+    json: bool,
+    stats: bool,
+    started: Instant,
+    // (来源的名字, 诊断信息)
+    diagnostics: Vec<(String, Diagnostic)>,
+}
+
+impl RunReport {
+    fn new(flags: &[String], plain: bool) -> Self {
+        let has = |name: &str| flags.iter().any(|flag| flag == name);
+        Self {
+            plain,
+            quiet: has("--quiet"),
+            json: has("--json"),
+            stats: has("--stats"),
+            started: Instant::now(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // 带列号的诊断信息后面显示出错的源码行并标出位置；CodeCrafters 的旧格式（plain）不显示
+    fn add(&mut self, name: &str, source: &str, diagnostic: Diagnostic) {
+        if !self.quiet && !self.json {
+            Diagnostics::new(name, self.plain).report(&diagnostic);
+            if !self.plain {
+                if let Some(snippet) = snippet(source, diagnostic.line, diagnostic.column) {
+                    eprintln!("{}", snippet);
+                }
+            }
+        }
+        self.diagnostics.push((name.to_string(), diagnostic));
+    }
+
+    fn finish(&self, status: Status) {
+        let elapsed = self.started.elapsed().as_secs_f64() * 1000.0;
+        let stats = stats::current();
+        if self.json {
+            let entries = |severity: Severity| {
+                let entries = self
+                    .diagnostics
+                    .iter()
+                    .filter(|(_, diagnostic)| diagnostic.severity == severity)
+                    .map(|(name, diagnostic)| {
+                        Value::map(vec![
+                            ("file".to_string(), Value::from(name.as_str())),
+                            ("line".to_string(), Value::Number(diagnostic.line as f64)),
+                            ("column".to_string(), Value::Number(diagnostic.column as f64)),
+                            ("message".to_string(), Value::from(diagnostic.message.as_str())),
+                            ("code".to_string(), Value::from(diagnostic.code)),
+                        ])
+                    })
+                    .collect();
+                Value::list(entries)
+            };
+            let mut fields = vec![
+                ("status".to_string(), Value::from(status.name())),
+                ("errors".to_string(), entries(Severity::Error)),
+                ("warnings".to_string(), entries(Severity::Warning)),
+            ];
+            if self.stats {
+                let time = (elapsed * 1000.0).round() / 1000.0;
+                fields.push(("time_ms".to_string(), Value::Number(time)));
+                fields.push((
+                    "stats".to_string(),
+                    Value::map(vec![
+                        ("environments".to_string(), Value::Number(stats.environments as f64)),
+                        ("functions".to_string(), Value::Number(stats.functions as f64)),
+                        ("lists".to_string(), Value::Number(stats.lists as f64)),
+                        ("maps".to_string(), Value::Number(stats.maps as f64)),
+                    ]),
+                ));
+            }
+            let report = json::stringify(&Value::map(fields), None)
+                .expect("run report contains only JSON values");
+            eprintln!("{}", report);
+        } else if self.stats {
+            eprintln!(
+                "time: {:.3} ms, environments: {}, functions: {}, lists: {}, maps: {}",
+                elapsed, stats.environments, stats.functions, stats.lists, stats.maps
+            );
+        }
+        if status != Status::Ok {
+            let _ = io::stdout().flush();
+            std::process::exit(status.exit_code());
+        }
+    }
+}
+
+// run 命令和 -e 共用：按顺序检查并执行 (名字, 源码)，所有程序共用一个解释器和全局环境，
+// 前面的程序定义的函数后面可以使用。先检查全部程序，有错误时一个都不执行；
+// 错误信息前是各自的名字。退出码和运行单个文件时相同
+fn run_sources(sources: &[(String, String)], flags: &[String], plain: bool) {
+    let multiple = sources.len() > 1;
+    // 多个文件时总是带上名字，否则看不出错误来自哪个文件
+    let mut run = RunReport::new(flags, plain && !multiple);
+    let mut checked = Vec::new();
+    for (name, source) in sources {
+        let (result, diagnostics) = check_source(source, flags);
+        for diagnostic in diagnostics {
+            run.add(name, source, diagnostic);
+        }
+        match result {
+            Ok(stmts) => checked.push(stmts),
+            Err(status) => return run.finish(status),
+        }
+    }
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.check_types = flags.iter().any(|flag| flag == "--check-types");
+    interpreter.allow_eval = flags.iter().any(|flag| flag == "--allow-eval");
+    if flags.iter().any(|flag| flag == "--intern-strings") {
+        interpreter.strings = Some(HashSet::new());
+    }
+    if let Some(kb) = flag_value(flags, "--max-memory-kb") {
+        match kb.parse::<usize>() {
+            Ok(kb) => interpreter.max_memory = Some(kb.saturating_mul(1024)),
+            Err(_) => {
+                eprintln!("Invalid value for --max-memory-kb: {}", kb);
+                std::process::exit(64);
+            }
+        }
+    }
+    if let Some(kb) = flag_value(flags, "--max-output-kb") {
+        match kb.parse::<usize>() {
+            Ok(kb) => interpreter.max_output = Some(kb.saturating_mul(1024)),
+            Err(_) => {
+                eprintln!("Invalid value for --max-output-kb: {}", kb);
+                std::process::exit(64);
+            }
+        }
+    }
+    // run --coverage <report>：执行结束后（包括运行时出错）写入覆盖率报告；覆盖率按行号统计，只支持单个文件
+    let report = flag_value(flags, "--coverage");
+    if report.is_some() && multiple {
+        eprintln!("--coverage can only be used with a single file");
+        std::process::exit(64);
+    }
+    for ((name, source), stmts) in sources.iter().zip(checked) {
+        location::set_script(name);
+        let coverable = coverage::coverable_lines(&stmts);
+        if report.is_some() {
+            interpreter.coverage = Some(HashMap::new());
+        }
+        let result = interpreter.interpret(stmts);
+        if let (Some(path), Some(hits)) = (report, &interpreter.coverage) {
+            if let Err(error) = fs::write(path, coverage::report(source, &coverable, hits)) {
+                eprintln!("Failed to write coverage report {}: {}", path, error);
+            }
+        }
+        if let Err(error) = result {
+            run.add(name, source, Diagnostic::from(&error));
+            return run.finish(Status::RuntimeError);
+        }
+    }
+    run.finish(Status::Ok);
+}
+
+// 诊断信息默认使用 CodeCrafters 的格式 `[line 3] Error: ...`，.codecrafters/run.sh 直接运行可执行文件；
+// --prefix-errors 或环境变量 LOX_PREFIX_ERRORS=1 时在前面加上来源的名字，并显示出错的源码行。
+// --plain-errors 和默认相同，同时给出时以它为准
+fn run_command() {
+    let prefix = env::args().any(|arg| arg == "--prefix-errors")
+        || env::var("LOX_PREFIX_ERRORS").is_ok_and(|value| !value.is_empty() && value != "0");
+    let plain = !prefix || env::args().any(|arg| arg == "--plain-errors");
+    let args: Vec<String> = env::args()
+        .filter(|arg| arg != "--prefix-errors" && arg != "--plain-errors")
+        .collect();
+    if args.get(1).is_some_and(|command| command == "repl") {
+        repl(plain);
+        return;
+    }
+    // -e <code>：直接运行命令行中给出的程序，后面可以跟 run 的选项
+    if args.get(1).is_some_and(|flag| flag == "-e") {
+        let Some(code) = args.get(2) else {
+            eprintln!("Missing value for -e");
+            std::process::exit(64);
+        };
+        run_sources(&[("<command-line>".to_string(), code.clone())], &args[3..], plain);
+        return;
+    }
     if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
+        eprintln!("Usage: {} tokenize <filename>", args[0]);
         return;
     }
 
     let command = &args[1];
     let filename = &args[2];
+    location::set_script(filename);
+    let flags = &args[3..];
+    let mut diagnostics = Diagnostics::new(filename, plain);
 
     match command.as_str() {
         "tokenize" => {
-            let file_contents = read_file_contents(&filename);
-
-            if !file_contents.is_empty() {
-                let mut s = scanner::Scanner::new(&file_contents);
-                let (tokens, errors) = s.scan_tokens();
-                for err in errors {
-                    eprintln!("{}", err);
-                }
+            let file_contents = read_file_contents(filename);
+            let mut s = scanner::Scanner::new(&file_contents);
+            let (tokens, errors) = s.scan_tokens();
+            for err in errors {
+                diagnostics.report(err);
+            }
+            // --json：输出 JSON 数组，可以交给 parse --from-tokens 解析
+            if flags.iter().any(|flag| flag == "--json") {
+                println!("{}", scanner::json::to_json(tokens));
+            } else {
                 for token in tokens {
-                    println!("{}", token.to_string());
+                    println!("{}", token);
                 }
-                if !errors.is_empty() {
-                    std::process::exit(65);
-                }
-            } else {
-                println!("EOF  null");
             }
-        }
-        "parse" => {
-            let file_contents = read_file_contents(&filename);
-            let mut s = scanner::Scanner::new(&file_contents);
-            let (tokens, errors) = s.scan_tokens();
-            if !errors.is_empty() {
+            let failed = !errors.is_empty();
+            for warning in diagnostics::scanner_warnings(&s) {
+                diagnostics.report(warning);
+            }
+            if failed {
                 std::process::exit(65);
             }
-            let mut parser = parser::Parser::new(tokens);
+        }
+        "parse" => {
+            // parse --from-tokens <tokens.json>：解析 tokenize --json 的输出，不再扫描源码
+            let (tokens, flags) = if filename == "--from-tokens" {
+                let Some(path) = flags.first() else {
+                    eprintln!("Missing value for --from-tokens");
+                    std::process::exit(64);
+                };
+                location::set_script(path);
+                diagnostics.name = path.clone();
+                match scanner::json::from_json(&read_file_contents(path)) {
+                    Ok(tokens) => (tokens, &flags[1..]),
+                    Err(message) => {
+                        diagnostics.report(message);
+                        std::process::exit(65);
+                    }
+                }
+            } else {
+                let file_contents = read_file_contents(filename);
+                let mut s = scanner::Scanner::new(&file_contents);
+                let (tokens, errors) = s.scan_tokens();
+                if !errors.is_empty() {
+                    std::process::exit(65);
+                }
+                (tokens.clone(), flags)
+            };
+            let mut parser = parser::Parser::new(&tokens);
             let expr = match parser.parse_expr() {
                 Ok(expr) => expr,
                 Err(error) => {
-                    eprintln!("{}", error);
+                    diagnostics.report(error);
                     std::process::exit(65);
                 }
             };
-            println!("{}", expr);
+            if flags.iter().any(|flag| flag == "--pretty") {
+                println!("{}", parser::printer::pretty(&expr));
+            } else if flags.iter().any(|flag| flag == "--no-groups") {
+                println!("{}", parser::lower::strip_groups(expr));
+            } else {
+                println!("{}", expr);
+            }
         }
         "evaluate" => {
-            let file_contents = read_file_contents(&filename);
+            let file_contents = read_file_contents(filename);
             let mut s = scanner::Scanner::new(&file_contents);
-            let (tokens, errors) = s.scan_tokens();
+            let (tokens, _) = s.scan_tokens();
             let mut parser = parser::Parser::new(tokens);
             let ast = match parser.parse_expr() {
-                Ok(expr) => expr,
+                Ok(expr) => parser::lower::strip_groups(expr),
                 Err(error) => {
-                    eprintln!("{}", error);
+                    diagnostics.report(error);
                     std::process::exit(65);
                 }
             };
@@ -78,39 +622,38 @@ fn main() {
             let value = match interpreter.evaluate(&ast, &Rc::clone(&interpreter.env)) {
                 Ok(result) => result,
                 Err(error) => {
-                    eprintln!("{}", error);
+                    diagnostics.report(error);
                     std::process::exit(70);
                 }
             };
             println!("{}", value);
         },
+        // 第二个参数是表达式源码而不是文件名
+        "explain" => explain(filename, plain),
+        // 只做解析和静态检查，不执行
+        "check" => {
+            check_file(filename, flags, plain);
+        }
+        // run a.lox b.lox ... [选项]：依次运行多个文件
         "run" => {
-            let file_contents = read_file_contents(&filename);
-            let mut s = scanner::Scanner::new(&file_contents);
-            let (tokens, errors) = s.scan_tokens();
-
-            let mut parser = parser::Parser::new(tokens);
-            let stmts = match parser.parse() {
-                Ok(expr) => expr,
-                Err(error) => {
-                    eprintln!("{}", error);
-                    std::process::exit(65);
-                }
-            };
-            // println!("{:#?}", stmts);
-            let mut interpreter = interpreter::Interpreter::new();
-            let _ = match interpreter.interpret(stmts) {
-                Ok(result) => result,
-                Err(error) => {
-                    eprintln!("{}", error);
-                    std::process::exit(70);
-                }
-            };
-            // println!("{:#?}\n", interpreter.env);
+            let count = args[2..]
+                .iter()
+                .take_while(|arg| !arg.starts_with("--"))
+                .count();
+            let flags = &args[2 + count..];
+            let max_bytes = limits(flags).max_source_bytes;
+            let sources: Vec<_> = args[2..2 + count]
+                .iter()
+                .map(|filename| (filename.clone(), read_source(filename, max_bytes)))
+                .collect();
+            run_sources(&sources, flags, plain);
         }
+        // bench <file> [--runs N] [--warmup N]：多次运行程序并统计耗时
+        "bench" => bench(filename, flags, plain),
+        // conformance <dir> [--skip <file>]：按官方测试的注释格式运行目录下的 .lox 测试
+        "conformance" => conformance(filename, flag_value(flags, "--skip")),
         _ => {
-            writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
-            return;
+            eprintln!("Unknown command: {}", command);
         }
     }
 }
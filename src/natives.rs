@@ -0,0 +1,608 @@
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    interpreter::{Interpreter, NativeFunction, RuntimeError, Value},
+    parser::{self, Parser},
+    resolver::Resolver,
+    scanner::{
+        token::{self, Token},
+        Scanner,
+    },
+};
+
+pub mod args;
+pub mod format;
+pub mod json;
+pub mod math;
+pub mod memo;
+pub mod time;
+
+use args::{expect_number, expect_string};
+
+// 所有内置函数，Interpreter 创建时注册到全局环境
+pub fn all() -> Vec<NativeFunction> {
+    #[allow(unused_mut)]
+    let mut natives = vec![
+        NativeFunction {
+            name: "clock",
+            arity: 0..=0,
+            func: clock,
+        },
+        NativeFunction {
+            name: "getenv",
+            arity: 1..=1,
+            func: getenv,
+        },
+        NativeFunction {
+            name: "setenv",
+            arity: 2..=2,
+            func: setenv,
+        },
+        NativeFunction {
+            name: "exec",
+            arity: 1..=1,
+            func: exec,
+        },
+        NativeFunction {
+            name: "jsonParse",
+            arity: 1..=1,
+            func: json_parse,
+        },
+        NativeFunction {
+            name: "jsonStringify",
+            arity: 1..=2,
+            func: json_stringify,
+        },
+        // to_json、from_json 是上面两个函数的别名，见 json_parse
+        NativeFunction {
+            name: "to_json",
+            arity: 1..=1,
+            func: to_json,
+        },
+        NativeFunction {
+            name: "from_json",
+            arity: 1..=1,
+            func: from_json,
+        },
+        NativeFunction {
+            name: "memoize",
+            arity: 1..=1,
+            func: memoize,
+        },
+        NativeFunction {
+            name: "cacheStats",
+            arity: 1..=1,
+            func: cache_stats,
+        },
+        NativeFunction {
+            name: "fields",
+            arity: 1..=1,
+            func: fields,
+        },
+        NativeFunction {
+            name: "envCount",
+            arity: 0..=0,
+            func: env_count,
+        },
+        NativeFunction {
+            name: "valueStats",
+            arity: 0..=0,
+            func: value_stats,
+        },
+        NativeFunction {
+            name: "timeNow",
+            arity: 0..=0,
+            func: time_now,
+        },
+        NativeFunction {
+            name: "timeUtcNow",
+            arity: 0..=0,
+            func: time_utc_now,
+        },
+        NativeFunction {
+            name: "timeFormat",
+            arity: 2..=2,
+            func: time_format,
+        },
+        NativeFunction {
+            name: "printf",
+            arity: 1..=255,
+            func: printf,
+        },
+        NativeFunction {
+            name: "flush",
+            arity: 0..=0,
+            func: flush,
+        },
+        NativeFunction {
+            name: "repr",
+            arity: 1..=1,
+            func: repr,
+        },
+        NativeFunction {
+            name: "floorDiv",
+            arity: 2..=2,
+            func: floor_div,
+        },
+        NativeFunction {
+            name: "truncDiv",
+            arity: 2..=2,
+            func: trunc_div,
+        },
+        NativeFunction {
+            name: "round",
+            arity: 1..=1,
+            func: round,
+        },
+        NativeFunction {
+            name: "roundTo",
+            arity: 2..=2,
+            func: round_to,
+        },
+        NativeFunction {
+            name: "trunc",
+            arity: 1..=1,
+            func: trunc,
+        },
+        NativeFunction {
+            name: "eval",
+            arity: 1..=1,
+            func: eval,
+        },
+        NativeFunction {
+            name: "evalStatements",
+            arity: 1..=1,
+            func: eval_statements,
+        },
+    ];
+    // 只在 debug 构建中提供：故意触发内部 panic，用来检查 panic hook 的输出
+    #[cfg(debug_assertions)]
+    natives.push(NativeFunction {
+        name: "__panic",
+        arity: 0..=0,
+        func: |_, _, _| panic!("__panic() called"),
+    });
+    natives
+}
+
+fn clock(interpreter: &mut Interpreter, _: &[Value], _: usize) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(interpreter.time_source.now()))
+}
+
+// memoize(f)：返回按参数缓存结果的新函数，参数只能是数字、字符串、布尔值和 nil
+// f 内部的递归调用不会经过缓存，除非 f 递归调用的是 memoize 返回的那个名字：
+//   fun fib(n) { if (n < 2) return n; return fastFib(n - 1) + fastFib(n - 2); }
+//   var fastFib = memoize(fib);
+fn memoize(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Function(_) | Value::NativeFunction(_) | Value::Memoized(_) => Ok(Value::Memoized(
+            Rc::new(memo::Memoized::new(args[0].clone())),
+        )),
+        _ => Err(RuntimeError::new(
+            "memoize: argument 1 must be a function.".to_string(),
+            line,
+        )),
+    }
+}
+
+// cacheStats(f)：memoize 返回的函数的缓存情况 {size, hits, misses}
+fn cache_stats(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let Value::Memoized(memo) = &args[0] else {
+        return Err(RuntimeError::new(
+            "cacheStats: argument 1 must be a memoized function.".to_string(),
+            line,
+        ));
+    };
+    let entries = vec![
+        ("size".to_string(), Value::Number(memo.cache.borrow().len() as f64)),
+        ("hits".to_string(), Value::Number(memo.hits.get() as f64)),
+        ("misses".to_string(), Value::Number(memo.misses.get() as f64)),
+    ];
+    Ok(Value::map(entries))
+}
+
+// fields(instance)：实例的字段名列表，按第一次赋值的顺序
+fn fields(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let Value::Instance(instance) = &args[0] else {
+        return Err(RuntimeError::new(
+            "fields: argument 1 must be an instance.".to_string(),
+            line,
+        ));
+    };
+    let names = instance
+        .fields
+        .borrow()
+        .iter()
+        .map(|(name, _)| Value::from(name.as_str()))
+        .collect();
+    Ok(Value::list(names))
+}
+
+// envCount()：当前存活的 Environment 数量
+fn env_count(interpreter: &mut Interpreter, _: &[Value], _: usize) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(interpreter.stats().environments as f64))
+}
+
+// valueStats()：当前存活的 {functions, lists, maps} 数量
+fn value_stats(
+    interpreter: &mut Interpreter,
+    _: &[Value],
+    _: usize,
+) -> Result<Value, RuntimeError> {
+    let stats = interpreter.stats();
+    Ok(Value::map(vec![
+        ("functions".to_string(), Value::Number(stats.functions as f64)),
+        ("lists".to_string(), Value::Number(stats.lists as f64)),
+        ("maps".to_string(), Value::Number(stats.maps as f64)),
+    ]))
+}
+
+// timeNow()：本地时间的 {year, month, day, hour, minute, second}
+fn time_now(interpreter: &mut Interpreter, _: &[Value], _: usize) -> Result<Value, RuntimeError> {
+    let now = interpreter.time_source.now();
+    let offset = interpreter.time_source.utc_offset(now);
+    Ok(date_time_map(now.floor() as i64 + offset))
+}
+
+// timeUtcNow()：同 timeNow，但使用 UTC
+fn time_utc_now(
+    interpreter: &mut Interpreter,
+    _: &[Value],
+    _: usize,
+) -> Result<Value, RuntimeError> {
+    Ok(date_time_map(interpreter.time_source.now().floor() as i64))
+}
+
+// timeFormat(epochSeconds, format)：按本地时间格式化，支持 %Y %m %d %H %M %S %%
+fn time_format(
+    interpreter: &mut Interpreter,
+    args: &[Value],
+    line: usize,
+) -> Result<Value, RuntimeError> {
+    let epoch = expect_number(args, 0, "timeFormat", line)?;
+    let pattern = expect_string(args, 1, "timeFormat", line)?;
+    if !epoch.is_finite() {
+        return Err(RuntimeError::new(
+            "timeFormat: argument 1 must be a finite number.".to_string(),
+            line,
+        ));
+    }
+    let offset = interpreter.time_source.utc_offset(epoch);
+    let date = time::to_date_time(epoch.floor() as i64 + offset);
+    time::format(&date, pattern)
+        .map(Value::from)
+        .map_err(|message| RuntimeError::new(format!("timeFormat: {}", message), line))
+}
+
+// 按格式串输出，不自动换行；换行写在格式串里，例如 printf("x={}\n", x)
+fn printf(interpreter: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let pattern = expect_string(args, 0, "printf", line)?;
+    let text = format::format(pattern, &args[1..])
+        .map_err(|message| RuntimeError::new(format!("printf: {}", message), line))?;
+    if !interpreter.charge_output(text.len()) {
+        return Err(RuntimeError::new("Output limit exceeded.".to_string(), line));
+    }
+    write!(interpreter.output, "{}", text)
+        .and_then(|()| interpreter.output.flush())
+        .map_err(|error| RuntimeError::new(format!("printf: {}.", error), line))?;
+    Ok(Value::Nil)
+}
+
+// flush()：把 print/printf 的输出写出去。两者每次输出后都会自动 flush，
+// 输出被替换为其他 writer 时也可以显式调用
+fn flush(interpreter: &mut Interpreter, _: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    interpreter
+        .output
+        .flush()
+        .map_err(|error| RuntimeError::new(format!("flush: {}.", error), line))?;
+    Ok(Value::Nil)
+}
+
+// repr(x)：调试用的字符串形式，字符串带引号并转义，repr("a") 是 "a"（含引号），
+// repr(1) 是 1；其他值和 print 的输出相同
+fn repr(_: &mut Interpreter, args: &[Value], _: usize) -> Result<Value, RuntimeError> {
+    Ok(Value::from(match &args[0] {
+        Value::String(s) => format!("\"{}\"", token::escape(s)),
+        value => value.to_string(),
+    }))
+}
+
+// floorDiv(a, b)：向负无穷取整的除法，floorDiv(-7, 2) 是 -4
+fn floor_div(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let a = expect_number(args, 0, "floorDiv", line)?;
+    let b = expect_number(args, 1, "floorDiv", line)?;
+    math::floor_div(a, b)
+        .map(Value::Number)
+        .map_err(|message| RuntimeError::new(format!("floorDiv: {}", message), line))
+}
+
+// truncDiv(a, b)：向零取整的除法，truncDiv(-7, 2) 是 -3
+fn trunc_div(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let a = expect_number(args, 0, "truncDiv", line)?;
+    let b = expect_number(args, 1, "truncDiv", line)?;
+    math::trunc_div(a, b)
+        .map(Value::Number)
+        .map_err(|message| RuntimeError::new(format!("truncDiv: {}", message), line))
+}
+
+// round(x)：四舍五入到整数，.5 远离零：round(2.5) 是 3，round(-2.5) 是 -3
+fn round(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(expect_number(args, 0, "round", line)?.round()))
+}
+
+// roundTo(x, decimals)：保留 decimals 位小数
+fn round_to(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let x = expect_number(args, 0, "roundTo", line)?;
+    let decimals = expect_number(args, 1, "roundTo", line)?;
+    math::round_to(x, decimals)
+        .map(Value::Number)
+        .map_err(|message| RuntimeError::new(format!("roundTo: {}", message), line))
+}
+
+// trunc(x)：去掉小数部分（向零取整）：trunc(-2.7) 是 -2
+fn trunc(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(expect_number(args, 0, "trunc", line)?.trunc()))
+}
+
+// eval(source)：把字符串作为一个表达式求值并返回结果。内置函数拿不到调用处的局部环境，
+// 所以在全局环境中求值；需要先打开 Interpreter::allow_eval
+fn eval(interpreter: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    check_eval_allowed(interpreter, "eval", line)?;
+    let source = expect_string(args, 0, "eval", line)?;
+    let tokens = scan_source(source, "eval", line)?;
+    let expr = Parser::new(&tokens)
+        .parse_expr()
+        .map_err(|error| syntax_error("eval", error.line(), error.message(), line))?;
+    let expr = parser::lower::strip_groups(expr);
+    let env = Rc::clone(&interpreter.env);
+    interpreter.evaluate(&expr, &env)
+}
+
+// evalStatements(source)：在全局环境中执行一段语句，返回 nil。
+// 名字没有用 exec，因为 exec 已经用来执行系统命令
+fn eval_statements(
+    interpreter: &mut Interpreter,
+    args: &[Value],
+    line: usize,
+) -> Result<Value, RuntimeError> {
+    check_eval_allowed(interpreter, "evalStatements", line)?;
+    let source = expect_string(args, 0, "evalStatements", line)?;
+    let tokens = scan_source(source, "evalStatements", line)?;
+    let stmts = Parser::new(&tokens).parse().map_err(|errors| {
+        syntax_error("evalStatements", errors[0].line(), errors[0].message(), line)
+    })?;
+    let mut stmts = parser::lower::strip_groups_stmts(stmts);
+    let mut resolver = Resolver::new();
+    resolver.resolve(&mut stmts);
+    if let Some(error) = resolver.errors().first() {
+        return Err(syntax_error("evalStatements", error.line(), error.message(), line));
+    }
+    let env = Rc::clone(&interpreter.env);
+    for stmt in &stmts {
+        interpreter.execute(stmt, &env)?;
+    }
+    Ok(Value::Nil)
+}
+
+fn check_eval_allowed(
+    interpreter: &Interpreter,
+    name: &str,
+    line: usize,
+) -> Result<(), RuntimeError> {
+    if interpreter.allow_eval {
+        return Ok(());
+    }
+    Err(RuntimeError::new(
+        format!("{}: evaluating code from strings is disabled.", name),
+        line,
+    ))
+}
+
+fn scan_source(source: &str, name: &str, line: usize) -> Result<Vec<Token>, RuntimeError> {
+    let mut scanner = Scanner::new(source);
+    let (tokens, errors) = scanner.scan_tokens();
+    match errors.first() {
+        Some(error) => Err(syntax_error(name, error.line, &error.message, line)),
+        None => Ok(tokens.clone()),
+    }
+}
+
+// 被求值代码中的扫描和语法错误作为调用处的运行时错误报告，信息中带上它在字符串里的行号
+fn syntax_error(name: &str, error_line: usize, message: &str, line: usize) -> RuntimeError {
+    RuntimeError::new(
+        format!("{}: syntax error at line {}: {}", name, error_line, message),
+        line,
+    )
+}
+
+fn date_time_map(seconds: i64) -> Value {
+    let date = time::to_date_time(seconds);
+    let entries = [
+        ("year", date.year),
+        ("month", date.month),
+        ("day", date.day),
+        ("hour", date.hour),
+        ("minute", date.minute),
+        ("second", date.second),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), Value::Number(value as f64)))
+    .collect();
+    Value::map(entries)
+}
+
+// getenv(key)：未设置时返回 nil；先查 setenv 写入的覆盖表，再查进程环境变量
+fn getenv(
+    interpreter: &mut Interpreter,
+    args: &[Value],
+    line: usize,
+) -> Result<Value, RuntimeError> {
+    check_env_access(interpreter, "getenv", line)?;
+    let key = expect_string(args, 0, "getenv", line)?;
+    if let Some(value) = interpreter.env_overlay.get(key) {
+        return Ok(Value::from(value.as_str()));
+    }
+    match std::env::var(key) {
+        Ok(value) => Ok(Value::from(value)),
+        Err(_) => Ok(Value::Nil),
+    }
+}
+
+// setenv(key, value)：std::env::set_var 在多线程下不安全，所以只写入解释器自己的覆盖表，
+// 对之后的 getenv 可见，但不会影响进程环境和子进程
+fn setenv(
+    interpreter: &mut Interpreter,
+    args: &[Value],
+    line: usize,
+) -> Result<Value, RuntimeError> {
+    check_env_access(interpreter, "setenv", line)?;
+    let key = expect_string(args, 0, "setenv", line)?;
+    let value = expect_string(args, 1, "setenv", line)?;
+    interpreter
+        .env_overlay
+        .insert(key.to_string(), value.to_string());
+    Ok(Value::Nil)
+}
+
+// exec(command)：通过系统 shell 执行命令，返回 [退出码, stdout, stderr]
+// 子进程不继承解释器的 stdin；超过 Interpreter::exec_timeout 时杀掉子进程并报错
+fn exec(interpreter: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    if !interpreter.capabilities.process {
+        return Err(RuntimeError::new(
+            "exec: process execution is disabled.".to_string(),
+            line,
+        ));
+    }
+    let command = expect_string(args, 0, "exec", line)?;
+    let mut child = shell(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| RuntimeError::new(format!("exec: {}.", error), line))?;
+
+    // 在单独的线程里读取输出，避免管道写满时子进程阻塞
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(error) => return Err(RuntimeError::new(format!("exec: {}.", error), line)),
+        }
+        if let Some(timeout) = interpreter.exec_timeout {
+            if started.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(RuntimeError::new(
+                    format!("exec: command timed out after {} ms.", timeout.as_millis()),
+                    line,
+                ));
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    // 被信号终止的进程没有退出码，用 -1 表示
+    let code = status.code().map_or(-1.0, f64::from);
+    let result = vec![
+        Value::Number(code),
+        Value::from(stdout.join().unwrap_or_default()),
+        Value::from(stderr.join().unwrap_or_default()),
+    ];
+    Ok(Value::list(result))
+}
+
+// JSON 内置函数只有一份实现：jsonParse(text) 和 jsonStringify(value, indent?)。
+// from_json(text) 是 jsonParse 的别名，to_json(value) 是不带 indent 的 jsonStringify 的别名；
+// 别名只在这里定义，错误信息带上调用时用的名字
+fn json_parse(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    parse_json("jsonParse", args, line)
+}
+
+fn json_stringify(
+    _: &mut Interpreter,
+    args: &[Value],
+    line: usize,
+) -> Result<Value, RuntimeError> {
+    stringify_json("jsonStringify", args, line)
+}
+
+fn from_json(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    parse_json("from_json", args, line)
+}
+
+fn to_json(_: &mut Interpreter, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    stringify_json("to_json", args, line)
+}
+
+fn parse_json(name: &str, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let text = expect_string(args, 0, name, line)?;
+    json::parse(text).map_err(|message| RuntimeError::new(format!("{}: {}", name, message), line))
+}
+
+// 缩进超过 10 个空格没有意义，很大的值还会生成巨大的字符串
+const MAX_JSON_INDENT: f64 = 10.0;
+
+// 不传 indent 时输出紧凑格式，indent 为 0 到 10 的整数
+fn stringify_json(name: &str, args: &[Value], line: usize) -> Result<Value, RuntimeError> {
+    let indent = match args.get(1) {
+        Some(_) => {
+            let indent = expect_number(args, 1, name, line)?;
+            if !(0.0..=MAX_JSON_INDENT).contains(&indent) || indent.fract() != 0.0 {
+                return Err(RuntimeError::new(
+                    format!("{}: indent must be an integer between 0 and 10.", name),
+                    line,
+                ));
+            }
+            Some(indent as usize)
+        }
+        None => None,
+    };
+    json::stringify(&args[0], indent)
+        .map(Value::from)
+        .map_err(|message| RuntimeError::new(format!("{}: {}", name, message), line))
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        output
+    })
+}
+
+fn check_env_access(interpreter: &Interpreter, name: &str, line: usize) -> Result<(), RuntimeError> {
+    if interpreter.capabilities.env {
+        Ok(())
+    } else {
+        Err(RuntimeError::new(
+            format!("{}: environment access is disabled.", name),
+            line,
+        ))
+    }
+}
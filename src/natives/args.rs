@@ -0,0 +1,50 @@
+use std::rc::Rc;
+
+use crate::interpreter::{ListRef, RuntimeError, Value};
+
+// 内置函数的参数检查，统一错误信息的格式：
+//   "<fn>: argument <n> must be a <type>."
+// 参数个数在调用处已经按 NativeFunction::arity 检查过
+
+pub fn expect_number(
+    args: &[Value],
+    index: usize,
+    fn_name: &str,
+    line: usize,
+) -> Result<f64, RuntimeError> {
+    match args.get(index) {
+        Some(Value::Number(n)) => Ok(*n),
+        _ => Err(type_error(index, fn_name, "number", line)),
+    }
+}
+
+pub fn expect_string<'a>(
+    args: &'a [Value],
+    index: usize,
+    fn_name: &str,
+    line: usize,
+) -> Result<&'a str, RuntimeError> {
+    match args.get(index) {
+        Some(Value::String(s)) => Ok(s),
+        _ => Err(type_error(index, fn_name, "string", line)),
+    }
+}
+
+pub fn expect_list(
+    args: &[Value],
+    index: usize,
+    fn_name: &str,
+    line: usize,
+) -> Result<ListRef, RuntimeError> {
+    match args.get(index) {
+        Some(Value::List(elements)) => Ok(Rc::clone(elements)),
+        _ => Err(type_error(index, fn_name, "list", line)),
+    }
+}
+
+fn type_error(index: usize, fn_name: &str, expected: &str, line: usize) -> RuntimeError {
+    RuntimeError::new(
+        format!("{}: argument {} must be a {}.", fn_name, index + 1, expected),
+        line,
+    )
+}
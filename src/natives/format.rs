@@ -0,0 +1,223 @@
+use crate::interpreter::Value;
+
+// printf 的格式串：{} 按 print 的方式输出参数，{:.2} 指定数字的小数位数，
+// {:>8} / {:<8} 右对齐 / 左对齐到指定宽度，两者可以组合为 {:>8.2}；{{ 和 }} 输出花括号本身
+// 错误信息中的位置是字符下标，从 0 开始
+enum Piece {
+    Text(String),
+    Placeholder(Spec),
+}
+
+struct Spec {
+    position: usize,
+    align: Align,
+    width: usize,
+    precision: Option<usize>,
+}
+
+enum Align {
+    Left,
+    Right,
+}
+
+pub fn format(pattern: &str, args: &[Value]) -> Result<String, String> {
+    let pieces = parse(pattern)?;
+    let placeholders = pieces
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Placeholder(_)))
+        .count();
+    if args.len() > placeholders {
+        return Err(format!(
+            "Format has {} placeholders but got {} arguments.",
+            placeholders,
+            args.len()
+        ));
+    }
+    let mut args = args.iter();
+    let mut out = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => out.push_str(&text),
+            Piece::Placeholder(spec) => {
+                let Some(arg) = args.next() else {
+                    return Err(format!(
+                        "Missing argument for placeholder at position {}.",
+                        spec.position
+                    ));
+                };
+                out.push_str(&render(&spec, arg)?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn render(spec: &Spec, arg: &Value) -> Result<String, String> {
+    let text = match (spec.precision, arg) {
+        (None, arg) => arg.to_string(),
+        (Some(precision), Value::Number(n)) => format!("{:.*}", precision, n),
+        (Some(_), arg) => {
+            return Err(format!(
+                "Precision at position {} requires a number but got {}.",
+                spec.position,
+                arg.type_name()
+            ))
+        }
+    };
+    let padding = " ".repeat(spec.width.saturating_sub(text.chars().count()));
+    Ok(match spec.align {
+        Align::Left => text + &padding,
+        Align::Right => padding + &text,
+    })
+}
+
+fn parse(pattern: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = pattern.chars().enumerate().peekable();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '{' if chars.next_if(|&(_, c)| c == '{').is_some() => text.push('{'),
+            '}' if chars.next_if(|&(_, c)| c == '}').is_some() => text.push('}'),
+            '}' => return Err(format!("Unmatched '}}' at position {}.", position)),
+            '{' => {
+                let mut body = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, c)) => body.push(c),
+                        None => return Err(format!("Unmatched '{{' at position {}.", position)),
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Placeholder(spec(&body, position)?));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+// 宽度和精度的上限，更大的值会让 render 分配巨大的字符串（{:.99999999999} 甚至会 panic）
+const MAX_WIDTH: usize = 4096;
+const MAX_PRECISION: usize = 100;
+
+// 花括号内的部分：空，或者 `:` 后跟可选的对齐和宽度、可选的 `.精度`
+fn spec(body: &str, position: usize) -> Result<Spec, String> {
+    let invalid = || format!("Invalid placeholder '{{{}}}' at position {}.", body, position);
+    let mut spec = Spec {
+        position,
+        align: Align::Right,
+        width: 0,
+        precision: None,
+    };
+    if body.is_empty() {
+        return Ok(spec);
+    }
+    let mut rest = body.strip_prefix(':').ok_or_else(invalid)?;
+    if let Some(after) = rest.strip_prefix('<') {
+        spec.align = Align::Left;
+        rest = after;
+    } else if let Some(after) = rest.strip_prefix('>') {
+        rest = after;
+    }
+    let (width, precision) = match rest.split_once('.') {
+        Some((width, precision)) => (width, Some(precision)),
+        None => (rest, None),
+    };
+    if !width.is_empty() {
+        spec.width = width.parse().map_err(|_| invalid())?;
+        if spec.width > MAX_WIDTH {
+            return Err(invalid());
+        }
+    }
+    if let Some(precision) = precision {
+        let precision = precision.parse().map_err(|_| invalid())?;
+        if precision > MAX_PRECISION {
+            return Err(invalid());
+        }
+        spec.precision = Some(precision);
+    }
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::from(s)
+    }
+
+    #[test]
+    fn placeholders() {
+        let args = [Value::Number(1.5), string("two"), Value::Nil];
+        assert_eq!(format("{} {} {}!", &args), Ok("1.5 two nil!".to_string()));
+        let numbers = [Value::Number(1.23456), Value::Number(2.5)];
+        assert_eq!(format("{:.2}|{:.0}", &numbers), Ok("1.23|2".to_string()));
+        assert_eq!(format("no placeholders", &[]), Ok("no placeholders".to_string()));
+    }
+
+    #[test]
+    fn width_and_alignment() {
+        let n = [Value::Number(42.0)];
+        assert_eq!(format("[{:>8}]", &n), Ok("[      42]".to_string()));
+        assert_eq!(format("[{:<8}]", &n), Ok("[42      ]".to_string()));
+        assert_eq!(format("[{:6}]", &n), Ok("[    42]".to_string()));
+        assert_eq!(format("[{:>8.2}]", &n), Ok("[   42.00]".to_string()));
+        // 宽度按字符计算，比内容短时不截断
+        assert_eq!(format("[{:<4}]", &[string("é")]), Ok("[é   ]".to_string()));
+        assert_eq!(format("[{:1}]", &[string("long")]), Ok("[long]".to_string()));
+    }
+
+    // 超过上限的宽度和精度是无效的占位符，不会分配巨大的字符串
+    #[test]
+    fn width_and_precision_limits() {
+        let n = [Value::Number(1.0)];
+        assert_eq!(format("{:4096}", &n).map(|s| s.len()), Ok(4096));
+        assert_eq!(format("{:.100}", &n).map(|s| s.len()), Ok(102));
+        let cases = [
+            ("{:4097}", "Invalid placeholder '{:4097}' at position 0."),
+            ("x{:<99999999999}", "Invalid placeholder '{:<99999999999}' at position 1."),
+            ("{:.101}", "Invalid placeholder '{:.101}' at position 0."),
+            ("{:>8.99999999999}", "Invalid placeholder '{:>8.99999999999}' at position 0."),
+            // 超出 usize 的数字同样报错
+            (
+                "{:.99999999999999999999}",
+                "Invalid placeholder '{:.99999999999999999999}' at position 0.",
+            ),
+        ];
+        for (pattern, message) in cases {
+            assert_eq!(format(pattern, &n), Err(message.to_string()), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(format("{{}} {{{}}}", &[Value::Bool(true)]), Ok("{} {true}".to_string()));
+    }
+
+    #[test]
+    fn errors_report_positions() {
+        let one = [Value::Number(1.0)];
+        let cases = [
+            ("ab}", &one[..0], "Unmatched '}' at position 2."),
+            ("x {:>3", &one[..], "Unmatched '{' at position 2."),
+            ("{} {", &one[..], "Unmatched '{' at position 3."),
+            ("{x}", &one[..], "Invalid placeholder '{x}' at position 0."),
+            ("  {:>a}", &one[..], "Invalid placeholder '{:>a}' at position 2."),
+            ("{:.}", &one[..], "Invalid placeholder '{:.}' at position 0."),
+            ("{} {}", &one[..], "Missing argument for placeholder at position 3."),
+            ("{}", &[Value::Nil, Value::Nil][..], "Format has 1 placeholders but got 2 arguments."),
+            ("{:.1}", &[Value::Nil][..], "Precision at position 0 requires a number but got nil."),
+        ];
+        for (pattern, args, message) in cases {
+            assert_eq!(format(pattern, args), Err(message.to_string()), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::rc::Rc;
+
+use crate::{interpreter::Value, number::format_number};
+
+// JSON 与 Lox 值之间的转换：
+//   对象 <-> map，数组 <-> list，数字 <-> number，字符串/布尔值/null <-> string/bool/nil
+// 错误信息中的 position 是从 0 开始的字符下标
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = JsonParser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.unexpected());
+    }
+    Ok(value)
+}
+
+// indent 为 None 时输出紧凑格式，否则每层缩进 indent 个空格
+pub fn stringify(value: &Value, indent: Option<usize>) -> Result<String, String> {
+    let mut writer = JsonWriter {
+        out: String::new(),
+        indent,
+        visiting: vec![],
+    };
+    writer.value(value, 0)?;
+    Ok(writer.out)
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::from(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('n') => self.keyword("null", Value::Nil),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut entries: Vec<(String, Value)> = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::map(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.unexpected());
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            // 重复的键以最后一次出现为准
+            match entries.iter_mut().find(|(k, _)| *k == key) {
+                Some(entry) => entry.1 = value,
+                None => entries.push((key, value)),
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::map(entries));
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::list(elements));
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Value::list(elements));
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(format!("Unterminated string at position {}.", self.pos)),
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => out.push(self.escape()?),
+                c if (c as u32) < 0x20 => {
+                    return Err(format!(
+                        "Control character in string at position {}.",
+                        self.pos - 1
+                    ))
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(format!("Unterminated string at position {}.", self.pos)),
+        };
+        self.pos += 1;
+        match c {
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            '/' => Ok('/'),
+            'b' => Ok('\u{8}'),
+            'f' => Ok('\u{c}'),
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            'u' => {
+                let start = self.pos - 2;
+                let high = self.hex4()?;
+                // UTF-16 代理对：😀
+                if (0xD800..0xDC00).contains(&high) {
+                    if self.peek() == Some('\\') && self.chars.get(self.pos + 1) == Some(&'u') {
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        if (0xDC00..0xE000).contains(&low) {
+                            let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                            return char::from_u32(code)
+                                .ok_or_else(|| format!("Invalid escape at position {}.", start));
+                        }
+                    }
+                    return Err(format!("Invalid escape at position {}.", start));
+                }
+                char::from_u32(high).ok_or_else(|| format!("Invalid escape at position {}.", start))
+            }
+            _ => Err(format!("Invalid escape at position {}.", self.pos - 2)),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let start = self.pos;
+        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+        if digits.len() != 4 {
+            return Err(format!("Invalid escape at position {}.", start));
+        }
+        self.pos += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid escape at position {}.", start))
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return Err(self.unexpected());
+        }
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-') {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| format!("Invalid number at position {}.", start))
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("Unexpected character '{}' at position {}.", c, self.pos),
+            None => format!("Unexpected end of input at position {}.", self.pos),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+}
+
+struct JsonWriter {
+    out: String,
+    indent: Option<usize>,
+    // 正在输出的容器（按指针区分），用于发现循环引用
+    visiting: Vec<*const ()>,
+}
+
+impl JsonWriter {
+    fn value(&mut self, value: &Value, depth: usize) -> Result<(), String> {
+        match value {
+            Value::Nil => self.out.push_str("null"),
+            Value::Bool(b) => self.out.push_str(&b.to_string()),
+            Value::Number(n) => {
+                if !n.is_finite() {
+                    return Err(format!("Cannot serialize {} to JSON.", format_number(*n)));
+                }
+                self.out.push_str(&format_number(*n));
+            }
+            Value::String(s) => self.string(s),
+            Value::List(elements) => {
+                self.enter(Rc::as_ptr(elements) as *const ())?;
+                let elements = elements.borrow();
+                self.out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.newline(depth + 1);
+                    self.value(element, depth + 1)?;
+                }
+                if !elements.is_empty() {
+                    self.newline(depth);
+                }
+                self.out.push(']');
+                self.visiting.pop();
+            }
+            Value::Map(entries) => {
+                self.enter(Rc::as_ptr(entries) as *const ())?;
+                let entries = entries.borrow();
+                self.out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.newline(depth + 1);
+                    self.string(key);
+                    self.out.push(':');
+                    if self.indent.is_some() {
+                        self.out.push(' ');
+                    }
+                    self.value(value, depth + 1)?;
+                }
+                if !entries.is_empty() {
+                    self.newline(depth);
+                }
+                self.out.push('}');
+                self.visiting.pop();
+            }
+            Value::Function(..)
+            | Value::NativeFunction(_)
+            | Value::Memoized(_)
+            | Value::Class(_)
+            | Value::Instance(_) => {
+                return Err(format!("Cannot serialize {} to JSON.", value.type_name()));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, container: *const ()) -> Result<(), String> {
+        if self.visiting.contains(&container) {
+            return Err("Cannot serialize a cyclic structure to JSON.".to_string());
+        }
+        self.visiting.push(container);
+        Ok(())
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = self.indent {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(indent * depth));
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c if (c as u32) < 0x20 => self.out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
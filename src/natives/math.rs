@@ -0,0 +1,60 @@
+// 整数风格的运算。所有数字都是 f64，这里统一约定负数的取整方向
+
+// 向负无穷取整的除法，和 div 运算符一致：floor_div(-7, 2) == -4。
+// 目前没有 % 运算符；加入时余数应为 a - b * floor_div(a, b)（和除数同号），两者才配套
+pub fn floor_div(a: f64, b: f64) -> Result<f64, String> {
+    if b == 0.0 {
+        return Err("division by zero.".to_string());
+    }
+    Ok((a / b).floor())
+}
+
+// 向零取整的除法：trunc_div(-7, 2) == -3
+pub fn trunc_div(a: f64, b: f64) -> Result<f64, String> {
+    if b == 0.0 {
+        return Err("division by zero.".to_string());
+    }
+    Ok((a / b).trunc())
+}
+
+// 保留 decimals 位小数，.5 远离零舍入（和 round 一致）。先乘以 10^decimals 再舍入，
+// 1.005、2.675 这样不能精确表示的数可能舍入到任一边：roundTo(1.005, 2) 是 1，roundTo(2.675, 2) 是 2.68
+pub fn round_to(x: f64, decimals: f64) -> Result<f64, String> {
+    if decimals.fract() != 0.0 || !(0.0..=15.0).contains(&decimals) {
+        return Err("argument 2 must be an integer from 0 to 15.".to_string());
+    }
+    let scale = 10f64.powi(decimals as i32);
+    let scaled = x * scale;
+    // 放大后溢出或已经没有小数部分（很大的数），原样返回
+    if !scaled.is_finite() || scaled.fract() == 0.0 {
+        return Ok(x);
+    }
+    Ok(scaled.round() / scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_division_rounding() {
+        assert_eq!(floor_div(7.0, 2.0), Ok(3.0));
+        assert_eq!(floor_div(-7.0, 2.0), Ok(-4.0));
+        assert_eq!(floor_div(7.0, -2.0), Ok(-4.0));
+        assert_eq!(trunc_div(-7.0, 2.0), Ok(-3.0));
+        assert_eq!(trunc_div(7.0, -2.0), Ok(-3.0));
+        assert_eq!(floor_div(1.0, 0.0), Err("division by zero.".to_string()));
+        assert_eq!(trunc_div(1.0, 0.0), Err("division by zero.".to_string()));
+    }
+
+    #[test]
+    fn round_to_decimals() {
+        assert_eq!(round_to(1.23456, 2.0), Ok(1.23));
+        assert_eq!(round_to(-2.5, 0.0), Ok(-3.0));
+        assert_eq!(round_to(2.675, 2.0), Ok(2.68));
+        assert_eq!(round_to(1e300, 15.0), Ok(1e300));
+        for decimals in [-1.0, 1.5, 16.0] {
+            assert!(round_to(1.0, decimals).is_err(), "{}", decimals);
+        }
+    }
+}
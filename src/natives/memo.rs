@@ -0,0 +1,91 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use crate::interpreter::Value;
+
+// memoize(f) 返回的可调用值：按参数列表缓存 f 的结果
+#[derive(Debug)]
+pub struct Memoized {
+    pub function: Value,
+    pub cache: RefCell<HashMap<Vec<Key>, Value>>,
+    pub hits: Cell<usize>,
+    pub misses: Cell<usize>,
+}
+
+impl Memoized {
+    pub fn new(function: Value) -> Self {
+        Self {
+            function,
+            cache: RefCell::new(HashMap::new()),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+}
+
+// 可以作为缓存键的参数值；数字按位比较，0 和 -0 视为同一个键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Number(u64),
+    String(String),
+    Bool(bool),
+    Nil,
+}
+
+// 列表、映射和函数不能作为键，返回出错的类型名
+pub fn key(args: &[Value]) -> Result<Vec<Key>, &'static str> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::Number(n) if *n == 0.0 => Ok(Key::Number(0)),
+            Value::Number(n) => Ok(Key::Number(n.to_bits())),
+            Value::String(s) => Ok(Key::String(s.to_string())),
+            Value::Bool(b) => Ok(Key::Bool(*b)),
+            Value::Nil => Ok(Key::Nil),
+            other => Err(other.type_name()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::from(s)
+    }
+
+    #[test]
+    fn equal_arguments_give_equal_keys() {
+        let args = [Value::Number(1.5), string("a"), Value::Bool(true), Value::Nil];
+        assert_eq!(key(&args), key(&args.clone()));
+        assert_eq!(key(&[]), Ok(vec![]));
+        assert_ne!(key(&[string("a")]), key(&[string("b")]));
+        assert_ne!(key(&[Value::Number(1.0)]), key(&[string("1")]));
+        assert_ne!(key(&[Value::Number(1.0), Value::Nil]), key(&[Value::Nil, Value::Number(1.0)]));
+    }
+
+    #[test]
+    fn zero_and_negative_zero_share_a_key() {
+        assert_eq!(key(&[Value::Number(0.0)]), key(&[Value::Number(-0.0)]));
+        assert_ne!(key(&[Value::Number(0.0)]), key(&[Value::Number(f64::MIN_POSITIVE)]));
+        // NaN 按位比较，同一个 NaN 可以命中缓存
+        assert_eq!(key(&[Value::Number(f64::NAN)]), key(&[Value::Number(f64::NAN)]));
+    }
+
+    #[test]
+    fn lists_and_maps_are_not_keys() {
+        let list = Value::list(vec![]);
+        let map = Value::map(vec![]);
+        assert_eq!(key(&[Value::Nil, list]), Err("list"));
+        assert_eq!(key(&[map]), Err("map"));
+    }
+
+    #[test]
+    fn new_cache_is_empty() {
+        let memo = Memoized::new(Value::Nil);
+        assert!(memo.cache.borrow().is_empty());
+        assert_eq!((memo.hits.get(), memo.misses.get()), (0, 0));
+    }
+}
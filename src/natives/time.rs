@@ -0,0 +1,209 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// 时间来源。内置的时间函数都通过它取时间，嵌入方可以换成固定的时间以得到确定的输出
+pub trait TimeSource {
+    // 当前时间，Unix 纪元以来的秒数
+    fn now(&self) -> f64;
+    // 给定时刻本地时间相对 UTC 的偏移（秒）
+    fn utc_offset(&self, epoch: f64) -> i64;
+}
+
+// 系统时钟；本地时区从 /etc/localtime（或 TZ 指向的 TZif 文件）读取，读不到时按 UTC 处理
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs_f64())
+            .unwrap_or(0.0)
+    }
+
+    fn utc_offset(&self, epoch: f64) -> i64 {
+        let path = match std::env::var("TZ") {
+            Ok(tz) if tz.starts_with('/') => tz,
+            Ok(tz) if !tz.is_empty() => format!("/usr/share/zoneinfo/{}", tz),
+            _ => "/etc/localtime".to_string(),
+        };
+        fs::read(path)
+            .ok()
+            .and_then(|data| tzif_offset(&data, epoch.floor() as i64))
+            .unwrap_or(0)
+    }
+}
+
+// 固定的时间和时区偏移
+pub struct FixedTime {
+    pub epoch: f64,
+    pub offset: i64,
+}
+
+impl TimeSource for FixedTime {
+    fn now(&self) -> f64 {
+        self.epoch
+    }
+
+    fn utc_offset(&self, _: f64) -> i64 {
+        self.offset
+    }
+}
+
+// 日历时间的各个字段
+pub struct DateTime {
+    pub year: i64,
+    pub month: i64,
+    pub day: i64,
+    pub hour: i64,
+    pub minute: i64,
+    pub second: i64,
+}
+
+// epoch 秒数（已经加上时区偏移）转换为日历时间
+pub fn to_date_time(seconds: i64) -> DateTime {
+    let days = seconds.div_euclid(86_400);
+    let secs = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    DateTime {
+        year,
+        month,
+        day,
+        hour: secs / 3600,
+        minute: secs % 3600 / 60,
+        second: secs % 60,
+    }
+}
+
+// 支持 %Y %m %d %H %M %S %%，其他指令报错
+pub fn format(date: &DateTime, pattern: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", date.year)),
+            Some('m') => out.push_str(&format!("{:02}", date.month)),
+            Some('d') => out.push_str(&format!("{:02}", date.day)),
+            Some('H') => out.push_str(&format!("{:02}", date.hour)),
+            Some('M') => out.push_str(&format!("{:02}", date.minute)),
+            Some('S') => out.push_str(&format!("{:02}", date.second)),
+            Some('%') => out.push('%'),
+            Some(other) => return Err(format!("Unknown directive '%{}'.", other)),
+            None => return Err("Incomplete directive '%' at end of format.".to_string()),
+        }
+    }
+    Ok(out)
+}
+
+// 公历日期算法（Howard Hinnant, days_from_civil 的逆运算）
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// 读取 TZif 文件第一部分（32 位时间表），找到 epoch 时刻生效的偏移
+fn tzif_offset(data: &[u8], epoch: i64) -> Option<i64> {
+    if data.len() < 44 || &data[0..4] != b"TZif" {
+        return None;
+    }
+    let count = |i: usize| -> usize {
+        u32::from_be_bytes([data[20 + i * 4], data[21 + i * 4], data[22 + i * 4], data[23 + i * 4]])
+            as usize
+    };
+    let (time_count, type_count) = (count(3), count(4));
+    let transitions = 44;
+    let indices = transitions + time_count * 4;
+    let types = indices + time_count;
+    if type_count == 0 || data.len() < types + type_count * 6 {
+        return None;
+    }
+    let mut type_index = 0;
+    for i in 0..time_count {
+        let at = transitions + i * 4;
+        let time = i32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as i64;
+        if time > epoch {
+            break;
+        }
+        type_index = data[indices + i] as usize;
+    }
+    let at = types + type_index.min(type_count - 1) * 6;
+    Some(i32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(seconds: i64) -> String {
+        format(&to_date_time(seconds), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn epoch_seconds_to_calendar_time() {
+        assert_eq!(formatted(0), "1970-01-01 00:00:00");
+        assert_eq!(formatted(-1), "1969-12-31 23:59:59");
+        assert_eq!(formatted(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(formatted(951_868_799), "2000-02-29 23:59:59");
+        assert_eq!(formatted(4_107_542_400), "2100-03-01 00:00:00");
+        assert_eq!(formatted(1_700_000_000), "2023-11-14 22:13:20");
+    }
+
+    #[test]
+    fn format_directives() {
+        let date = to_date_time(1_700_000_000);
+        assert_eq!(format(&date, "100%% at %H:%M").unwrap(), "100% at 22:13");
+        assert_eq!(format(&date, "").unwrap(), "");
+        assert_eq!(format(&date, "%y").unwrap_err(), "Unknown directive '%y'.");
+        assert_eq!(format(&date, "%Y%").unwrap_err(), "Incomplete directive '%' at end of format.");
+    }
+
+    // 两个时区类型（+1h、+2h），一次在 epoch 1000 的切换
+    fn tzif() -> Vec<u8> {
+        let mut data = b"TZif2".to_vec();
+        data.resize(20, 0);
+        for count in [0u32, 0, 0, 1, 2, 0] {
+            data.extend_from_slice(&count.to_be_bytes());
+        }
+        data.extend_from_slice(&1000i32.to_be_bytes());
+        data.push(1);
+        for offset in [3600i32, 7200] {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn tzif_offset_follows_transitions() {
+        let data = tzif();
+        assert_eq!(tzif_offset(&data, 0), Some(3600));
+        assert_eq!(tzif_offset(&data, 999), Some(3600));
+        assert_eq!(tzif_offset(&data, 1000), Some(7200));
+        assert_eq!(tzif_offset(&data, 1_700_000_000), Some(7200));
+        assert_eq!(tzif_offset(b"not a tzif file", 0), None);
+        assert_eq!(tzif_offset(&data[..data.len() - 1], 0), None);
+    }
+
+    #[test]
+    fn fixed_time_source() {
+        let time = FixedTime {
+            epoch: 12.5,
+            offset: -3600,
+        };
+        assert_eq!(time.now(), 12.5);
+        assert_eq!(time.utc_offset(0.0), -3600);
+    }
+}
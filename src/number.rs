@@ -0,0 +1,37 @@
+// 数字转字符串，所有渲染数值的地方（print、evaluate、字符串拼接）都走这里，保证输出一致
+//
+// 使用最短往返表示（shortest round-trip），与语言环境无关：
+//
+//   0.1 + 0.2  -> 0.30000000000000004
+//   1 / 3      -> 0.3333333333333333
+//   7          -> 7
+//   -0.0       -> -0
+//   1e21       -> 1e21      (jlox: 1.0E21)
+//   0.00000001 -> 1e-8      (jlox: 1.0E-8)
+//   NaN        -> NaN
+//
+// 与 jlox 的差异：Java 的 Double.toString 在 >= 1e7 时就切换到科学计数法，
+// 这里和 JavaScript 一样，只在 >= 1e21 或 < 1e-7 时使用科学计数法
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    let abs = n.abs();
+    if abs >= 1e21 || (abs != 0.0 && abs < 1e-7) {
+        format!("{:e}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+// tokenize/parse 输出中的数字字面量，整数也保留 ".0"（CodeCrafters 要求的格式）
+pub fn format_literal(n: f64) -> String {
+    let mut value = n.to_string();
+    if !value.contains('.') {
+        value.push_str(".0");
+    }
+    value
+}
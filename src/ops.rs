@@ -0,0 +1,299 @@
+use std::{fmt, rc::Rc};
+
+use crate::{interpreter::Value, scanner::token::TokenType};
+
+// Lox 运算符的语义，不依赖语法树和解释器。解释器求值一元、二元运算时调用这里，
+// 出错时把 OpError 的信息和运算符所在的行包装成 RuntimeError；
+// 宿主程序可以直接对手里的 Value 使用同样的规则。
+// and/or/xor 需要控制操作数是否求值，由解释器处理，这里只提供 is_truthy
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpError {
+    pub message: String,
+}
+
+impl OpError {
+    fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for OpError {}
+
+// 只有 false 和 nil 为假，0、"" 和空列表都为真
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Nil => false,
+        _ => true,
+    }
+}
+
+// == 的语义
+pub fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        // 按 IEEE 754：NaN 和任何值（包括自己）都不相等；无穷大和同号的无穷大相等，
+        // 不能只用差值判断（inf - inf 是 NaN）。其他情况差值小于 EPSILON 视为相等
+        (Value::Number(l), Value::Number(r)) => {
+            !l.is_nan() && !r.is_nan() && (l == r || (l - r).abs() < f64::EPSILON)
+        }
+        // 共用存储（驻留）的字符串只需比较指针
+        (Value::String(l), Value::String(r)) => Rc::ptr_eq(l, r) || l == r,
+        (Value::Bool(l), Value::Bool(r)) => l == r,
+        (Value::Nil, Value::Nil) => true,
+        // 函数、列表、映射、类和实例按引用比较；内置函数按名字区分
+        (Value::NativeFunction(l), Value::NativeFunction(r)) => l.name == r.name,
+        (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+        (Value::Memoized(l), Value::Memoized(r)) => Rc::ptr_eq(l, r),
+        (Value::List(l), Value::List(r)) => Rc::ptr_eq(l, r),
+        (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+        (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+        (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+        // 不同类型的值不做隐式转换，一律不相等：1 == true、0 == false、"1" == 1、nil == false 都是 false
+        _ => false,
+    }
+}
+
+// 数字相加或字符串拼接
+pub fn add(left: &Value, right: &Value) -> Result<Value, OpError> {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+        (Value::String(l), Value::String(r)) => {
+            let mut joined = String::with_capacity(l.len() + r.len());
+            joined.push_str(l);
+            joined.push_str(r);
+            Ok(Value::from(joined))
+        }
+        _ => Err(OpError::new("Operands must be two numbers or two strings.")),
+    }
+}
+
+// - * / 和 div；除数为 0 时报错，div 向负无穷取整（-7 div 2 是 -4）
+pub fn arithmetic(op: TokenType, left: &Value, right: &Value) -> Result<Value, OpError> {
+    let (Value::Number(l), Value::Number(r)) = (left, right) else {
+        return Err(OpError::new("Operands must be numbers."));
+    };
+    match op {
+        TokenType::Minus => Ok(Value::Number(l - r)),
+        TokenType::Star => Ok(Value::Number(l * r)),
+        TokenType::Slash | TokenType::Div if *r == 0.0 => {
+            Err(OpError::new("Division by zero."))
+        }
+        TokenType::Slash => Ok(Value::Number(l / r)),
+        TokenType::Div => Ok(Value::Number((l / r).floor())),
+        _ => Err(unsupported(op)),
+    }
+}
+
+// > >= < <=，只能比较数字；和 NaN 比较的结果都是 false
+pub fn compare(op: TokenType, left: &Value, right: &Value) -> Result<Value, OpError> {
+    let (Value::Number(l), Value::Number(r)) = (left, right) else {
+        return Err(OpError::new("Operands must be numbers."));
+    };
+    let result = match op {
+        TokenType::Greater => l > r,
+        TokenType::GreaterEqual => l >= r,
+        TokenType::Less => l < r,
+        TokenType::LessEqual => l <= r,
+        _ => return Err(unsupported(op)),
+    };
+    Ok(Value::Bool(result))
+}
+
+// 二元运算符（不含 and/or/xor），op 是运算符 token 的类型，例如 TokenType::Plus
+pub fn binary(op: TokenType, left: &Value, right: &Value) -> Result<Value, OpError> {
+    match op {
+        TokenType::Plus => add(left, right),
+        TokenType::Minus | TokenType::Star | TokenType::Slash | TokenType::Div => {
+            arithmetic(op, left, right)
+        }
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+            compare(op, left, right)
+        }
+        TokenType::EqualEqual => Ok(Value::Bool(equals(left, right))),
+        TokenType::BangEqual => Ok(Value::Bool(!equals(left, right))),
+        _ => Err(unsupported(op)),
+    }
+}
+
+// 一元运算符 - 和 !
+pub fn unary(op: TokenType, right: &Value) -> Result<Value, OpError> {
+    match (op, right) {
+        (TokenType::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+        (TokenType::Minus, _) => Err(OpError::new("Invalid operand for unary operator")),
+        (TokenType::Bang, _) => Ok(Value::Bool(!is_truthy(right))),
+        _ => Err(unsupported(op)),
+    }
+}
+
+fn unsupported(op: TokenType) -> OpError {
+    OpError {
+        message: format!("Unsupported operator {}.", op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, scanner::token::Token};
+
+    // 每种类型一个值：数字、字符串、布尔值、nil、内置函数、函数、带缓存的函数、列表、映射、类、实例
+    fn samples() -> Vec<Value> {
+        let mut interpreter = Interpreter::new();
+        let captured = interpreter.run_and_capture(
+            "fun f() {} var m = memoize(f); class C {} var c = C();",
+        );
+        assert_eq!(captured.exit_code, 0, "{:?}", captured.diagnostics);
+        let global = |name: &str| {
+            let token = Token::new(TokenType::Identifier, name.to_string(), None, 1);
+            interpreter.env.get(&token).unwrap().unwrap()
+        };
+        vec![
+            Value::Number(1.5),
+            Value::from("a"),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Nil,
+            global("clock"),
+            global("f"),
+            global("m"),
+            Value::list(vec![Value::Number(1.0)]),
+            Value::map(vec![("k".to_string(), Value::Nil)]),
+            global("C"),
+            global("c"),
+        ]
+    }
+
+    fn kind(value: &Value) -> &'static str {
+        match value {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            _ => "other",
+        }
+    }
+
+    #[test]
+    fn only_false_and_nil_are_falsy() {
+        for value in samples() {
+            let falsy = matches!(value, Value::Bool(false) | Value::Nil);
+            assert_eq!(is_truthy(&value), !falsy, "{}", value);
+        }
+        for value in [Value::Number(0.0), Value::from(""), Value::list(vec![])] {
+            assert!(is_truthy(&value), "{}", value);
+        }
+    }
+
+    // 每个值只和自己相等，不同类型之间没有隐式转换
+    #[test]
+    fn equals_every_pair() {
+        let samples = samples();
+        for (i, left) in samples.iter().enumerate() {
+            for (j, right) in samples.iter().enumerate() {
+                assert_eq!(equals(left, right), i == j, "{} == {}", left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn equals_compares_strings_by_content_and_containers_by_reference() {
+        assert!(equals(&Value::from("ab"), &Value::from(String::from("ab"))));
+        assert!(!equals(&Value::from("ab"), &Value::from("aB")));
+        assert!(equals(&Value::Number(0.1 + 0.2), &Value::Number(0.3)));
+        assert!(equals(&Value::Number(f64::INFINITY), &Value::Number(f64::INFINITY)));
+        assert!(!equals(&Value::Number(f64::NAN), &Value::Number(f64::NAN)));
+        let list = Value::list(vec![]);
+        assert!(equals(&list, &list.clone()));
+        assert!(!equals(&list, &Value::list(vec![])));
+        assert!(!equals(&Value::map(vec![]), &Value::map(vec![])));
+    }
+
+    #[test]
+    fn add_every_pair() {
+        let samples = samples();
+        for left in &samples {
+            for right in &samples {
+                let result = add(left, right);
+                match (kind(left), kind(right)) {
+                    ("number", "number") => assert_eq!(result.unwrap().to_string(), "3"),
+                    ("string", "string") => assert_eq!(result.unwrap().to_string(), "aa"),
+                    _ => assert_eq!(
+                        result.unwrap_err().message,
+                        "Operands must be two numbers or two strings.",
+                        "{} + {}",
+                        left,
+                        right
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compare_every_pair() {
+        let samples = samples();
+        let ops = [
+            (TokenType::Greater, false),
+            (TokenType::GreaterEqual, true),
+            (TokenType::Less, false),
+            (TokenType::LessEqual, true),
+        ];
+        for (op, expected) in ops {
+            for left in &samples {
+                for right in &samples {
+                    let result = compare(op, left, right);
+                    match (kind(left), kind(right)) {
+                        ("number", "number") => {
+                            assert!(matches!(result, Ok(Value::Bool(b)) if b == expected))
+                        }
+                        _ => assert_eq!(
+                            result.unwrap_err().message,
+                            "Operands must be numbers.",
+                            "{} {} {}",
+                            left,
+                            op,
+                            right
+                        ),
+                    }
+                }
+            }
+        }
+        let (one, two) = (Value::Number(1.0), Value::Number(2.0));
+        assert!(matches!(compare(TokenType::Less, &one, &two), Ok(Value::Bool(true))));
+        assert!(matches!(compare(TokenType::Greater, &one, &two), Ok(Value::Bool(false))));
+        assert_eq!(
+            compare(TokenType::Plus, &one, &two).unwrap_err().message,
+            format!("Unsupported operator {}.", TokenType::Plus)
+        );
+    }
+
+    #[test]
+    fn arithmetic_and_unary_errors() {
+        let (seven, zero) = (Value::Number(7.0), Value::Number(0.0));
+        let result = |op, left: &Value, right: &Value| match binary(op, left, right) {
+            Ok(value) => value.to_string(),
+            Err(error) => error.message,
+        };
+        assert_eq!(result(TokenType::Slash, &seven, &zero), "Division by zero.");
+        assert_eq!(result(TokenType::Div, &seven, &zero), "Division by zero.");
+        assert_eq!(result(TokenType::Div, &Value::Number(-7.0), &Value::Number(2.0)), "-4");
+        assert_eq!(result(TokenType::Star, &seven, &Value::Nil), "Operands must be numbers.");
+        assert_eq!(result(TokenType::BangEqual, &seven, &Value::Nil), "true");
+        assert_eq!(
+            unary(TokenType::Minus, &Value::from("a")).unwrap_err().message,
+            "Invalid operand for unary operator"
+        );
+        assert!(matches!(unary(TokenType::Bang, &Value::Nil), Ok(Value::Bool(true))));
+        assert_eq!(
+            binary(TokenType::And, &seven, &seven).unwrap_err().message,
+            format!("Unsupported operator {}.", TokenType::And)
+        );
+    }
+}
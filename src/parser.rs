@@ -1,6 +1,11 @@
+#[allow(clippy::module_inception)]
 mod parser;
 mod error;
 pub mod expr;
 pub mod stmt;
+pub mod lower;
+pub mod printer;
+pub mod build;
 
+pub use error::ParseError;
 pub use parser::*;
\ No newline at end of file
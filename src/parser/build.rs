@@ -0,0 +1,290 @@
+use crate::scanner::token::{Token, TokenType};
+
+use super::{
+    expr::{Expr, Literal},
+    stmt::{Param, Stmt},
+};
+
+// 不经过扫描和解析直接构造语法树，供测试和生成 Lox 代码的工具使用：
+//   let expr = binary(num(1.0), "+", var_("x").call(vec![str_("a")]));
+//   let program = vec![var_stmt("x", Some(num(2.0))), print(expr)];
+// 构造出的 token 都在第 1 行，偏移和列号为 0。变量没有经过 Resolver，
+// 解释器运行时沿环境链按名字查找
+const LINE: usize = 1;
+
+fn token(token_type: TokenType, lexeme: &str) -> Token {
+    Token::new(token_type, lexeme.to_string(), None, LINE)
+}
+
+fn identifier(name: &str) -> Token {
+    token(TokenType::Identifier, name)
+}
+
+// 按源码中的写法得到运算符的 token，例如 "+"、"<="、"and"、"div"。
+// op 不是一个运算符时 panic：这是调用方的错误，不是 Lox 程序的错误
+fn operator(op: &str) -> Token {
+    let token_type = match op {
+        "+" => TokenType::Plus,
+        "-" => TokenType::Minus,
+        "*" => TokenType::Star,
+        "/" => TokenType::Slash,
+        "div" => TokenType::Div,
+        "!" => TokenType::Bang,
+        "==" => TokenType::EqualEqual,
+        "!=" => TokenType::BangEqual,
+        "<" => TokenType::Less,
+        "<=" => TokenType::LessEqual,
+        ">" => TokenType::Greater,
+        ">=" => TokenType::GreaterEqual,
+        "and" => TokenType::And,
+        "or" => TokenType::Or,
+        "xor" => TokenType::Xor,
+        _ => panic!("'{}' is not an operator", op),
+    };
+    token(token_type, op)
+}
+
+fn params(names: &[&str]) -> Vec<Param> {
+    names
+        .iter()
+        .map(|name| Param {
+            name: identifier(name),
+            annotation: None,
+        })
+        .collect()
+}
+
+pub fn num(value: f64) -> Expr {
+    Expr::Literal(Literal::Number(value))
+}
+
+pub fn str_(value: &str) -> Expr {
+    Expr::Literal(Literal::String(value.to_string()))
+}
+
+pub fn bool_(value: bool) -> Expr {
+    Expr::Literal(Literal::Bool(value))
+}
+
+pub fn nil() -> Expr {
+    Expr::Literal(Literal::Nil)
+}
+
+pub fn var_(name: &str) -> Expr {
+    Expr::Variable(identifier(name), None)
+}
+
+pub fn assign(name: &str, value: Expr) -> Expr {
+    Expr::Assign(identifier(name), Box::new(value), None)
+}
+
+// op 是 "-" 或 "!"
+pub fn unary(op: &str, right: Expr) -> Expr {
+    Expr::Unary(operator(op), Box::new(right))
+}
+
+// "and"、"or" 和 "xor" 和解析器一样得到 Expr::Logical，其他运算符得到 Expr::Binary
+pub fn binary(left: Expr, op: &str, right: Expr) -> Expr {
+    let operator = operator(op);
+    match operator.token_type {
+        TokenType::And | TokenType::Or | TokenType::Xor => {
+            Expr::Logical(Box::new(left), operator, Box::new(right))
+        }
+        _ => Expr::Binary(Box::new(left), operator, Box::new(right)),
+    }
+}
+
+pub fn group(inner: Expr) -> Expr {
+    Expr::Grouping(Box::new(inner))
+}
+
+pub fn call(callee: Expr, args: Vec<Expr>) -> Expr {
+    Expr::Call(Box::new(callee), token(TokenType::RightParen, ")"), args, false)
+}
+
+pub fn list(elements: Vec<Expr>) -> Expr {
+    Expr::List(elements)
+}
+
+pub fn index(list: Expr, index: Expr) -> Expr {
+    Expr::Index(Box::new(list), token(TokenType::LeftBracket, "["), Box::new(index), false)
+}
+
+pub fn get(object: Expr, name: &str) -> Expr {
+    Expr::Get(Box::new(object), identifier(name), false)
+}
+
+pub fn set(object: Expr, name: &str, value: Expr) -> Expr {
+    Expr::Set(Box::new(object), identifier(name), Box::new(value))
+}
+
+pub fn lambda(params_: &[&str], body: Vec<Stmt>) -> Expr {
+    Expr::Lambda(token(TokenType::Fun, "fun"), params(params_), None, body)
+}
+
+pub fn expr_stmt(expr: Expr) -> Stmt {
+    Stmt::Expression(expr)
+}
+
+pub fn print(expr: Expr) -> Stmt {
+    Stmt::Print(token(TokenType::Print, "print"), expr)
+}
+
+pub fn var_stmt(name: &str, initializer: Option<Expr>) -> Stmt {
+    Stmt::Var(identifier(name), None, initializer)
+}
+
+pub fn block(stmts: Vec<Stmt>) -> Stmt {
+    Stmt::Block(stmts)
+}
+
+pub fn if_(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Stmt {
+    Stmt::If(
+        token(TokenType::If, "if"),
+        condition,
+        Box::new(then_branch),
+        else_branch.map(Box::new),
+    )
+}
+
+pub fn while_(condition: Expr, body: Stmt) -> Stmt {
+    Stmt::While(token(TokenType::While, "while"), condition, Box::new(body))
+}
+
+pub fn function(name: &str, params_: &[&str], body: Vec<Stmt>) -> Stmt {
+    Stmt::Function(identifier(name), params(params_), None, body)
+}
+
+pub fn return_(value: Option<Expr>) -> Stmt {
+    Stmt::Return(token(TokenType::Return, "return"), value.into_iter().collect())
+}
+
+// 链式写法：var_("list").index(num(0.0)).call(vec![]).stmt()
+impl Expr {
+    pub fn call(self, args: Vec<Expr>) -> Expr {
+        call(self, args)
+    }
+    pub fn index(self, index_: Expr) -> Expr {
+        index(self, index_)
+    }
+    pub fn get(self, name: &str) -> Expr {
+        get(self, name)
+    }
+    pub fn stmt(self) -> Stmt {
+        expr_stmt(self)
+    }
+}
+
+impl From<Expr> for Stmt {
+    fn from(expr: Expr) -> Self {
+        expr_stmt(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use super::*;
+    use crate::interpreter::{Interpreter, Value};
+
+    // 收集 print 的输出
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // 不经过扫描和解析，直接执行构造出的语法树
+    fn run(program: Vec<Stmt>) -> String {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new();
+        interpreter.output = Box::new(Buffer(Rc::clone(&buffer)));
+        interpreter.interpret(program).expect("program should run");
+        let output = String::from_utf8(buffer.borrow().clone()).unwrap();
+        output
+    }
+
+    fn evaluate(expr: Expr) -> Value {
+        let mut interpreter = Interpreter::new();
+        let env = Rc::clone(&interpreter.env);
+        interpreter.evaluate(&expr, &env).expect("expression should evaluate")
+    }
+
+    #[test]
+    fn operators() {
+        let cases = [
+            (binary(num(1.0), "+", binary(num(2.0), "*", num(3.0))), "7"),
+            (binary(num(7.0), "div", num(2.0)), "3"),
+            (binary(str_("a"), "+", str_("b")), "ab"),
+            (binary(num(1.0), "<=", num(1.0)), "true"),
+            (binary(num(1.0), "!=", nil()), "true"),
+            (binary(bool_(false), "or", str_("x")), "x"),
+            (binary(bool_(true), "xor", bool_(true)), "false"),
+            (unary("!", unary("-", num(0.0))), "false"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(evaluate(expr).to_string(), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "'=' is not an operator")]
+    fn unknown_operator_panics() {
+        binary(var_("a"), "=", num(1.0));
+    }
+
+    #[test]
+    fn statements() {
+        let program = vec![
+            var_stmt("total", Some(num(0.0))),
+            var_stmt("i", Some(num(0.0))),
+            while_(
+                binary(var_("i"), "<", num(4.0)),
+                block(vec![
+                    assign("total", binary(var_("total"), "+", var_("i"))).stmt(),
+                    assign("i", binary(var_("i"), "+", num(1.0))).into(),
+                ]),
+            ),
+            if_(
+                binary(var_("total"), "==", num(6.0)),
+                print(str_("six")),
+                Some(print(var_("total"))),
+            ),
+        ];
+        assert_eq!(run(program), "six\n");
+    }
+
+    #[test]
+    fn functions_closures_and_lists() {
+        let program = vec![
+            function(
+                "counter",
+                &[],
+                vec![
+                    var_stmt("n", Some(num(0.0))),
+                    return_(Some(lambda(
+                        &[],
+                        vec![
+                            assign("n", binary(var_("n"), "+", num(1.0))).stmt(),
+                            return_(Some(var_("n"))),
+                        ],
+                    ))),
+                ],
+            ),
+            var_stmt("next", Some(var_("counter").call(vec![]))),
+            var_("next").call(vec![]).stmt(),
+            print(var_("next").call(vec![])),
+            var_stmt("xs", Some(list(vec![num(1.0), str_("two")]))),
+            print(var_("xs").index(num(1.0))),
+            print(call(var_("repr"), vec![var_("xs").index(num(1.0))])),
+        ];
+        assert_eq!(run(program), "2\ntwo\n\"two\"\n");
+    }
+}
@@ -1,8 +1,11 @@
 
+use crate::diagnostics::PARSE_ERROR;
+
 #[derive(Debug)]
 pub struct ParseError {
   message: String,
   line: usize,
+  code: &'static str,
 }
 
 impl ParseError {
@@ -10,12 +13,35 @@ impl ParseError {
     Self {
       message: message.to_string(),
       line,
+      code: PARSE_ERROR,
     }
   }
+  // 诊断代码，默认是 diagnostics::PARSE_ERROR
+  pub fn with_code(mut self, code: &'static str) -> Self {
+    self.code = code;
+    self
+  }
+  pub fn code(&self) -> &'static str {
+    self.code
+  }
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+  pub fn line(&self) -> usize {
+    self.line
+  }
+  // 在错误信息后追加提示
+  pub fn hint(mut self, hint: &str) -> Self {
+    self.message.push(' ');
+    self.message.push_str(hint);
+    self
+  }
 }
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
       write!(f, "[line {}] Error: {}", self.line, self.message)
     }
-}
\ No newline at end of file
+}
+
+impl std::error::Error for ParseError {}
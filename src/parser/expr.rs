@@ -1,6 +1,11 @@
 use std::fmt::{Display, Formatter};
 
-use crate::scanner::token::Token;
+use crate::{
+    number::format_literal,
+    scanner::token::{escape, Token},
+};
+
+use super::stmt::{Param, Stmt};
 
 #[derive(Debug, Clone)]
 pub enum Literal {
@@ -16,10 +21,47 @@ pub enum Expr {
     Unary(Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
     Grouping(Box<Expr>),
-    Variable(Token),
-    Assign(Token, Box<Expr>),
+    // 第二个字段由 Resolver 填入：变量所在作用域相对当前环境的距离
+    Variable(Token, Option<usize>),
+    // 和 Variable 一样，最后一个字段是 Resolver 填入的作用域距离
+    Assign(Token, Box<Expr>, Option<usize>),
     Logical(Box<Expr>, Token, Box<Expr>),
-    Call(Box<Expr>, Token, Vec<Expr>),
+    // Call 和 Index 的最后一个字段表示 f?(...) 和 xs?[i]，和 Get 的 ?. 一样在 nil 处短路
+    Call(Box<Expr>, Token, Vec<Expr>, bool),
+    List(Vec<Expr>),
+    Index(Box<Expr>, Token, Box<Expr>, bool),
+    // 属性读取 object.name 和属性赋值 object.name = value
+    // Get 的最后一个字段表示 object?.name，object 为 nil 时整条链的结果是 nil
+    Get(Box<Expr>, Token, bool),
+    Set(Box<Expr>, Token, Box<Expr>),
+    // this 和 super.method，和 Variable 一样带 Resolver 填入的作用域距离
+    This(Token, Option<usize>),
+    Super(Token, Token, Option<usize>),
+    // 匿名函数 fun (a, b) { ... }，第一个字段是 fun 关键字，其余和 Stmt::Function 相同
+    Lambda(Token, Vec<Param>, Option<Token>, Vec<Stmt>),
+}
+
+impl Expr {
+    // 表达式开头所在的行；字面量不带 token，没有行号
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Expr::Literal(_) => None,
+            Expr::Unary(op, _) => Some(op.line),
+            Expr::Binary(left, op, _) | Expr::Logical(left, op, _) | Expr::Index(left, op, ..) => {
+                left.line().or(Some(op.line))
+            }
+            Expr::Grouping(inner) => inner.line(),
+            Expr::Variable(name, _) | Expr::Assign(name, _, _) => Some(name.line),
+            Expr::Call(callee, paren, ..) => callee.line().or(Some(paren.line)),
+            Expr::List(elements) => elements.iter().find_map(Expr::line),
+            Expr::Get(object, name, _) | Expr::Set(object, name, _) => {
+                object.line().or(Some(name.line))
+            }
+            Expr::This(keyword, _) | Expr::Super(keyword, _, _) | Expr::Lambda(keyword, ..) => {
+                Some(keyword.line)
+            }
+        }
+    }
 }
 
 impl Display for Expr {
@@ -28,12 +70,46 @@ impl Display for Expr {
             Expr::Literal(l) => write!(f, "{}", l),
             Expr::Unary(op, e) => write!(f, "({} {e})", op.lexeme),
             Expr::Binary(l, op, r) => write!(f, "({} {l} {r})", op.lexeme),
-            Expr::Grouping(g) => write!(f, "(group {})", g),
-            Expr::Variable(t) => write!(f, "{}", t.lexeme),
-            Expr::Assign(t, e) => write!(f, "({} = {e})", t.lexeme),
+            Expr::Grouping(_) => {
+                // 连续嵌套的括号用循环展开，避免深度递归
+                let mut depth = 0;
+                let mut inner = self;
+                while let Expr::Grouping(g) = inner {
+                    depth += 1;
+                    inner = g;
+                }
+                write!(f, "{}{}{}", "(group ".repeat(depth), inner, ")".repeat(depth))
+            }
+            Expr::Variable(t, _) => write!(f, "{}", t.lexeme),
+            Expr::Assign(t, e, _) => write!(f, "({} = {e})", t.lexeme),
             Expr::Logical(l, op, r) => write!(f, "({} {l} {r})", op.lexeme),
-            Expr::Call(callee, paren, args) => {
-                write!(f, "{}({:?})", callee, args)
+            Expr::Call(callee, _, args, optional) => {
+                let optional = if *optional { "?" } else { "" };
+                write!(f, "{}{}({:?})", callee, optional, args)
+            }
+            Expr::List(elements) => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Index(list, _, index, false) => write!(f, "(index {list} {index})"),
+            Expr::Index(list, _, index, true) => write!(f, "(index? {list} {index})"),
+            Expr::Get(object, name, false) => write!(f, "(get {object} {})", name.lexeme),
+            Expr::Get(object, name, true) => write!(f, "(get? {object} {})", name.lexeme),
+            Expr::Set(object, name, value) => {
+                write!(f, "(set {object} {} {value})", name.lexeme)
+            }
+            Expr::This(_, _) => write!(f, "this"),
+            Expr::Super(_, method, _) => write!(f, "(super {})", method.lexeme),
+            Expr::Lambda(_, params, _, _) => {
+                write!(f, "(fun (")?;
+                for (i, param) in params.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { " " };
+                    write!(f, "{}{}", separator, param.name.lexeme)?;
+                }
+                write!(f, "))")
             }
         }
     }
@@ -41,14 +117,9 @@ impl Display for Expr {
 impl Display for Literal {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Literal::Number(n) => {
-                let mut value = n.to_string();
-                if !value.contains(".") {
-                    value.push_str(".0");
-                }
-                write!(f, "{}", value)
-            }
-            Literal::String(s) => write!(f, "{}", s),
+            Literal::Number(n) => write!(f, "{}", format_literal(*n)),
+            // 转义后语法树输出始终在一行内
+            Literal::String(s) => write!(f, "{}", escape(s)),
             Literal::Bool(b) => write!(f, "{}", b),
             Literal::Nil => write!(f, "nil"),
         }
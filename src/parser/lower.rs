@@ -0,0 +1,112 @@
+use super::{expr::Expr, stmt::Stmt};
+
+// Grouping 只在 parse 命令的 Lisp 输出中有意义（"(group ...)"），
+// run/evaluate 在解析之后把它们去掉，省去一次分配和一次转发
+pub fn strip_groups(expr: Expr) -> Expr {
+    match expr {
+        Expr::Grouping(mut inner) => {
+            while let Expr::Grouping(next) = *inner {
+                inner = next;
+            }
+            strip_groups(*inner)
+        }
+        Expr::Literal(_) | Expr::Variable(_, _) | Expr::This(_, _) | Expr::Super(_, _, _) => expr,
+        Expr::Unary(op, right) => Expr::Unary(op, Box::new(strip_groups(*right))),
+        Expr::Binary(left, op, right) => Expr::Binary(
+            Box::new(strip_groups(*left)),
+            op,
+            Box::new(strip_groups(*right)),
+        ),
+        Expr::Assign(name, value, depth) => {
+            Expr::Assign(name, Box::new(strip_groups(*value)), depth)
+        }
+        Expr::Logical(left, op, right) => Expr::Logical(
+            Box::new(strip_groups(*left)),
+            op,
+            Box::new(strip_groups(*right)),
+        ),
+        Expr::Call(callee, paren, args, optional) => Expr::Call(
+            Box::new(strip_groups(*callee)),
+            paren,
+            args.into_iter().map(strip_groups).collect(),
+            optional,
+        ),
+        Expr::List(elements) => Expr::List(elements.into_iter().map(strip_groups).collect()),
+        Expr::Index(list, bracket, index, optional) => Expr::Index(
+            Box::new(strip_groups(*list)),
+            bracket,
+            Box::new(strip_groups(*index)),
+            optional,
+        ),
+        Expr::Get(object, name, optional) => {
+            Expr::Get(Box::new(strip_groups(*object)), name, optional)
+        }
+        Expr::Set(object, name, value) => Expr::Set(
+            Box::new(strip_groups(*object)),
+            name,
+            Box::new(strip_groups(*value)),
+        ),
+        Expr::Lambda(keyword, params, return_type, body) => {
+            Expr::Lambda(keyword, params, return_type, strip_groups_stmts(body))
+        }
+    }
+}
+
+pub fn strip_groups_stmts(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    stmts.into_iter().map(strip_groups_stmt).collect()
+}
+
+fn strip_groups_stmt(stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::Expression(expr) => Stmt::Expression(strip_groups(expr)),
+        Stmt::Print(keyword, expr) => Stmt::Print(keyword, strip_groups(expr)),
+        Stmt::Var(name, annotation, initializer) => {
+            Stmt::Var(name, annotation, initializer.map(strip_groups))
+        }
+        Stmt::Class(name, superclass, methods) => {
+            Stmt::Class(name, superclass, strip_groups_stmts(methods))
+        }
+        Stmt::Destructure(targets, value) => Stmt::Destructure(targets, strip_groups(value)),
+        Stmt::Block(stmts) => Stmt::Block(strip_groups_stmts(stmts)),
+        Stmt::If(keyword, condition, then_branch, else_branch) => Stmt::If(
+            keyword,
+            strip_groups(condition),
+            Box::new(strip_groups_stmt(*then_branch)),
+            else_branch.map(|stmt| Box::new(strip_groups_stmt(*stmt))),
+        ),
+        Stmt::While(keyword, condition, body) => Stmt::While(
+            keyword,
+            strip_groups(condition),
+            Box::new(strip_groups_stmt(*body)),
+        ),
+        Stmt::For(keyword, initializer, condition, increment, body) => Stmt::For(
+            keyword,
+            initializer.map(|stmt| Box::new(strip_groups_stmt(*stmt))),
+            condition.map(strip_groups),
+            increment.map(strip_groups),
+            Box::new(strip_groups_stmt(*body)),
+        ),
+        Stmt::Function(name, params, return_type, body) => {
+            Stmt::Function(name, params, return_type, strip_groups_stmts(body))
+        }
+        Stmt::Return(keyword, values) => {
+            Stmt::Return(keyword, values.into_iter().map(strip_groups).collect())
+        }
+        Stmt::Loop(keyword, body) => Stmt::Loop(keyword, Box::new(strip_groups_stmt(*body))),
+        Stmt::Labeled(label, body) => Stmt::Labeled(label, Box::new(strip_groups_stmt(*body))),
+        Stmt::ForIn(keyword, name, iterable, body) => Stmt::ForIn(
+            keyword,
+            name,
+            strip_groups(iterable),
+            Box::new(strip_groups_stmt(*body)),
+        ),
+        Stmt::Break(..) | Stmt::Continue(..) => stmt,
+        Stmt::Match(keyword, scrutinee, arms) => Stmt::Match(
+            keyword,
+            strip_groups(scrutinee),
+            arms.into_iter()
+                .map(|(pattern, body)| (pattern, strip_groups_stmt(body)))
+                .collect(),
+        ),
+    }
+}
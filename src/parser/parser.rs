@@ -1,60 +1,300 @@
-use std::{cell::RefCell, rc::Rc};
-
 // Grammar in grammar.txt file
-use crate::{environment::Environment, scanner::token::{Token, TokenType}};
+use crate::{
+    diagnostics::LIMIT_EXCEEDED,
+    limits::Limits,
+    location::{self, Phase},
+    scanner::{
+        keywords,
+        token::{Token, TokenType},
+    },
+};
 
 use super::{
     error::ParseError,
-    expr::{self, Expr, Literal},
-    stmt::Stmt,
+    expr::{Expr, Literal},
+    stmt::{Param, Pattern, Stmt},
 };
 
+// 参数列表、返回类型注解和函数体
+type FunctionParts = (Vec<Param>, Option<Token>, Vec<Stmt>);
+
 pub struct Parser<'a> {
     tokens: &'a [Token], // slice
     current: usize,
+    // 当前嵌套的循环层数，用于检查 break/continue 是否在循环内
+    loop_depth: usize,
+    // 当前表达式的嵌套层数，超过 MAX_NESTING 时报错而不是栈溢出
+    nesting: usize,
+    // 正在解析的类（由外到内），值表示该类是否有父类；用于检查 this/super 的位置
+    classes: Vec<bool>,
+    // 是否在 init 方法内，init 不能 return 一个值
+    in_initializer: bool,
+    // 不影响后续解析的错误（例如无效的赋值目标）记录在这里，解析继续进行
+    errors: Vec<ParseError>,
+    // 语法树节点数超过时报错并停止解析，默认是 Limits::default() 中的值
+    pub max_nodes: usize,
+    // 已经解析的语句和操作数
+    nodes: usize,
 }
 
+const MAX_NESTING: usize = 10_000;
+
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            loop_depth: 0,
+            nesting: 0,
+            classes: vec![],
+            in_initializer: false,
+            errors: vec![],
+            max_nodes: Limits::default().max_nodes,
+            nodes: 0,
+        }
     }
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
+    // 出错后同步到下一条语句继续解析，一次报告所有语法错误
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = Vec::new();
+        let mut errors = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            let result = self.declaration();
+            errors.append(&mut self.errors);
+            match result {
+                Ok(stmt) => statements.push(stmt),
+                // 超出规模限制时不再继续解析
+                Err(error) if error.code() == LIMIT_EXCEEDED => {
+                    errors.push(error);
+                    break;
+                }
+                Err(error) => {
+                    errors.push(self.keyword_hint(error));
+                    self.synchronize();
+                }
+            }
+        }
+        location::clear();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
+    }
+    // 丢弃 token 直到语句边界：刚越过一个 `;`，或者下一个 token 是语句开头的关键字
+    fn synchronize(&mut self) {
+        // 出错时可能停在类或函数内部，恢复到顶层的解析状态
+        self.classes.clear();
+        self.in_initializer = false;
+        self.loop_depth = 0;
+        self.nesting = 0;
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
         }
-        Ok(statements)
     }
     pub fn parse_expr(&mut self) -> Result<Expr, ParseError> {
-        self.expression()
+        // 空文件或只有空白、注释
+        if self.is_at_end() {
+            return Err(ParseError::new(
+                "Expected an expression but found end of input.",
+                self.peek().line,
+            ));
+        }
+        let result = self.expression();
+        location::clear();
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0));
+        }
+        let expr = result.map_err(|error| self.keyword_hint(error))?;
+        // parse、evaluate 只处理一个表达式，后面还有内容（例如 "1 + 1; 2 + 2;"）时报错，不忽略
+        if !self.is_at_end() {
+            return Err(ParseError::new(
+                "Unexpected tokens after expression.",
+                self.peek().line,
+            ));
+        }
+        Ok(expr)
+    }
+    // 出错的 token 或紧挨在它前面的 token 是像 let、function、True 这样的标识符时，
+    // 很可能是把它当成了关键字，在错误信息后提示对应的 Lox 关键字。
+    // 同一语句中更早出现的标识符和出错无关（var True = 1; print True +; 不提示）
+    fn keyword_hint(&self, error: ParseError) -> ParseError {
+        let at = self.current.min(self.tokens.len() - 1);
+        let suggestion = [Some(at), at.checked_sub(1)].into_iter().flatten().find_map(|i| {
+            let token = &self.tokens[i];
+            if token.token_type != TokenType::Identifier {
+                return None;
+            }
+            keywords::suggestion(&token.lexeme)
+        });
+        match suggestion {
+            Some(keyword) => error.hint(&format!("Did you mean '{}'?", keyword)),
+            None => error,
+        }
     }
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
+        self.count_node()?;
         if self.matches(&[TokenType::Var]) {
             return self.var_declaration();
         }
-        if self.matches(&[TokenType::Fun]) {
-            return self.function();
+        if self.matches(&[TokenType::Class]) {
+            return self.class_declaration();
         }
-        return self.statement();
+        // fun 后面不是函数名时是匿名函数，交给表达式语句解析
+        if self.check(TokenType::Fun) && self.check_next(TokenType::Identifier) {
+            self.advance();
+            return self.function(false);
+        }
+        self.statement()
+    }
+    // classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" ( field | function )* "}" ;
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect class name.")?
+            .clone();
+        let superclass = if self.matches(&[TokenType::Less]) {
+            let superclass = self
+                .consume(TokenType::Identifier, "Expect superclass name.")?
+                .clone();
+            if superclass.lexeme == name.lexeme {
+                return Err(ParseError::new(
+                    "A class can't inherit from itself.",
+                    superclass.line,
+                ));
+            }
+            Some(Expr::Variable(superclass, None))
+        } else {
+            None
+        };
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        self.classes.push(superclass.is_some());
+        let mut methods = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let member = if self.matches(&[TokenType::Var]) {
+                self.field()
+            } else {
+                let initializer = self.peek().lexeme == "init";
+                self.function(initializer)
+            };
+            match member.and_then(|member| self.check_member(&methods, member)) {
+                Ok(member) => methods.push(member),
+                Err(error) => {
+                    self.classes.pop();
+                    return Err(error);
+                }
+            }
+        }
+        self.classes.pop();
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(name, superclass, methods))
+    }
+    // field          → "var" IDENTIFIER ( "=" expression )? ";" ;
+    // 默认值在创建实例时、调用 init 之前求值，不能使用 this 和 super
+    fn field(&mut self) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect field name.")?
+            .clone();
+        let initializer = if self.matches(&[TokenType::Equal]) {
+            let classes = std::mem::take(&mut self.classes);
+            let initializer = self.expression();
+            self.classes = classes;
+            Some(initializer?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after field declaration.")?;
+        Ok(Stmt::Var(name, None, initializer))
+    }
+    // 同一个类中字段不能重复声明，也不能和方法同名
+    fn check_member(&self, members: &[Stmt], member: Stmt) -> Result<Stmt, ParseError> {
+        let (Stmt::Var(name, ..) | Stmt::Function(name, ..)) = &member else {
+            return Ok(member);
+        };
+        for existing in members {
+            let message = match (existing, &member) {
+                (Stmt::Var(other, ..), Stmt::Var(..)) if other.lexeme == name.lexeme => {
+                    format!("Field '{}' is already declared in this class.", name.lexeme)
+                }
+                (Stmt::Var(other, ..), Stmt::Function(..))
+                | (Stmt::Function(other, ..), Stmt::Var(..))
+                    if other.lexeme == name.lexeme =>
+                {
+                    format!("A field and a method can't both be named '{}'.", name.lexeme)
+                }
+                _ => continue,
+            };
+            return Err(ParseError::new(&message, name.line));
+        }
+        Ok(member)
     }
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
         let name = self
             .consume(TokenType::Identifier, "Expect variable name.")?
             .clone();
+        let annotation = self.type_annotation()?;
+        if self.check(TokenType::Comma) {
+            return self.destructure(Param { name, annotation });
+        }
         let initializer = if self.matches(&[TokenType::Equal]) {
             Some(self.expression()?)
         } else {
             None
         };
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        return Ok(Stmt::Var(name, initializer));
+        Ok(Stmt::Var(name, annotation, initializer))
+    }
+    // 多个变量一起声明：var q, r = divmod(7, 2); 右边必须是长度相同的列表
+    fn destructure(&mut self, first: Param) -> Result<Stmt, ParseError> {
+        let mut targets = vec![first];
+        while self.matches(&[TokenType::Comma]) {
+            let name = self
+                .consume(TokenType::Identifier, "Expect variable name.")?
+                .clone();
+            let annotation = self.type_annotation()?;
+            targets.push(Param { name, annotation });
+        }
+        self.consume(TokenType::Equal, "Expect '=' after variable names.")?;
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Destructure(targets, value))
     }
-    // function       → IDENTIFIER "(" parameters? ")" block ;
-    fn function(&mut self) -> Result<Stmt, ParseError> {
+    // typeAnnotation → ":" ( IDENTIFIER | "nil" ) ;
+    // 类型名在这里不做检查，run --check-types 执行时才会校验
+    fn type_annotation(&mut self) -> Result<Option<Token>, ParseError> {
+        if !self.matches(&[TokenType::Colon]) {
+            return Ok(None);
+        }
+        if self.matches(&[TokenType::Identifier, TokenType::Nil]) {
+            return Ok(Some(self.previous().clone()));
+        }
+        Err(ParseError::new("Expect type name after ':'.", self.peek().line))
+    }
+    // function       → IDENTIFIER "(" parameters? ")" typeAnnotation? block ;
+    fn function(&mut self, initializer: bool) -> Result<Stmt, ParseError> {
         let name = self
             .consume(TokenType::Identifier, "Expect function name.")?
             .clone();
         self.consume(TokenType::LeftParen, "Expect '(' after function name.")?;
+        let (parameters, return_type, body) = self.function_rest(initializer)?;
+        Ok(Stmt::Function(name, parameters, return_type, body))
+    }
+    // 函数声明和匿名函数共用：从 "(" 之后的参数列表解析到函数体结束
+    fn function_rest(&mut self, initializer: bool) -> Result<FunctionParts, ParseError> {
         let mut parameters = vec![];
         if !self.check(TokenType::RightParen) {
             loop {
@@ -65,18 +305,26 @@ impl<'a> Parser<'a> {
                     ));
                 }
 
-                parameters.push(
-                    self.consume(TokenType::Identifier, "Expect parameter name.")?
-                        .clone(),
-                );
+                let name = self
+                    .consume(TokenType::Identifier, "Expect parameter name.")?
+                    .clone();
+                let annotation = self.type_annotation()?;
+                parameters.push(Param { name, annotation });
                 if !self.matches(&[TokenType::Comma]) {
                     break;
                 }
             }
         }
         self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        let return_type = self.type_annotation()?;
         self.consume(TokenType::LeftBrace, "Expect '{' before block.")?;
-        Ok(Stmt::Function(name, parameters, Box::new(self.block()?)))
+        // 函数体内的 break/continue 不能跳出外层的循环
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+        let enclosing_initializer = std::mem::replace(&mut self.in_initializer, initializer);
+        let body = self.block();
+        self.loop_depth = enclosing_loops;
+        self.in_initializer = enclosing_initializer;
+        Ok((parameters, return_type, body?))
     }
     fn statement(&mut self) -> Result<Stmt, ParseError> {
         if self.matches(&[TokenType::Print]) {
@@ -97,13 +345,44 @@ impl<'a> Parser<'a> {
         if self.matches(&[TokenType::Return]) {
             return self.return_statement();
         }
+        if self.matches(&[TokenType::Loop]) {
+            return self.loop_statement();
+        }
+        if self.matches(&[TokenType::Break, TokenType::Continue]) {
+            return self.jump_statement();
+        }
+        if self.matches(&[TokenType::Match]) {
+            return self.match_statement();
+        }
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::Colon) {
+            return self.labeled_statement();
+        }
         self.expression_stmt()
     }
+    // labeledStmt    → IDENTIFIER ":" ( whileStmt | forStmt | forInStmt | loopStmt ) ;
+    // 标签是否有定义、是否重复由 Resolver 检查，它同时算出 break/continue 要跳出的循环层数
+    fn labeled_statement(&mut self) -> Result<Stmt, ParseError> {
+        let label = self.advance().clone();
+        self.advance();
+        if !matches!(
+            self.peek().token_type,
+            TokenType::While | TokenType::For | TokenType::Loop
+        ) {
+            return Err(ParseError::new(
+                &format!("Expect a loop after label '{}'.", label.lexeme),
+                self.peek().line,
+            ));
+        }
+        let body = self.statement()?;
+        Ok(Stmt::Labeled(label, Box::new(body)))
+    }
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(value))
+        Ok(Stmt::Print(keyword, value))
     }
+    // 块在 "}" 处结束，不会消耗其后的 ";"，所以 if/while/函数声明等以块结尾的语句都不需要分号
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
@@ -113,26 +392,126 @@ impl<'a> Parser<'a> {
         Ok(statements)
     }
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after 'if'.")?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
         let then_branch = self.statement()?;
         let else_branch = if self.matches(&[TokenType::Else]) {
             Some(Box::new(self.statement()?))
         } else {
             None
         };
-        Ok(Stmt::If(condition, Box::new(then_branch), else_branch))
+        Ok(Stmt::If(keyword, condition, Box::new(then_branch), else_branch))
     }
     fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after 'while'.")?;
-        let body = Box::new(self.statement()?);
-        Ok(Stmt::While(condition, body))
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::While(keyword, condition, body))
+    }
+    // loopStmt       → "loop" block ;
+    fn loop_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if !self.check(TokenType::LeftBrace) {
+            return Err(ParseError::new("Expect '{' after 'loop'.", self.peek().line));
+        }
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::Loop(keyword, body))
+    }
+    // breakStmt      → "break" IDENTIFIER? ";" ;
+    // continueStmt   → "continue" IDENTIFIER? ";" ;
+    fn jump_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            return Err(ParseError::new(
+                &format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+                keyword.line,
+            ));
+        }
+        // 要跳出的循环层数先记为 0，带标签时由 Resolver 填写
+        let label = if self.matches(&[TokenType::Identifier]) {
+            Some(self.previous().clone())
+        } else {
+            None
+        };
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expect ';' after '{}'.", keyword.lexeme),
+        )?;
+        if keyword.token_type == TokenType::Break {
+            Ok(Stmt::Break(keyword, label, 0))
+        } else {
+            Ok(Stmt::Continue(keyword, label, 0))
+        }
+    }
+    // matchStmt      → "match" expression "{" ( pattern "=>" statement ","? )* "}" ;
+    fn match_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let scrutinee = self.expression()?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after match value.")?;
+        let mut arms = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            self.consume(TokenType::Arrow, "Expect '=>' after pattern.")?;
+            let body = self.statement()?;
+            self.matches(&[TokenType::Comma]);
+            arms.push((pattern, body));
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after match arms.")?;
+        Ok(Stmt::Match(keyword, scrutinee, arms))
+    }
+    // pattern        → "_" | "-"? NUMBER | STRING | "true" | "false" | "nil"
+    //                | typeName IDENTIFIER? ;
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        if self.matches(&[TokenType::Identifier]) {
+            let name = self.previous().clone();
+            if name.lexeme == "_" {
+                return Ok(Pattern::Wildcard);
+            }
+            if !matches!(
+                name.lexeme.as_str(),
+                "number" | "string" | "bool" | "function" | "list" | "map"
+            ) {
+                return Err(ParseError::new(
+                    &format!("Unknown type '{}' in pattern.", name.lexeme),
+                    name.line,
+                ));
+            }
+            let binding = if self.matches(&[TokenType::Identifier]) {
+                Some(self.previous().clone())
+            } else {
+                None
+            };
+            return Ok(Pattern::Type(name, binding));
+        }
+        if self.matches(&[TokenType::Minus]) {
+            let minus = self.previous().clone();
+            let number = self.consume(TokenType::Number, "Expect number after '-'.")?.clone();
+            return match self.convert_token_literal(number)? {
+                Literal::Number(n) => Ok(Pattern::Literal(Literal::Number(-n))),
+                _ => Err(ParseError::new("Expect number after '-'.", minus.line)),
+            };
+        }
+        match self.literal()? {
+            Expr::Literal(literal) => Ok(Pattern::Literal(literal)),
+            _ => Err(ParseError::new("Expect pattern.", self.previous().line)),
+        }
+    }
+    fn loop_body(&mut self) -> Result<Stmt, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
     }
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        if self.is_for_in() {
+            return self.for_in_statement(keyword);
+        }
         let initializer = if self.matches(&[TokenType::Semicolon]) {
             None
         } else if self.matches(&[TokenType::Var]) {
@@ -156,19 +535,51 @@ impl<'a> Parser<'a> {
         };
 
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
-        let body = Box::new(self.statement()?);
-        Ok(Stmt::For(initializer, condition, increment, body))
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::For(keyword, initializer, condition, increment, body))
+    }
+    // forInStmt      → "for" "(" "var"? IDENTIFIER "in" expression ")" statement ;
+    // "in" 不是关键字，只在这个位置有特殊含义
+    fn for_in_statement(&mut self, keyword: Token) -> Result<Stmt, ParseError> {
+        self.matches(&[TokenType::Var]);
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
+        self.advance();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::ForIn(keyword, name, iterable, body))
+    }
+    // 向后看几个 token，判断是不是 for (var x in ...) 或 for (x in ...)
+    fn is_for_in(&self) -> bool {
+        let offset = if self.check(TokenType::Var) { 1 } else { 0 };
+        let token = |i: usize| self.tokens.get(self.current + offset + i);
+        token(0).is_some_and(|t| t.token_type == TokenType::Identifier)
+            && token(1).is_some_and(|t| t.token_type == TokenType::Identifier && t.lexeme == "in")
     }
 
-    // returnStmt     → "return" expression? ";" ;
+    // returnStmt     → "return" ( expression ( "," expression )* )? ";" ;
     fn return_statement(&mut self) -> Result<Stmt, ParseError> {
-        let expr = if !self.check(TokenType::Semicolon) {
-            Some(self.expression()?)
+        let keyword = self.previous().clone();
+        let values = if !self.check(TokenType::Semicolon) {
+            if self.in_initializer {
+                return Err(ParseError::new(
+                    "Can't return a value from an initializer.",
+                    keyword.line,
+                ));
+            }
+            // return a, b; 返回多个值，只有 var q, r = f(); 会用到第一个之后的值
+            let mut values = vec![self.expression()?];
+            while self.matches(&[TokenType::Comma]) {
+                values.push(self.expression()?);
+            }
+            values
         } else {
-            None
+            Vec::new()
         };
         self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
-        Ok(Stmt::Return(expr)) 
+        Ok(Stmt::Return(keyword, values))
     }
 
     fn expression_stmt(&mut self) -> Result<Stmt, ParseError> {
@@ -178,27 +589,63 @@ impl<'a> Parser<'a> {
         }
         Ok(Stmt::Expression(expr))
     }
+    fn count_node(&mut self) -> Result<(), ParseError> {
+        self.nodes += 1;
+        if self.nodes > self.max_nodes {
+            let message = format!("Too many syntax tree nodes: limit is {}.", self.max_nodes);
+            return Err(ParseError::new(&message, self.peek().line).with_code(LIMIT_EXCEEDED));
+        }
+        Ok(())
+    }
     // *******解析器处理表达式时，优先从低优先级的运算符解析到高优先级的运算符************
     // expression     → assignment ;
     fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.assignment()
+        if self.nesting >= MAX_NESTING {
+            return Err(ParseError::new(
+                "Expression nesting is too deep.",
+                self.peek().line,
+            ));
+        }
+        self.nesting += 1;
+        let expr = self.assignment();
+        self.nesting -= 1;
+        expr
     }
-    // assignment     → IDENTIFIER "=" assignment | anonFunc | logic_or ;
+    // assignment     → IDENTIFIER "=" assignment | logic_or ;
     fn assignment(&mut self) -> Result<Expr, ParseError> {
         let expr = self.or()?;
         if self.matches(&[TokenType::Equal]) {
             let equals = self.previous().clone();
             let value = self.assignment()?;
-            if let Expr::Variable(name) = expr {
-                return Ok(Expr::Assign(name, Box::new(value)));
-            }
-            return Err(ParseError::new("Invalid assignment target.", equals.line));
+            let message = match expr {
+                Expr::Variable(name, _) => return Ok(Expr::Assign(name, Box::new(value), None)),
+                // object?.name 可能是 nil，不能作为赋值目标
+                Expr::Get(object, name, false) => return Ok(Expr::Set(object, name, Box::new(value))),
+                Expr::Grouping(_) => "Cannot assign to a grouped expression; remove the parentheses.",
+                Expr::Binary(..) => "Cannot assign to the result of a binary expression.",
+                Expr::Literal(_) => "Cannot assign to a literal.",
+                _ => "Invalid assignment target.",
+            };
+            // 赋值目标无效不影响后面的解析，记录错误后继续
+            self.errors.push(ParseError::new(message, equals.line));
+            return Ok(expr);
         }
         Ok(expr)
     }
     fn or(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.and()?;
+        let mut expr = self.xor()?;
         while self.matches(&[TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.xor()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right))
+        }
+        Ok(expr)
+    }
+    // logic_xor      → logic_and ( "xor" logic_and )* ;
+    // 优先级在 or 和 and 之间：a or b xor c and d 是 a or (b xor (c and d))
+    fn xor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        while self.matches(&[TokenType::Xor]) {
             let operator = self.previous().clone();
             let right = self.and()?;
             expr = Expr::Logical(Box::new(expr), operator, Box::new(right))
@@ -250,33 +697,96 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     // factor         → unary ( ( "/" | "*" ) unary )* ;
+    // factor         → unary ( ( "/" | "*" | "div" ) unary )* ;
+    // div 是上下文关键字：只在运算符的位置表示整除，其他地方仍然可以作为变量名
     fn factor(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.unary()?;
-        while self.matches(&[TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous().clone();
+        loop {
+            let operator = if self.matches(&[TokenType::Slash, TokenType::Star]) {
+                self.previous().clone()
+            } else if self.check(TokenType::Identifier) && self.peek().lexeme == "div" {
+                let mut operator = self.advance().clone();
+                operator.token_type = TokenType::Div;
+                operator
+            } else {
+                break;
+            };
             let right = self.unary()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right))
         }
         Ok(expr)
     }
     // unary          → ( "!" | "-" ) unary | call ;
+    // 用循环收集前缀运算符，避免很长的 `!!!!...` 链递归过深
     fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.matches(&[TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous().clone();
-            let right = self.unary()?;
-            return Ok(Expr::Unary(operator, Box::new(right)));
+        self.count_node()?;
+        let mut operators = vec![];
+        while self.matches(&[TokenType::Bang, TokenType::Minus]) {
+            operators.push(self.previous().clone());
+        }
+        let mut expr = self.call()?;
+        while let Some(operator) = operators.pop() {
+            expr = Expr::Unary(operator, Box::new(expr));
         }
-        self.call()
+        Ok(expr)
     }
     // primary        → NUMBER | STRING | "true" | "false" | "nil"
-    //                | "(" expression ")" ;
+    //                | "(" expression ")" | anonFunc ;
     fn primary(&mut self) -> Result<Expr, ParseError> {
         if self.matches(&[TokenType::LeftParen]) {
             let expr = self.expression()?;
             self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
-            return Ok(Expr::Grouping(Box::new(expr)))
+            Ok(Expr::Grouping(Box::new(expr)))
         } else if self.matches(&[TokenType::Identifier]) {
-            return Ok(Expr::Variable(self.previous().clone()))
+            Ok(Expr::Variable(self.previous().clone(), None))
+        } else if self.matches(&[TokenType::This]) {
+            let keyword = self.previous().clone();
+            if self.classes.is_empty() {
+                return Err(ParseError::new(
+                    "Can't use 'this' outside of a class.",
+                    keyword.line,
+                ));
+            }
+            Ok(Expr::This(keyword, None))
+        } else if self.matches(&[TokenType::Super]) {
+            let keyword = self.previous().clone();
+            match self.classes.last() {
+                None => {
+                    return Err(ParseError::new(
+                        "Can't use 'super' outside of a class.",
+                        keyword.line,
+                    ))
+                }
+                Some(false) => {
+                    return Err(ParseError::new(
+                        "Can't use 'super' in a class with no superclass.",
+                        keyword.line,
+                    ))
+                }
+                Some(true) => {}
+            }
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self
+                .consume(TokenType::Identifier, "Expect superclass method name.")?
+                .clone();
+            Ok(Expr::Super(keyword, method, None))
+        } else if self.matches(&[TokenType::LeftBracket]) {
+            let mut elements = vec![];
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.matches(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+            Ok(Expr::List(elements))
+        } else if self.matches(&[TokenType::Fun]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::LeftParen, "Expect '(' after 'fun'.")?;
+            let (parameters, return_type, body) = self.function_rest(false)?;
+            Ok(Expr::Lambda(keyword, parameters, return_type, body))
         } else {
             self.literal()
         }
@@ -289,50 +799,91 @@ impl<'a> Parser<'a> {
             TokenType::Number,
             TokenType::String,
         ]) {
-            return Ok(Expr::Literal(
-                self.convert_token_literal(self.previous().clone())?,
-            ));
+            let literal = self.convert_token_literal(self.previous().clone())?;
+            if let Literal::String(value) = literal {
+                return Ok(Expr::Literal(Literal::String(self.adjacent_strings(value))));
+            }
+            return Ok(Expr::Literal(literal));
         }
         Err(ParseError::new("Expect expression.", self.peek().line))
     }
+    // 相邻的字符串字面量在解析时合并为一个，例如 "part one " "part two"
+    // tokenize 仍然输出独立的 STRING token
+    fn adjacent_strings(&mut self, mut value: String) -> String {
+        while self.check(TokenType::String) {
+            if let Some(literal) = &self.advance().literal {
+                value.push_str(literal);
+            }
+        }
+        value
+    }
     fn call(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.primary()?;
-        while self.matches(&[TokenType::LeftParen]) {
-            expr = self.finish_call(expr)?;
+        loop {
+            if self.matches(&[TokenType::LeftParen, TokenType::QuestionParen]) {
+                let optional = self.previous().token_type == TokenType::QuestionParen;
+                expr = self.finish_call(expr, optional)?;
+            } else if self.matches(&[TokenType::Dot, TokenType::QuestionDot]) {
+                let optional = self.previous().token_type == TokenType::QuestionDot;
+                let message = if optional {
+                    "Expect property name after '?.'."
+                } else {
+                    "Expect property name after '.'."
+                };
+                let name = self.consume(TokenType::Identifier, message)?.clone();
+                expr = Expr::Get(Box::new(expr), name, optional);
+            } else if self.matches(&[TokenType::LeftBracket, TokenType::QuestionBracket]) {
+                let optional = self.previous().token_type == TokenType::QuestionBracket;
+                let index = self.expression()?;
+                let bracket = self
+                    .consume(TokenType::RightBracket, "Expect ']' after index.")?
+                    .clone();
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index), optional);
+            } else {
+                break;
+            }
         }
         Ok(expr)
     }
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+    fn finish_call(&mut self, callee: Expr, optional: bool) -> Result<Expr, ParseError> {
         let mut arguments = vec![];
         if !self.check(TokenType::RightParen) {
             loop {
+                if arguments.len() >= 255 {
+                    return Err(ParseError::new(
+                        "Can't have more than 255 arguments.",
+                        self.peek().line,
+                    ));
+                }
                 arguments.push(self.expression()?);
                 if !self.matches(&[TokenType::Comma]) {
                     break;
                 }
             }
         }
+        // 和分组括号的 "Expect ')' after expression." 区分开；行号是出错的 token 所在的行，
+        // 调用跨多行时指向缺少 ')' 的位置而不是调用开始的行
         let paren = self
             .consume(TokenType::RightParen, "Expect ')' after arguments.")?
             .clone();
-        Ok(Expr::Call(Box::new(callee), paren, arguments))
+        Ok(Expr::Call(Box::new(callee), paren, arguments, optional))
     }
     // *******辅助方法************
     fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
-        if self.check(token_type.clone()) {
+        if self.check(token_type) {
             return Ok(self.advance());
         }
-        return Err(ParseError::new(message, self.peek().line));
+        Err(ParseError::new(message, self.peek().line))
     }
     // 只要有一个匹配的，就调一下advance，返回true
     fn matches(&mut self, types: &[TokenType]) -> bool {
         for token_type in types {
-            if self.check(token_type.clone()) {
+            if self.check(*token_type) {
                 self.advance();
                 return true;
             }
         }
-        return false;
+        false
     }
     fn check(&self, token_type: TokenType) -> bool {
         if self.is_at_end() {
@@ -340,11 +891,18 @@ impl<'a> Parser<'a> {
         }
         self.peek().token_type == token_type
     }
+    fn check_next(&self, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
     // 移动指针，并且返回前一个token
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
         }
+        location::enter(Phase::Parsing, self.previous().line);
         self.previous()
     }
     fn previous(&self) -> &Token {
@@ -404,3 +962,252 @@ impl<'a> Parser<'a> {
 //         Expr::Grouping(g) => format!("(group {})", print_ast(g)),
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_tokens();
+        match Parser::new(tokens).parse() {
+            Ok(stmts) => stmts.iter().map(ToString::to_string).collect(),
+            Err(errors) => panic!("{:?} should parse: {:?}", source, errors),
+        }
+    }
+
+    // 所有语法错误，每个一行
+    fn parse_error(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_tokens();
+        match Parser::new(tokens).parse() {
+            Ok(_) => panic!("{:?} should not parse", source),
+            Err(errors) => errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"),
+        }
+    }
+
+    // 以 `}` 结束的语句（块、if、while、for、loop 和函数声明）后面不需要分号，也不会吃掉后面的分号
+    #[test]
+    fn block_terminated_statements_take_no_semicolon() {
+        let cases = [
+            "if (c) {} print 1;",
+            "fun f() {} f();",
+            "{} print 1;",
+            "if (c) {} else {} print 1;",
+            "while (c) {} print 1;",
+            "for (;;) {} print 1;",
+            "loop { break; } print 1;",
+            "{ { } } { }",
+        ];
+        for source in cases {
+            assert_eq!(parse(source).len(), 2, "{}", source);
+        }
+        assert_eq!(parse("if (c) {} print 1;")[1], "print 1.0");
+    }
+
+    #[test]
+    fn semicolon_after_block_is_an_error() {
+        for source in ["{};", "fun f() {};", "if (c) {};", "while (c) {};"] {
+            assert_eq!(parse_error(source), "[line 1] Error: Expect expression.", "{}", source);
+        }
+    }
+
+    #[test]
+    fn class_declarations_take_no_semicolon() {
+        assert_eq!(parse("class A {} print A;").len(), 2);
+        assert_eq!(parse_error("class A {};"), "[line 1] Error: Expect expression.");
+    }
+
+    #[test]
+    fn safe_navigation_marks_only_its_own_link() {
+        assert_eq!(parse("a?.b.c;"), ["(get (get? a b) c)"]);
+        assert_eq!(parse("a.b?.c();"), ["(get? (get a b) c)([])"]);
+    }
+
+    // 出错后跳到下一条语句继续解析，每条出错的语句各报告一次
+    #[test]
+    fn every_statement_error_is_reported() {
+        assert_eq!(
+            parse_error("var = 1;\nprint 2;\nprint (;\nvar ok = 3;\nfun 5() {}\nclass {}"),
+            "[line 1] Error: Expect variable name.\n\
+             [line 3] Error: Expect expression.\n\
+             [line 5] Error: Expect '(' after 'fun'.\n\
+             [line 6] Error: Expect class name."
+        );
+    }
+
+    // 同步时丢弃循环内部的状态：块中出错后剩下的 `}` 单独报错，之后的 break 不在循环中
+    #[test]
+    fn recovery_resets_loop_state() {
+        assert_eq!(
+            parse_error("while (true) { print ; }\nprint 1;\nbreak;"),
+            "[line 1] Error: Expect expression.\n\
+             [line 1] Error: Expect expression.\n\
+             [line 3] Error: Can't use 'break' outside of a loop."
+        );
+    }
+
+    // 无效的赋值目标说明原因；错误不影响后面的解析，同一条语句之后的错误也会报告
+    #[test]
+    fn invalid_assignment_targets() {
+        let cases = [
+            ("(a) = 1;", "Cannot assign to a grouped expression; remove the parentheses."),
+            ("a + b = 1;", "Cannot assign to the result of a binary expression."),
+            ("1 = 2;", "Cannot assign to a literal."),
+            ("-a = 1;", "Invalid assignment target."),
+        ];
+        for (source, message) in cases {
+            assert_eq!(parse_error(source), format!("[line 1] Error: {}", message), "{}", source);
+        }
+        assert_eq!(
+            parse_error("1 = 2;\nprint (;\n\"s\" = 3;"),
+            "[line 1] Error: Cannot assign to a literal.\n\
+             [line 2] Error: Expect expression.\n\
+             [line 3] Error: Cannot assign to a literal."
+        );
+        assert_eq!(parse("a = b = 1;").len(), 1);
+    }
+
+    // 其他语言的关键字写法在解析出错时给出提示
+    #[test]
+    fn keyword_hints() {
+        let cases = [
+            ("function f() {}", "Expect ';' after expression. Did you mean 'fun'?"),
+            ("let x = 1;", "Expect ';' after expression. Did you mean 'var'?"),
+            ("const x = 1;", "Expect ';' after expression. Did you mean 'var'?"),
+            ("if (a) {} elif b {}", "Expect ';' after expression. Did you mean 'else if'?"),
+            ("var x = 1 True;", "Expect ';' after value. Did you mean 'true'?"),
+            ("var x = null 1;", "Expect ';' after value. Did you mean 'nil'?"),
+            ("var x = None 1;", "Expect ';' after value. Did you mean 'nil'?"),
+            ("var x = True 1;", "Expect ';' after value. Did you mean 'true'?"),
+            ("var x = False 1;", "Expect ';' after value. Did you mean 'false'?"),
+        ];
+        for (source, message) in cases {
+            assert_eq!(parse_error(source), format!("[line 1] Error: {}", message), "{}", source);
+        }
+    }
+
+    // 合法使用的标识符不会触发提示
+    #[test]
+    fn no_keyword_hint_for_valid_identifiers() {
+        assert_eq!(parse("var let_ = 1; print let_;").len(), 2);
+        assert_eq!(parse("var function_name = 1; var nullable = function_name;").len(), 2);
+        assert_eq!(parse_error("var x = let_ 1;"), "[line 1] Error: Expect ';' after value.");
+    }
+
+    // 只看出错的 token 和它前面的一个 token，同一语句中更早的标识符不会触发提示
+    #[test]
+    fn keyword_hint_only_near_the_error() {
+        let cases = [
+            ("var True = 1; print True +;", "Expect expression."),
+            ("print None + (1 +);", "Expect expression."),
+            ("let = 1 +;", "Expect expression."),
+            // 调用的参数列表之后才出错，elif 已经不在旁边
+            ("if (a) {} elif (b) {}", "Expect ';' after expression."),
+        ];
+        for (source, message) in cases {
+            let errors = parse_error(source);
+            assert!(errors.ends_with(message), "{}: {}", source, errors);
+            assert!(!errors.contains("Did you mean"), "{}: {}", source, errors);
+        }
+    }
+
+    // 参数列表缺少 `)` 时报告出错的 token 所在的行，和分组的错误信息不同
+    #[test]
+    fn unclosed_argument_list() {
+        assert_eq!(
+            parse_error("print f(1,\n  2,\n  3;\nprint 4;"),
+            "[line 3] Error: Expect ')' after arguments."
+        );
+        assert_eq!(parse_error("f(1, 2\n"), "[line 2] Error: Expect ')' after arguments.");
+        assert_eq!(parse_error("print (1 + 2;"), "[line 1] Error: Expect ')' after expression.");
+    }
+
+    fn parse_expr(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_tokens();
+        match Parser::new(tokens).parse_expr() {
+            Ok(expr) => expr.to_string(),
+            Err(error) => panic!("{:?} should parse: {}", source, error),
+        }
+    }
+
+    // 固定运算符的优先级和结合性，改写解析器时输出必须不变
+    #[test]
+    fn precedence_and_associativity() {
+        let cases = [
+            ("2 + 3 * 4", "(+ 2.0 (* 3.0 4.0))"),
+            ("2 * 3 + 4", "(+ (* 2.0 3.0) 4.0)"),
+            ("1 - 2 - 3", "(- (- 1.0 2.0) 3.0)"),
+            ("1 / 2 / 3", "(/ (/ 1.0 2.0) 3.0)"),
+            ("8 div 2 * 3", "(* (div 8.0 2.0) 3.0)"),
+            ("!!true", "(! (! true))"),
+            ("--1", "(- (- 1.0))"),
+            ("-2 * 3", "(* (- 2.0) 3.0)"),
+            ("-a.b", "(- (get a b))"),
+            ("!a == b", "(== (! a) b)"),
+            ("a.b.c", "(get (get a b) c)"),
+            ("a = b = c", "(a = (b = c))"),
+            ("a.b = c = d", "(set a b (c = d))"),
+            ("x = 1 or 2", "(x = (or 1.0 2.0))"),
+            ("(1 + 2) * 3", "(* (group (+ 1.0 2.0)) 3.0)"),
+            ("1 < 2 == 3 > 4", "(== (< 1.0 2.0) (> 3.0 4.0))"),
+            ("1 + 2 < 3 * 4", "(< (+ 1.0 2.0) (* 3.0 4.0))"),
+            ("a == b != c", "(!= (== a b) c)"),
+            ("1 <= 2 >= 3", "(>= (<= 1.0 2.0) 3.0)"),
+            ("a or b and c", "(or a (and b c))"),
+            ("a and b or c", "(or (and a b) c)"),
+            ("a or b or c", "(or (or a b) c)"),
+            ("a or b xor c and d", "(or a (xor b (and c d)))"),
+            ("a xor b xor c", "(xor (xor a b) c)"),
+            ("a == 1 and b < 2 or !c", "(or (and (== a 1.0) (< b 2.0)) (! c))"),
+            ("a[1][2]", "(index (index a 1.0) 2.0)"),
+            ("-a[0]", "(- (index a 0.0))"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_expr(source), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn precedence_in_statements() {
+        assert_eq!(
+            parse(
+                "print 1 + 2 * 3;
+                 a = b = !c;
+                 if (a or b and c) print -x; else print !y;
+                 while (i < 10 and !done) i = i + 1;
+                 { print a - b - c; }"
+            ),
+            vec![
+                "print (+ 1.0 (* 2.0 3.0))",
+                "(a = (b = (! c)))",
+                "if ((or a (and b c))) { print (- x) } else { print (! y) }",
+                "while ((and (< i 10.0) (! done))) { (i = (+ i 1.0)) }",
+                "{print (- (- a b) c)}",
+            ]
+        );
+    }
+
+    // 单个表达式后面还有 token 时报错，不默默丢掉后面的部分
+    #[test]
+    fn trailing_tokens_after_expression() {
+        for source in ["1 + 1; 2 + 2;", "1 2", "(1) )", "a = 1;"] {
+            let mut scanner = Scanner::new(source);
+            let (tokens, _) = scanner.scan_tokens();
+            let error = Parser::new(tokens).parse_expr().expect_err(source);
+            assert_eq!(error.to_string(), "[line 1] Error: Unexpected tokens after expression.");
+        }
+        assert_eq!(parse_expr("1 + 1"), "(+ 1.0 1.0)");
+    }
+
+    #[test]
+    fn if_condition_errors() {
+        assert_eq!(parse_error("if true) print 1;"), "[line 1] Error: Expect '(' after 'if'.");
+        assert_eq!(
+            parse_error("if (true\nprint 1;"),
+            "[line 2] Error: Expect ')' after if condition."
+        );
+    }
+}
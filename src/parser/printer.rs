@@ -0,0 +1,128 @@
+use crate::scanner::token::{escape, TokenType};
+
+use super::expr::{Expr, Literal};
+
+// 按优先级重新推导括号的源码形式打印器，不依赖 Grouping 节点
+// 优先级从低到高，与 grammar.txt 中的层级一致
+const ASSIGNMENT: u8 = 1;
+const OR: u8 = 2;
+const XOR: u8 = 3;
+const AND: u8 = 4;
+const EQUALITY: u8 = 5;
+const COMPARISON: u8 = 6;
+const TERM: u8 = 7;
+const FACTOR: u8 = 8;
+const UNARY: u8 = 9;
+const CALL: u8 = 10;
+const PRIMARY: u8 = 11;
+
+pub fn pretty(expr: &Expr) -> String {
+    match expr {
+        Expr::Literal(Literal::String(s)) => string_literal(s),
+        Expr::Literal(Literal::Number(n)) => n.to_string(),
+        Expr::Literal(l) => l.to_string(),
+        Expr::Grouping(inner) => pretty(inner),
+        Expr::Variable(name, _) => name.lexeme.clone(),
+        Expr::Unary(op, right) => format!("{}{}", op.lexeme, operand(right, UNARY, false)),
+        Expr::Binary(left, op, right) | Expr::Logical(left, op, right) => {
+            let prec = precedence(expr);
+            format!(
+                "{} {} {}",
+                operand(left, prec, false),
+                op.lexeme,
+                operand(right, prec, !is_associative(op.token_type, right)),
+            )
+        }
+        Expr::Assign(name, value, _) => format!("{} = {}", name.lexeme, pretty(value)),
+        Expr::Call(callee, _, args, optional) => format!(
+            "{}{}({})",
+            operand(callee, CALL, false),
+            if *optional { "?" } else { "" },
+            args.iter().map(pretty).collect::<Vec<_>>().join(", ")
+        ),
+        Expr::List(elements) => format!(
+            "[{}]",
+            elements.iter().map(pretty).collect::<Vec<_>>().join(", ")
+        ),
+        Expr::Index(list, _, index, optional) => format!(
+            "{}{}[{}]",
+            operand(list, CALL, false),
+            if *optional { "?" } else { "" },
+            pretty(index)
+        ),
+        Expr::Get(object, name, optional) => format!(
+            "{}{}{}",
+            operand(object, CALL, false),
+            if *optional { "?." } else { "." },
+            name.lexeme
+        ),
+        Expr::Set(object, name, value) => format!(
+            "{}.{} = {}",
+            operand(object, CALL, false),
+            name.lexeme,
+            pretty(value)
+        ),
+        Expr::This(_, _) => "this".to_string(),
+        Expr::Super(_, method, _) => format!("super.{}", method.lexeme),
+        // 函数体是语句，这里只打印参数列表
+        Expr::Lambda(_, params, _, _) => format!(
+            "fun ({}) {{ ... }}",
+            params.iter().map(|p| p.name.lexeme.as_str()).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+// 字符串的源码形式：escape 之后加上引号，重新解析得到的值不变
+fn string_literal(value: &str) -> String {
+    format!("\"{}\"", escape(value))
+}
+
+// 子表达式优先级低于父节点时加括号；左结合运算符的右操作数在同级时也要加括号
+fn operand(expr: &Expr, parent: u8, strict: bool) -> String {
+    let prec = precedence(expr);
+    if prec < parent || (strict && prec == parent) {
+        format!("({})", pretty(expr))
+    } else {
+        pretty(expr)
+    }
+}
+
+// `a + (b + c)` 和 `a + b + c` 结果相同，这类右操作数不需要括号
+fn is_associative(op: TokenType, right: &Expr) -> bool {
+    let right = match right {
+        Expr::Grouping(inner) => return is_associative(op, inner),
+        Expr::Binary(_, right_op, _) | Expr::Logical(_, right_op, _) => right_op.token_type,
+        _ => return false,
+    };
+    right == op
+        && matches!(
+            op,
+            TokenType::Plus | TokenType::Star | TokenType::And | TokenType::Or | TokenType::Xor
+        )
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Grouping(inner) => precedence(inner),
+        Expr::Assign(_, _, _) | Expr::Set(_, _, _) => ASSIGNMENT,
+        Expr::Logical(_, op, _) => match op.token_type {
+            TokenType::Or => OR,
+            TokenType::Xor => XOR,
+            _ => AND,
+        },
+        Expr::Binary(_, op, _) => match op.token_type {
+            TokenType::EqualEqual | TokenType::BangEqual => EQUALITY,
+            TokenType::Plus | TokenType::Minus => TERM,
+            TokenType::Star | TokenType::Slash | TokenType::Div => FACTOR,
+            _ => COMPARISON,
+        },
+        Expr::Unary(_, _) => UNARY,
+        Expr::Call(..) | Expr::Index(..) | Expr::Get(..) => CALL,
+        Expr::Literal(_)
+        | Expr::Variable(_, _)
+        | Expr::List(_)
+        | Expr::This(_, _)
+        | Expr::Super(_, _, _)
+        | Expr::Lambda(..) => PRIMARY,
+    }
+}
@@ -1,28 +1,114 @@
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::fmt::Display;
 
-use crate::{environment::Environment, scanner::token::Token};
+use crate::scanner::token::Token;
 
-use super::expr::Expr;
+use super::expr::{Expr, Literal};
+
+// match 语句的分支模式
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Literal(Literal),
+    Wildcard,
+    // 类型模式：类型名，以及可选的绑定变量，例如 `number n`
+    Type(Token, Option<Token>),
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Literal(literal) => write!(f, "{}", literal),
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Type(type_name, Some(name)) => {
+                write!(f, "{} {}", type_name.lexeme, name.lexeme)
+            }
+            Pattern::Type(type_name, None) => write!(f, "{}", type_name.lexeme),
+        }
+    }
+}
+
+// 函数参数，类型标注可选，例如 `a: number`
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Token,
+    pub annotation: Option<Token>,
+}
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
     Expression(Expr),
-    Print(Expr),
-    Var(Token, Option<Expr>),
+    Print(Token, Expr),
+    // 变量名、可选的类型标注、可选的初始值
+    Var(Token, Option<Token>, Option<Expr>),
+    // var q, r = expr; 右边调用的函数用 return q, r; 返回多个值时依次绑定这些值，
+    // 否则右边的值必须是列表，把列表的元素依次绑定到各个变量
+    Destructure(Vec<Param>, Expr),
     Block(Vec<Stmt>),
-    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
-    While(Expr, Box<Stmt>),
-    For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
-    Function(Token, Vec<Token>, Box<Vec<Stmt>>),
-    Return(Option<Expr>),
+    If(Token, Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Token, Expr, Box<Stmt>),
+    For(Token, Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    // 函数名、参数、可选的返回值类型标注、函数体
+    Function(Token, Vec<Param>, Option<Token>, Vec<Stmt>),
+    // 关键字和返回的值：return; 时为空，return a, b; 有多个，调用表达式的值是第一个
+    Return(Token, Vec<Expr>),
+    Loop(Token, Box<Stmt>),
+    // for (x in iterable) body：关键字、循环变量、被遍历的值、循环体
+    ForIn(Token, Token, Expr, Box<Stmt>),
+    // label: 循环：标签和它标记的循环
+    Labeled(Token, Box<Stmt>),
+    // 关键字、可选的标签、要先跳出的内层循环数（不带标签或标签就是最内层循环时为 0，
+    // 由 Resolver 根据标签算出）
+    Break(Token, Option<Token>, usize),
+    Continue(Token, Option<Token>, usize),
+    Match(Token, Expr, Vec<(Pattern, Stmt)>),
+    // 类名、可选的父类（Expr::Variable）、方法（Stmt::Function）和带默认值的字段（Stmt::Var）
+    Class(Token, Option<Expr>, Vec<Stmt>),
+}
+
+impl Stmt {
+    // 语句开头所在的行，用于报告警告；拿不到行号时返回 None
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Expression(expr) => expr.line(),
+            Stmt::Block(stmts) => stmts.iter().find_map(Stmt::line),
+            Stmt::Var(name, ..)
+            | Stmt::Function(name, ..)
+            | Stmt::Class(name, ..)
+            | Stmt::Labeled(name, _) => Some(name.line),
+            Stmt::Destructure(targets, value) => {
+                targets.first().map(|target| target.name.line).or(value.line())
+            }
+            Stmt::Print(keyword, _)
+            | Stmt::If(keyword, ..)
+            | Stmt::While(keyword, ..)
+            | Stmt::For(keyword, ..)
+            | Stmt::Return(keyword, _)
+            | Stmt::Loop(keyword, _)
+            | Stmt::ForIn(keyword, ..)
+            | Stmt::Break(keyword, ..)
+            | Stmt::Continue(keyword, ..)
+            | Stmt::Match(keyword, ..) => Some(keyword.line),
+        }
+    }
+    // var 和解构声明的变量名，其他语句为空；for 循环据此为每次迭代复制循环变量
+    pub fn declared_names(&self) -> Vec<&Token> {
+        match self {
+            Stmt::Var(name, ..) => vec![name],
+            Stmt::Destructure(targets, _) => targets.iter().map(|target| &target.name).collect(),
+            _ => vec![],
+        }
+    }
 }
 
 impl Display for Stmt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Stmt::Expression(expr) => write!(f, "{}", expr),
-            Stmt::Print(expr) => write!(f, "print {}", expr),
-            Stmt::Var(name, expr) => write!(f, "var {} = {:?}", name.lexeme, expr),
+            Stmt::Print(_, expr) => write!(f, "print {}", expr),
+            Stmt::Var(name, _, expr) => write!(f, "var {} = {:?}", name.lexeme, expr),
+            Stmt::Destructure(targets, value) => {
+                let names: Vec<&str> = targets.iter().map(|t| t.name.lexeme.as_str()).collect();
+                write!(f, "var {} = {}", names.join(", "), value)
+            }
             Stmt::Block(stmts) => {
                 write!(f, "{{")?;
                 for stmt in stmts {
@@ -30,7 +116,7 @@ impl Display for Stmt {
                 }
                 write!(f, "}}")
             }
-            Stmt::If(condition, then_branch, else_branch) => {
+            Stmt::If(_, condition, then_branch, else_branch) => {
                 write!(f, "if ({}) {{ {} }}", condition, then_branch)?;
                 if let Some(else_branch) = else_branch {
                     write!(f, " else {{ {} }}", else_branch)
@@ -38,18 +124,45 @@ impl Display for Stmt {
                     Ok(())
                 }
             }
-            Stmt::While(condition, body) => write!(f, "while ({}) {{ {} }}", condition, body),
-            Stmt::For(initializer, condition, increment, body) => {
+            Stmt::While(_, condition, body) => write!(f, "while ({}) {{ {} }}", condition, body),
+            Stmt::For(_, initializer, condition, increment, body) => {
                 write!(
                     f,
                     "for ({:?}; {:?}; {:?}) {{ {} }}",
                     initializer, condition, increment, body
                 )
             }
-            Stmt::Function(name, params, body) => {
+            Stmt::Function(name, params, _, body) => {
                 write!(f, "fun {}({:?}) {{ {:?} }}", name.lexeme, params, body)
             }
-            Stmt::Return(expr) => write!(f, "return {:?}", expr),
+            Stmt::Return(_, expr) => write!(f, "return {:?}", expr),
+            Stmt::Loop(_, body) => write!(f, "loop {}", body),
+            Stmt::Labeled(label, body) => write!(f, "{}: {}", label.lexeme, body),
+            Stmt::ForIn(_, name, iterable, body) => {
+                write!(f, "for ({} in {}) {{ {} }}", name.lexeme, iterable, body)
+            }
+            Stmt::Break(_, None, _) => write!(f, "break"),
+            Stmt::Break(_, Some(label), _) => write!(f, "break {}", label.lexeme),
+            Stmt::Continue(_, None, _) => write!(f, "continue"),
+            Stmt::Continue(_, Some(label), _) => write!(f, "continue {}", label.lexeme),
+            Stmt::Match(_, scrutinee, arms) => {
+                write!(f, "match {} {{", scrutinee)?;
+                for (pattern, body) in arms {
+                    write!(f, " {} => {},", pattern, body)?;
+                }
+                write!(f, " }}")
+            }
+            Stmt::Class(name, superclass, methods) => {
+                write!(f, "class {}", name.lexeme)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass)?;
+                }
+                write!(f, " {{")?;
+                for method in methods {
+                    write!(f, " {}", method)?;
+                }
+                write!(f, " }}")
+            }
         }
     }
 }
@@ -0,0 +1,454 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+};
+
+use crate::{
+    diagnostics::{INFINITE_LOOP, SHADOW_NATIVE, UNREACHABLE_CODE},
+    natives,
+    parser::{
+        expr::Expr,
+        stmt::{Param, Pattern, Stmt},
+        ParseError,
+    },
+    scanner::token::Token,
+};
+
+// 静态检查阶段：在解释执行之前遍历语法树，收集警告和错误，
+// 并把局部变量解析到它们所在的作用域深度（写回 Expr::Variable），
+// 把带标签的 break/continue 解析到要跳出的循环层数（写回 Stmt::Break/Continue）
+#[derive(Clone)]
+pub struct Warning {
+    pub message: String,
+    pub line: usize,
+    pub code: &'static str,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Warning: {}", self.line, self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct Resolver {
+    warnings: Vec<Warning>,
+    // 局部作用域栈，和解释器运行时创建的 Environment 一一对应；全局作用域不在栈里
+    scopes: Vec<HashSet<String>>,
+    // 标签不存在或和外层循环的标签重复
+    errors: Vec<ParseError>,
+    // 当前函数中嵌套的循环层数，以及各层标签和标签所在循环的层数（由外到内）
+    loops: usize,
+    labels: Vec<(String, usize)>,
+    // 为 true 时全局的 var、fun、class 和内置函数同名会给出警告（run --warn-shadow-native）；
+    // 默认允许覆盖内置函数，不警告
+    pub warn_shadow_native: bool,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resolve(&mut self, stmts: &mut [Stmt]) -> &[Warning] {
+        self.check_unreachable(stmts);
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+        &self.warnings
+    }
+
+    // resolve 发现的错误；有错误时语法树不能执行，和解析错误一样以 65 退出
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block(stmts) => {
+                self.check_unreachable(stmts);
+                self.begin_scope();
+                self.declare_functions(stmts);
+                for stmt in stmts {
+                    self.resolve_stmt(stmt);
+                }
+                self.end_scope();
+            }
+            Stmt::Function(name, params, _, body) => {
+                self.check_shadow_native(name);
+                self.declare(&name.lexeme);
+                self.resolve_function(params, body);
+            }
+            // 有父类时运行时会多一层定义 super 的环境；方法绑定到实例时再多一层定义 this 的环境
+            Stmt::Class(name, superclass, methods) => {
+                self.check_shadow_native(name);
+                self.declare(&name.lexeme);
+                // 字段默认值在定义类的环境中求值，在 super 那一层之外
+                for member in methods.iter_mut() {
+                    if let Stmt::Var(_, _, Some(initializer)) = member {
+                        self.resolve_expr(initializer);
+                    }
+                }
+                if let Some(superclass) = superclass {
+                    self.resolve_expr(superclass);
+                    self.begin_scope();
+                    self.declare("super");
+                }
+                for method in methods {
+                    if let Stmt::Function(_, params, _, body) = method {
+                        self.begin_scope();
+                        self.declare("this");
+                        self.resolve_function(params, body);
+                        self.end_scope();
+                    }
+                }
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+            Stmt::Var(name, _, initializer) => {
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.check_shadow_native(name);
+                self.declare(&name.lexeme);
+            }
+            Stmt::Destructure(targets, value) => {
+                self.resolve_expr(value);
+                for target in targets.iter() {
+                    self.check_shadow_native(&target.name);
+                    self.declare(&target.name.lexeme);
+                }
+            }
+            Stmt::Expression(expr) | Stmt::Print(_, expr) => self.resolve_expr(expr),
+            Stmt::Return(_, values) => {
+                for value in values.iter_mut() {
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While(_, condition, body) => {
+                self.resolve_expr(condition);
+                self.resolve_loop_body(body);
+            }
+            // 和解释器一致：初始化语句在循环自己的作用域中；初始化语句声明了变量时，
+            // 条件、增量和循环体在每次迭代的作用域中，其中是循环变量的副本
+            Stmt::For(_, initializer, condition, increment, body) => {
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.resolve_stmt(initializer);
+                }
+                let names = initializer.as_ref().map_or(vec![], |stmt| stmt.declared_names());
+                if !names.is_empty() {
+                    self.begin_scope();
+                    for name in &names {
+                        self.declare(&name.lexeme);
+                    }
+                }
+                if let Some(condition) = condition {
+                    self.resolve_expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+                self.resolve_loop_body(body);
+                if !names.is_empty() {
+                    self.end_scope();
+                }
+                self.end_scope();
+            }
+            // 每次迭代都在新的环境中定义循环变量
+            Stmt::ForIn(_, name, iterable, body) => {
+                self.resolve_expr(iterable);
+                self.begin_scope();
+                self.declare(&name.lexeme);
+                self.resolve_loop_body(body);
+                self.end_scope();
+            }
+            Stmt::Loop(keyword, body) => {
+                if !exits_loop(body, false) {
+                    self.warnings.push(Warning {
+                        message: "'loop' without 'break' never terminates.".to_string(),
+                        line: keyword.line,
+                        code: INFINITE_LOOP,
+                    });
+                }
+                self.resolve_loop_body(body);
+            }
+            // 标签对应的是它后面的循环，即第 loops + 1 层
+            Stmt::Labeled(label, body) => {
+                if self.labels.iter().any(|(name, _)| *name == label.lexeme) {
+                    let message =
+                        format!("Label '{}' is already in use by an enclosing loop.", label.lexeme);
+                    self.errors.push(ParseError::new(&message, label.line));
+                }
+                self.labels.push((label.lexeme.clone(), self.loops + 1));
+                self.resolve_stmt(body);
+                self.labels.pop();
+            }
+            Stmt::Match(_, scrutinee, arms) => {
+                self.resolve_expr(scrutinee);
+                for (pattern, body) in arms {
+                    self.begin_scope();
+                    if let Pattern::Type(_, Some(name)) = pattern {
+                        self.declare(&name.lexeme);
+                    }
+                    self.resolve_stmt(body);
+                    self.end_scope();
+                }
+            }
+            Stmt::Break(_, Some(label), outer) | Stmt::Continue(_, Some(label), outer) => {
+                match self.labels.iter().rev().find(|(name, _)| *name == label.lexeme) {
+                    Some((_, depth)) => *outer = self.loops - depth,
+                    None => self.errors.push(ParseError::new(
+                        &format!("Undefined label '{}'.", label.lexeme),
+                        label.line,
+                    )),
+                }
+            }
+            Stmt::Break(..) | Stmt::Continue(..) => {}
+        }
+    }
+
+    fn resolve_loop_body(&mut self, body: &mut Stmt) {
+        self.loops += 1;
+        self.resolve_stmt(body);
+        self.loops -= 1;
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Variable(name, depth) => *depth = self.lookup(&name.lexeme),
+            Expr::Literal(_) => {}
+            Expr::Assign(name, value, depth) => {
+                self.resolve_expr(value);
+                *depth = self.lookup(&name.lexeme);
+            }
+            Expr::Grouping(inner) | Expr::Unary(_, inner) => self.resolve_expr(inner),
+            Expr::Binary(left, _, right)
+            | Expr::Logical(left, _, right)
+            | Expr::Index(left, _, right, _) => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call(callee, _, args, _) => {
+                self.resolve_expr(callee);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::List(elements) => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Get(object, _, _) => self.resolve_expr(object),
+            Expr::Set(object, _, value) => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::This(_, depth) => *depth = self.lookup("this"),
+            Expr::Super(_, _, depth) => *depth = self.lookup("super"),
+            Expr::Lambda(_, params, _, body) => self.resolve_function(params, body),
+        }
+    }
+
+    // 调用时先为参数创建一层环境，再由 execute_block 为函数体创建一层。
+    // 函数体内的 break/continue 不能跳出外层的循环，外层的标签在函数体中不可见
+    fn resolve_function(&mut self, params: &[Param], body: &mut [Stmt]) {
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        let enclosing_labels = std::mem::take(&mut self.labels);
+        self.begin_scope();
+        for param in params {
+            self.declare(&param.name.lexeme);
+        }
+        self.check_unreachable(body);
+        self.begin_scope();
+        self.declare_functions(body);
+        for stmt in body {
+            self.resolve_stmt(stmt);
+        }
+        self.end_scope();
+        self.end_scope();
+        self.loops = enclosing_loops;
+        self.labels = enclosing_labels;
+    }
+
+    // 同一个语句列表中，一定会跳出的语句之后的代码不会执行；每个列表只报告第一条
+    fn check_unreachable(&mut self, stmts: &[Stmt]) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if always_exits(stmt) {
+                if let Some(dead) = stmts.get(i + 1) {
+                    self.warnings.push(Warning {
+                        message: "Unreachable code.".to_string(),
+                        line: dead.line().or(stmt.line()).unwrap_or_default(),
+                        code: UNREACHABLE_CODE,
+                    });
+                }
+                return;
+            }
+        }
+    }
+
+    // 在局部作用域中找到则返回距离；否则是全局变量，距离就是局部作用域的层数
+    fn lookup(&self, name: &str) -> Option<usize> {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains(name) {
+                return Some(depth);
+            }
+        }
+        Some(self.scopes.len())
+    }
+
+    // 只检查全局作用域：局部变量覆盖内置函数只影响它所在的作用域
+    fn check_shadow_native(&mut self, name: &Token) {
+        if !self.warn_shadow_native || !self.scopes.is_empty() {
+            return;
+        }
+        if natives::all().iter().any(|native| native.name == name.lexeme) {
+            self.warnings.push(Warning {
+                message: format!("Shadowing built-in '{}'.", name.lexeme),
+                line: name.line,
+                code: SHADOW_NATIVE,
+            });
+        }
+    }
+
+    // 块中的函数名在整个块中可见，块里的函数可以互相调用（isEven/isOdd）；
+    // 运行时函数仍按顺序定义，在声明之前调用会报 Undefined variable。var 不提前声明
+    fn declare_functions(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Stmt::Function(name, ..) = stmt {
+                self.declare(&name.lexeme);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashSet::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+}
+
+// 语句执行后是否一定会通过 return/break/continue 跳出，后面的语句因此不可达
+fn always_exits(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(..) | Stmt::Break(..) | Stmt::Continue(..) => true,
+        Stmt::Block(stmts) => stmts.iter().any(always_exits),
+        Stmt::If(_, _, then_branch, Some(else_branch)) => {
+            always_exits(then_branch) && always_exits(else_branch)
+        }
+        // 只有带通配分支时 match 才一定会执行某个分支
+        Stmt::Match(_, _, arms) => {
+            arms.iter()
+                .any(|(pattern, _)| matches!(pattern, Pattern::Wildcard))
+                && arms.iter().all(|(_, body)| always_exits(body))
+        }
+        _ => false,
+    }
+}
+
+// 循环体中是否有能离开当前循环的语句：本层的 break，或任意层的 return
+// 内层循环里的 break 只跳出内层循环，不算
+fn exits_loop(stmt: &Stmt, nested: bool) -> bool {
+    match stmt {
+        // 带标签的 break 可能跳出更外层的循环，按能离开当前循环处理
+        Stmt::Break(_, label, _) => label.is_some() || !nested,
+        Stmt::Return(..) => true,
+        Stmt::Block(stmts) => stmts.iter().any(|stmt| exits_loop(stmt, nested)),
+        Stmt::If(_, _, then_branch, else_branch) => {
+            exits_loop(then_branch, nested)
+                || else_branch
+                    .as_ref()
+                    .is_some_and(|stmt| exits_loop(stmt, nested))
+        }
+        Stmt::While(_, _, body)
+        | Stmt::For(_, _, _, _, body)
+        | Stmt::Loop(_, body)
+        | Stmt::ForIn(_, _, _, body) => {
+            exits_loop(body, true)
+        }
+        Stmt::Labeled(_, body) => exits_loop(body, nested),
+        Stmt::Match(_, _, arms) => arms.iter().any(|(_, body)| exits_loop(body, nested)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn resolve(source: &str) -> (Vec<Stmt>, Vec<String>) {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_tokens();
+        let mut stmts = Parser::new(tokens).parse().expect("source should parse");
+        let mut resolver = Resolver::new();
+        resolver.resolve(&mut stmts);
+        let errors = resolver.errors().iter().map(ToString::to_string).collect();
+        (stmts, errors)
+    }
+
+    // 按出现的顺序收集 break 要跳出的内层循环数
+    fn break_counts(stmt: &Stmt, counts: &mut Vec<usize>) {
+        match stmt {
+            Stmt::Break(_, _, outer) => counts.push(*outer),
+            Stmt::Block(stmts) => stmts.iter().for_each(|stmt| break_counts(stmt, counts)),
+            Stmt::Labeled(_, body)
+            | Stmt::While(_, _, body)
+            | Stmt::For(.., body)
+            | Stmt::Loop(_, body) => break_counts(body, counts),
+            _ => {}
+        }
+    }
+
+    // 带标签的 break 写回要跳出的内层循环数，不带标签的是 0
+    #[test]
+    fn labeled_breaks_count_the_loops_to_leave() {
+        let (stmts, errors) = resolve(
+            "outer: while (true) {
+               for (;;) { inner: loop { break outer; break inner; break; } break outer; }
+             }",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut counts = vec![];
+        break_counts(&stmts[0], &mut counts);
+        assert_eq!(counts, vec![2, 0, 0, 1]);
+    }
+
+    #[test]
+    fn undefined_and_duplicate_labels() {
+        let cases = [
+            ("while (true) { break x; }", "[line 1] Error: Undefined label 'x'."),
+            ("loop { continue x; }", "[line 1] Error: Undefined label 'x'."),
+            (
+                "a: loop {\n  a: while (true) { break a; }\n}",
+                "[line 2] Error: Label 'a' is already in use by an enclosing loop.",
+            ),
+            // 函数体中看不到外层循环的标签
+            ("a: loop { fun f() { loop { break a; } } }", "[line 1] Error: Undefined label 'a'."),
+        ];
+        for (source, message) in cases {
+            assert_eq!(resolve(source).1, vec![message.to_string()], "{}", source);
+        }
+        // 并列的循环可以用同一个标签，函数体中也可以重新使用外层的标签
+        let (_, errors) =
+            resolve("a: loop { break a; } a: loop { fun f() { a: loop { break a; } } break a; }");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+}
@@ -1,5 +1,7 @@
+#[allow(clippy::module_inception)]
 mod scanner;
 pub mod token;
 pub mod keywords;
+pub mod json;
 
 pub use scanner::*;
\ No newline at end of file
@@ -0,0 +1,127 @@
+use crate::{interpreter::Value, natives::json};
+
+use super::token::{Token, TokenType};
+
+// tokenize --json 输出、parse --from-tokens 读取的格式：一个数组，每个 token 一个对象
+//   {"type": "NUMBER", "lexeme": "1.50", "literal": "1.5", "line": 1, "column": 1, "offset": 0}
+// literal 和 Token::literal 一样保存为字符串（没有时为 null），读回时不经过数字转换，
+// 解析得到的语法树和直接扫描源码时完全相同
+
+pub fn to_json(tokens: &[Token]) -> String {
+    let tokens: Vec<Value> = tokens
+        .iter()
+        .map(|token| {
+            Value::map(vec![
+                ("type".to_string(), Value::from(token.token_type.to_string())),
+                ("lexeme".to_string(), Value::from(token.lexeme.as_str())),
+                (
+                    "literal".to_string(),
+                    token.literal.as_deref().map_or(Value::Nil, Value::from),
+                ),
+                ("line".to_string(), Value::from(token.line as f64)),
+                ("column".to_string(), Value::from(token.column as f64)),
+                ("offset".to_string(), Value::from(token.offset as f64)),
+            ])
+        })
+        .collect();
+    // 只有字符串、数字和 nil，不会失败
+    json::stringify(&Value::from(tokens), None).unwrap_or_default()
+}
+
+// 错误信息中的 token 下标从 0 开始
+pub fn from_json(text: &str) -> Result<Vec<Token>, String> {
+    let value = json::parse(text).map_err(|message| format!("Invalid JSON: {}", message))?;
+    let Value::List(items) = value else {
+        return Err("Expect an array of tokens.".to_string());
+    };
+    let mut tokens = Vec::new();
+    for (index, item) in items.borrow().iter().enumerate() {
+        let Value::Map(entries) = item else {
+            return Err(format!("Token {}: expect an object.", index));
+        };
+        let entries = entries.borrow();
+        let field = |name: &str| {
+            entries
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let string = |name: &str| match field(name) {
+            Some(Value::String(s)) => Ok(s.to_string()),
+            _ => Err(format!("Token {}: '{}' must be a string.", index, name)),
+        };
+        let number = |name: &str| match field(name) {
+            Some(Value::Number(n)) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            _ => Err(format!("Token {}: '{}' must be a non-negative integer.", index, name)),
+        };
+
+        let name = string("type")?;
+        let token_type = TokenType::from_name(&name)
+            .ok_or_else(|| format!("Token {}: unknown token type '{}'.", index, name))?;
+        let literal = match field("literal") {
+            None | Some(Value::Nil) => None,
+            Some(Value::String(s)) => Some(s.to_string()),
+            Some(_) => return Err(format!("Token {}: 'literal' must be a string or null.", index)),
+        };
+        if token_type == TokenType::Eof && index + 1 != items.borrow().len() {
+            return Err(format!("Token {}: EOF before the end of the stream.", index));
+        }
+        let mut token = Token::new(token_type, string("lexeme")?, literal, number("line")?);
+        token.column = number("column")?;
+        token.offset = number("offset")?;
+        tokens.push(token);
+    }
+    if !tokens.last().is_some_and(|token| token.token_type == TokenType::Eof) {
+        return Err("Token stream must end with an EOF token.".to_string());
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    // 读回的 token 和扫描得到的完全相同，包括位置
+    #[test]
+    fn round_trip() {
+        let source = "class A < B { f(x) { return this.x?.y ?? 1.50 div 2; } }\n\
+                      var s = \"a\\\"b\"; // c\n\
+                      for (i in xs?[0]) { if (!i >= 2 or i != nil) break; else continue; }\n\
+                      f?(1, -2) <= 3 and true == false; match x { _ => print super.y; }\n\
+                      loop { fun g() -> 1; } while (a < b) a = b * c / d;\n";
+        let mut scanner = Scanner::new(source);
+        let (tokens, errors) = scanner.scan_tokens();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let read = from_json(&to_json(tokens)).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", tokens));
+    }
+
+    #[test]
+    fn invalid_token_streams() {
+        let eof = r#"{"type": "EOF", "lexeme": "", "line": 1, "column": 1, "offset": 0}"#;
+        let cases = [
+            ("[", "Invalid JSON: "),
+            ("{}", "Expect an array of tokens."),
+            ("[1]", "Token 0: expect an object."),
+            ("[]", "Token stream must end with an EOF token."),
+            (r#"[{"type": "PLUS"}]"#, "Token 0: 'lexeme' must be a string."),
+            (r#"[{"type": "PLUSS"}]"#, "Token 0: unknown token type 'PLUSS'."),
+            (r#"[{"type": 1}]"#, "Token 0: 'type' must be a string."),
+        ];
+        for (text, message) in cases {
+            let error = from_json(text).unwrap_err();
+            assert!(error.starts_with(message), "{}: {}", text, error);
+        }
+        let error = from_json(&format!("[{}, {}]", eof, eof)).unwrap_err();
+        assert_eq!(error, "Token 0: EOF before the end of the stream.");
+        let negative = r#"[{"type": "EOF", "lexeme": "", "line": -1, "column": 1, "offset": 0}]"#;
+        assert_eq!(
+            from_json(negative).unwrap_err(),
+            "Token 0: 'line' must be a non-negative integer."
+        );
+        let literal = r#"[{"type": "EOF", "lexeme": "", "literal": 1, "line": 1}]"#;
+        assert_eq!(from_json(literal).unwrap_err(), "Token 0: 'literal' must be a string or null.");
+        assert_eq!(from_json(&format!("[{}]", eof)).unwrap().len(), 1);
+    }
+}
@@ -9,12 +9,16 @@ pub fn map() -> &'static HashMap<&'static str, TokenType> {
   KEYWORDS.get_or_init(|| {
     let mut map = HashMap::new();
     map.insert("and", TokenType::And);
+    map.insert("break", TokenType::Break);
     map.insert("class", TokenType::Class);
+    map.insert("continue", TokenType::Continue);
     map.insert("else", TokenType::Else);
     map.insert("false", TokenType::False);
     map.insert("for", TokenType::For);
     map.insert("fun", TokenType::Fun);
     map.insert("if", TokenType::If);
+    map.insert("loop", TokenType::Loop);
+    map.insert("match", TokenType::Match);
     map.insert("nil", TokenType::Nil);
     map.insert("or", TokenType::Or);
     map.insert("print", TokenType::Print);
@@ -24,6 +28,60 @@ pub fn map() -> &'static HashMap<&'static str, TokenType> {
     map.insert("true", TokenType::True);
     map.insert("var", TokenType::Var);
     map.insert("while", TokenType::While);
+    map.insert("xor", TokenType::Xor);
     map
   })
-}
\ No newline at end of file
+}
+
+// 其他语言的写法或大小写不对的关键字，解析出错时提示对应的 Lox 关键字
+pub fn suggestion(lexeme: &str) -> Option<&'static str> {
+  let lower = lexeme.to_lowercase();
+  match lower.as_str() {
+    "function" | "func" | "fn" | "def" => return Some("fun"),
+    "let" | "const" => return Some("var"),
+    "elif" | "elsif" | "elseif" => return Some("else if"),
+    "null" | "none" | "undefined" => return Some("nil"),
+    "self" => return Some("this"),
+    _ => {}
+  }
+  map().get_key_value(lower.as_str()).map(|(keyword, _)| *keyword)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn suggestions() {
+    let cases = [
+      ("function", "fun"),
+      ("func", "fun"),
+      ("fn", "fun"),
+      ("def", "fun"),
+      ("let", "var"),
+      ("const", "var"),
+      ("elif", "else if"),
+      ("elsif", "else if"),
+      ("elseif", "else if"),
+      ("null", "nil"),
+      ("NULL", "nil"),
+      ("None", "nil"),
+      ("undefined", "nil"),
+      ("self", "this"),
+      ("True", "true"),
+      ("False", "false"),
+      ("While", "while"),
+      ("PRINT", "print"),
+    ];
+    for (lexeme, keyword) in cases {
+      assert_eq!(suggestion(lexeme), Some(keyword), "{}", lexeme);
+    }
+  }
+
+  #[test]
+  fn no_suggestion_for_other_identifiers() {
+    for lexeme in ["let_", "functions", "nullable", "x", "Fun_", "selfish"] {
+      assert_eq!(suggestion(lexeme), None, "{}", lexeme);
+    }
+  }
+}
@@ -1,17 +1,53 @@
-use crate::parser::expr::Literal;
+use crate::{
+    diagnostics::{INEXACT_NUMBER, LIMIT_EXCEEDED, SCAN_ERROR},
+    limits::Limits,
+    number::format_literal,
+};
 
 use super::{
     keywords,
-    token::{Error, Token, TokenType},
+    token::{Error, Span, Token, TokenType},
 };
 
+// 对源码的一次编辑：从字节偏移 start 开始删除 removed_len 个字节，再插入 inserted
+pub struct TextEdit<'a> {
+    pub start: usize,
+    pub removed_len: usize,
+    pub inserted: &'a str,
+}
+
+impl TextEdit<'_> {
+    // 编辑后的源码
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.inserted.len());
+        edited.push_str(&source[..self.start]);
+        edited.push_str(self.inserted);
+        edited.push_str(&source[self.start + self.removed_len..]);
+        edited
+    }
+}
+
+// start、current 都是字节偏移，总是落在字符边界上；advance 按字符的 UTF-8 长度前进
 pub struct Scanner<'a> {
     source: &'a str,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    // current 和 start 所在的列号，advance 时逐字符更新，不必每个 token 都从行首重新数
+    column: usize,
+    start_column: usize,
     errors: Vec<Error>,
+    // 不影响扫描结果的问题，例如数字字面量精度丢失
+    warnings: Vec<Error>,
+    // scan_kinds 时为 Some：只记录类型和范围，不生成 Token 的 lexeme 和 literal
+    kinds: Option<Vec<(TokenType, Span)>>,
+    // 最近一个 #line 指令给出的文件名
+    file: Option<String>,
+    // 计算列号时一个制表符占的列数，默认为 1（和字符数一致）；和编辑器一致时可以设为 4 或 8
+    pub tab_width: usize,
+    // token 数超过时报错并停止扫描，默认是 Limits::default() 中的值
+    pub max_tokens: usize,
 }
 
 impl<'a> Scanner<'a> {
@@ -22,24 +58,158 @@ impl<'a> Scanner<'a> {
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_column: 1,
             errors: Vec::new(),
+            warnings: Vec::new(),
+            kinds: None,
+            file: None,
+            tab_width: 1,
+            max_tokens: Limits::default().max_tokens,
         }
     }
 
+    // 可以重复调用：已经扫描完（最后是 EOF）时直接返回上次的结果
     pub fn scan_tokens(&mut self) -> (&Vec<Token>, &Vec<Error>) {
+        if self.tokens.last().is_some_and(|token| token.token_type == TokenType::Eof) {
+            return (&self.tokens, &self.errors);
+        }
         while !self.is_at_end() {
-            self.start = self.current;
+            self.start_token();
             self.scan_token();
         }
-        self.tokens.push(Token::new(
-            TokenType::Eof,
-            String::from(""),
-            None,
-            self.line,
-        ));
+        let mut eof = Token::new(TokenType::Eof, String::from(""), None, self.line);
+        eof.offset = self.source.len();
+        eof.column = self.column;
+        self.tokens.push(eof);
         (&self.tokens, &self.errors)
     }
 
+    // 只扫描 token 的类型和字节范围（最后是 EOF），不为每个 token 分配 lexeme 和 literal 字符串，
+    // 供语法高亮、括号匹配等工具使用。结果和 scan_tokens 得到的 token 的类型、范围一致；
+    // 字符串的转义和数字字面量不会被检查，所以相应的错误和警告不会出现。需要用新的 Scanner 调用
+    pub fn scan_kinds(&mut self) -> Vec<(TokenType, Span)> {
+        self.kinds = Some(Vec::new());
+        while !self.is_at_end() {
+            self.start_token();
+            self.scan_token();
+        }
+        let mut kinds = self.kinds.take().unwrap_or_default();
+        let end = self.source.len();
+        kinds.push((TokenType::Eof, Span { start: end, end }));
+        kinds
+    }
+
+    // 最近一个 #line 指令中的文件名，没有时为 None
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    // 扫描过程中的警告；rescan 只会得到重新扫描的部分的警告
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
+
+    // 增量扫描：self 持有编辑后的源码，old_tokens/old_errors 是编辑前源码的扫描结果。
+    // 编辑所在行之前结束的 token 原样复用，从最后一个复用的 token 之后开始重新扫描；
+    // 扫描越过编辑区域后，一旦得到的 token 和某个编辑之后的旧 token 位置、内容都相同，
+    // 后面的扫描结果必然和旧的一样，直接复用旧 token（调整偏移和行号）。结果和从头扫描相同
+    pub fn rescan(
+        &mut self,
+        old_tokens: &[Token],
+        old_errors: &[Error],
+        edit: &TextEdit,
+    ) -> (Vec<Token>, Vec<Error>) {
+        let line_start = self.source[..edit.start].rfind('\n').map_or(0, |i| i + 1);
+        // 未闭合的字符串一直延伸到文件结尾，编辑可能补上结尾的引号：从字符串开头重新扫描
+        let safe = old_errors
+            .iter()
+            .filter(|error| error.message.starts_with("Unterminated string"))
+            .map(|error| error.offset)
+            .fold(line_start, usize::min);
+        let reused = old_tokens
+            .iter()
+            .take_while(|token| token.token_type != TokenType::Eof && token.end() <= safe)
+            .count();
+        // token 之间没有未完成的状态，从一个 token 结束处开始扫描和从头扫描到这里是一样的；
+        // 多行字符串的行号是结束处的行号，所以最后一个 token 的行号就是当前行号
+        let (restart, line) = match reused {
+            0 => (0, 1),
+            n => (old_tokens[n - 1].end(), old_tokens[n - 1].line),
+        };
+        self.tokens = old_tokens[..reused].to_vec();
+        self.errors = old_errors
+            .iter()
+            .filter(|error| error.offset < restart)
+            .cloned()
+            .collect();
+        self.seek(restart);
+        self.line = line;
+
+        let delta = edit.inserted.len() as isize - edit.removed_len as isize;
+        let shift = |offset: usize| offset.saturating_add_signed(delta);
+        let mut tail = old_tokens[reused..]
+            .iter()
+            .filter(|token| token.offset >= edit.start + edit.removed_len)
+            .peekable();
+        while !self.is_at_end() {
+            self.start_token();
+            let count = self.tokens.len();
+            self.scan_token();
+            if self.tokens.len() == count {
+                continue;
+            }
+            let token = &self.tokens[count];
+            while tail.next_if(|old| shift(old.offset) < token.offset).is_some() {}
+            let Some(old) = tail.next_if(|old| {
+                shift(old.offset) == token.offset
+                    && old.token_type == token.token_type
+                    && old.lexeme == token.lexeme
+                    && old.literal == token.literal
+            }) else {
+                continue;
+            };
+            let lines = token.line as isize - old.line as isize;
+            let old_end = old.end();
+            // 和匹配的 token 在同一行的旧 token 列号可能变了，要从 token 结尾处接着往后数
+            let new_end = token.end();
+            let mut cursor = (new_end, self.column);
+            let line_end = self.source[new_end..]
+                .find('\n')
+                .map_or(self.source.len(), |i| new_end + i);
+            // 错误信息里可能带有行号（未闭合字符串的起始行），行号变化时后面的错误不能直接复用
+            if lines != 0 && old_errors.iter().any(|error| error.offset >= old_end) {
+                continue;
+            }
+            // #line 指令之后的行号是指令给出的，不随编辑移动
+            if lines != 0 && self.source[new_end..].contains("\n#line") {
+                continue;
+            }
+            for old in tail {
+                let mut token = old.clone();
+                token.offset = shift(old.offset);
+                token.line = old.line.saturating_add_signed(lines);
+                if token.offset <= line_end {
+                    token.column = cursor.1 + self.width(&self.source[cursor.0..token.offset]);
+                    cursor = (token.offset, token.column);
+                }
+                self.tokens.push(token);
+            }
+            for old in old_errors.iter().filter(|error| error.offset >= old_end) {
+                let mut error = old.clone();
+                error.offset = shift(old.offset);
+                error.line = old.line.saturating_add_signed(lines);
+                self.errors.push(error);
+            }
+            return (std::mem::take(&mut self.tokens), std::mem::take(&mut self.errors));
+        }
+        let mut eof = Token::new(TokenType::Eof, String::from(""), None, self.line);
+        eof.offset = self.source.len();
+        eof.column = self.column;
+        self.tokens.push(eof);
+        (std::mem::take(&mut self.tokens), std::mem::take(&mut self.errors))
+    }
+
     // 是否到达了文件的结尾
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
@@ -55,6 +225,23 @@ impl<'a> Scanner<'a> {
             ')' => self.add_token(TokenType::RightParen, None),
             '{' => self.add_token(TokenType::LeftBrace, None),
             '}' => self.add_token(TokenType::RightBrace, None),
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
+            // `?.5` 是问号后跟数字，不是 ?.
+            '?' if self.peek() == '.' && !self.peek_next().is_ascii_digit() => {
+                self.advance();
+                self.add_token(TokenType::QuestionDot, None)
+            }
+            '?' if self.peek() == '[' => {
+                self.advance();
+                self.add_token(TokenType::QuestionBracket, None)
+            }
+            '?' if self.peek() == '(' => {
+                self.advance();
+                self.add_token(TokenType::QuestionParen, None)
+            }
+            '?' => self.add_token(TokenType::Question, None),
+            ':' => self.add_token(TokenType::Colon, None),
             ',' => self.add_token(TokenType::Comma, None),
             '.' => self.add_token(TokenType::Dot, None),
             '-' => self.add_token(TokenType::Minus, None),
@@ -83,6 +270,8 @@ impl<'a> Scanner<'a> {
             '=' => {
                 if self.next_char_match('=') {
                     self.add_token(TokenType::EqualEqual, None);
+                } else if self.next_char_match('>') {
+                    self.add_token(TokenType::Arrow, None);
                 } else {
                     self.add_token(TokenType::Equal, None);
                 }
@@ -101,134 +290,735 @@ impl<'a> Scanner<'a> {
                     self.add_token(TokenType::Greater, None);
                 }
             }
+            '#' if self.at_line_start() && self.source[self.current..].starts_with("line") => {
+                self.line_directive()
+            }
             '"' => self.string(),
             '0'..='9' => self.number(),
-            c if c.is_alphabetic() || c == '_' => self.identifier(),
+            c if is_identifier_start(c) => self.identifier(),
             _ => {
                 self.errors.push(Error {
                     line: self.line,
                     message: format!("Unexpected character: {}", c),
+                    offset: self.start,
+                    code: SCAN_ERROR,
                 });
             }
         }
     }
     fn advance(&mut self) -> Option<char> {
-        self.current += 1;
-        self.source.chars().nth(self.current - 1)
+        let c = self.source[self.current..].chars().next()?;
+        self.current += c.len_utf8();
+        self.column = match c {
+            '\n' => 1,
+            '\t' => self.column + self.tab_width,
+            _ => self.column + 1,
+        };
+        Some(c)
+    }
+    // 新 token 从 current 开始
+    fn start_token(&mut self) {
+        self.start = self.current;
+        self.start_column = self.column;
+    }
+    // 跳到任意位置（增量扫描的起点、未闭合字符串之后的一行），列号从所在行的行首重新数
+    fn seek(&mut self, offset: usize) {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        self.current = offset;
+        self.column = self.width(&self.source[line_start..offset]) + 1;
     }
     // single-character tokens
     pub fn add_token(&mut self, token_type: TokenType, literal: Option<String>) {
-        // let text = &self.source[self.start..self.current];
-        let text = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect::<String>();
-        self.tokens.push(Token::new(
-            token_type,
-            String::from(text),
-            literal,
-            self.line,
-        ));
+        let count = self.kinds.as_ref().map_or(self.tokens.len(), Vec::len);
+        if count >= self.max_tokens {
+            self.errors.push(Error {
+                line: self.line,
+                message: format!("Too many tokens: limit is {}.", self.max_tokens),
+                offset: self.start,
+                code: LIMIT_EXCEEDED,
+            });
+            // 剩下的源码不再扫描
+            self.seek(self.source.len());
+            return;
+        }
+        if self.kinds.is_some() {
+            let span = Span {
+                start: self.start,
+                end: self.current,
+            };
+            self.kinds.get_or_insert_with(Vec::new).push((token_type, span));
+            return;
+        }
+        let text = self.text(self.start, self.current);
+        let mut token = Token::new(token_type, text, literal, self.line);
+        token.offset = self.start;
+        token.column = self.start_column;
+        self.tokens.push(token);
+    }
+    // 一段不含换行的文本占的列数；制表符占 tab_width 列
+    fn width(&self, text: &str) -> usize {
+        text.chars().map(|c| if c == '\t' { self.tab_width } else { 1 }).sum()
+    }
+    // 按字节偏移取出源码片段
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].to_string()
     }
     fn identifier(&mut self) {
-        loop {
-            let c = self.peek();
-            if c.is_alphanumeric() || c == '_' {
-                self.advance();
-            } else {
-                break;
-            }
+        while !self.is_at_end() && is_identifier_continue(self.peek()) {
+            self.advance();
         }
         let text = &self.source[self.start..self.current];
-        let keyword = keywords::map().get(text);
-        if let Some(token_type) = keyword {
-            self.add_token(*token_type, None);
-        } else {
-            self.add_token(TokenType::Identifier, None);
-        }
+        let token_type = keywords::map()
+            .get(text)
+            .copied()
+            .unwrap_or(TokenType::Identifier);
+        self.add_token(token_type, None);
     }
 
     fn next_char_match(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        let c = self.source.chars().nth(self.current).unwrap();
-        if c != expected {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\n';
-        }
-        match self.source.chars().nth(self.current) {
-            Some(c) => c,
-            None => '\n',
-        }
+        self.source[self.current..].chars().next().unwrap_or('\n')
     }
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\n';
-        }
-        match self.source.chars().nth(self.current + 1) {
-            Some(c) => c,
-            None => '\n',
-        }
+        self.source[self.current..].chars().nth(1).unwrap_or('\n')
     }
     fn string(&mut self) {
+        // 记录开头 `"` 所在的行，跨行的未闭合字符串要指回起点
+        let start_line = self.line;
+        // 字符串中第一个换行之后的位置
+        let mut next_line = None;
         while self.peek() != '"' && !self.is_at_end() {
+            // 反斜杠连同后面的字符一起跳过，`\"` 不会结束字符串
+            if self.peek() == '\\' {
+                self.advance();
+                if self.is_at_end() {
+                    break;
+                }
+            }
             if self.peek() == '\n' {
                 self.line += 1;
+                next_line.get_or_insert(self.current + 1);
             }
             self.advance();
         }
         if self.is_at_end() {
+            // 跨行的字符串在信息中写出起始行；只有一行时和 codecrafters 的测试一致
+            let message = match next_line {
+                Some(_) => format!("Unterminated string starting at line {}.", start_line),
+                None => "Unterminated string.".to_string(),
+            };
             self.errors.push(Error {
-                line: self.line,
-                message: "Unterminated string.".to_string(),
+                line: start_line,
+                message,
+                offset: self.start,
+                code: SCAN_ERROR,
             });
+            // 多半是忘了写结尾的引号：从开头所在行的下一行继续扫描，后面的 token 和错误照常报告
+            if let Some(next_line) = next_line {
+                self.seek(next_line);
+                self.line = start_line + 1;
+            }
             return;
         }
         // 当探查到 `"` 字符时，结束字符串并调用 advance
         self.advance();
-        // let literal = &self.source[self.start + 1..self.current - 1];
-        // 字符串（str 类型）是 UTF-8 编码的，因此字符串的底层存储是字节数组
-        // 按字符切片，而不是字节切片，因为字符串可能包含非 ASCII 字符
-        let literal = &self
-            .source
-            .chars()
-            .skip(self.start + 1)
-            .take(self.current - self.start - 2)
-            .collect::<String>();
-        self.add_token(TokenType::String, Some(String::from(literal)));
+        if self.kinds.is_some() {
+            return self.add_token(TokenType::String, None);
+        }
+        let raw = self.text(self.start + 1, self.current - 1);
+        let literal = self.unescape(&raw, start_line);
+        self.add_token(TokenType::String, Some(literal));
+    }
+
+    // 解码字符串中的转义序列：\n \t \r \" \\ 和 \u{XXXX}；其他转义报错，原样保留
+    fn unescape(&mut self, raw: &str, start_line: usize) -> String {
+        let mut literal = String::new();
+        let mut line = start_line;
+        let mut chars = raw.chars();
+        while let Some(c) = chars.next() {
+            if c == '\n' {
+                line += 1;
+            }
+            if c != '\\' {
+                literal.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => literal.push('\n'),
+                Some('t') => literal.push('\t'),
+                Some('r') => literal.push('\r'),
+                Some('"') => literal.push('"'),
+                Some('\\') => literal.push('\\'),
+                Some('u') => match unicode_escape(&mut chars) {
+                    Ok(c) => literal.push(c),
+                    Err((message, text)) => {
+                        self.errors.push(Error {
+                            line,
+                            message,
+                            offset: self.start,
+                            code: SCAN_ERROR,
+                        });
+                        literal.push_str(&text);
+                    }
+                },
+                other => {
+                    let escape = other.map(|c| c.to_string()).unwrap_or_default();
+                    self.errors.push(Error {
+                        line,
+                        message: format!("Invalid escape sequence '\\{}'.", escape.escape_debug()),
+                        offset: self.start,
+                        code: SCAN_ERROR,
+                    });
+                    literal.push('\\');
+                    literal.push_str(&escape);
+                    if other == Some('\n') {
+                        line += 1;
+                    }
+                }
+            }
+        }
+        literal
+    }
+
+    // start 是否是一行的第一个字符
+    fn at_line_start(&self) -> bool {
+        self.start == 0 || self.source[..self.start].ends_with('\n')
+    }
+
+    // #line N 或 #line N "file"：生成的代码用它指回原始源码的位置，下一行的行号是 N。
+    // 格式不对时报错并忽略整行
+    fn line_directive(&mut self) {
+        while !self.is_at_end() && self.peek() != '\n' {
+            self.advance();
+        }
+        let text = self.text(self.start, self.current);
+        let mut parts = text["#line".len()..].trim().splitn(2, char::is_whitespace);
+        let number = parts.next().and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0);
+        let file = match parts.next().map(str::trim) {
+            None => Some(None),
+            Some(file) if file.len() >= 2 && file.starts_with('"') && file.ends_with('"') => {
+                Some(Some(file[1..file.len() - 1].to_string()))
+            }
+            Some(_) => None,
+        };
+        match (number, file) {
+            (Some(number), Some(file)) => {
+                // 行尾的换行会把行号加一
+                self.line = number - 1;
+                if file.is_some() {
+                    self.file = file;
+                }
+            }
+            _ => self.errors.push(Error {
+                line: self.line,
+                message: format!(
+                    "Invalid directive '{}': expected '#line N' or '#line N \"file\"'.",
+                    text.trim_end()
+                ),
+                offset: self.start,
+                code: SCAN_ERROR,
+            }),
+        }
     }
 
     fn number(&mut self) {
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             // Consume the "."
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
+                self.advance();
+            }
+        }
+        // 数字后面紧跟标识符字符（123abc）多半是笔误：连同这些字符一起报错，不拆成数字和标识符
+        if is_identifier_start(self.peek()) {
+            while !self.is_at_end() && is_identifier_continue(self.peek()) {
                 self.advance();
             }
+            self.errors.push(Error {
+                line: self.line,
+                message: "Invalid number literal.".to_string(),
+                offset: self.start,
+                code: SCAN_ERROR,
+            });
+            return;
+        }
+        if self.kinds.is_some() {
+            return self.add_token(TokenType::Number, None);
         }
-        let literal = &self.source[self.start..self.current];
+        let literal = self.text(self.start, self.current);
         let float = literal
             .parse::<f64>()
             .expect("Number token should be parsed into float");
-        let mut value = float.to_string();
-        if !value.contains(".") {
-            value.push_str(".0");
+        if float.is_infinite() {
+            self.errors.push(Error {
+                line: self.line,
+                message: "Number literal overflows to infinity.".to_string(),
+                offset: self.start,
+                code: SCAN_ERROR,
+            });
+        } else if significant_digits(&literal) != significant_digits(&float.to_string()) {
+            // f64 的最短十进制表示和字面量的有效数字不同，说明有效数字超出了 f64 的精度
+            self.warnings.push(Error {
+                line: self.line,
+                message: format!(
+                    "Number literal {} can't be represented exactly; it becomes {}.",
+                    literal, float
+                ),
+                offset: self.start,
+                code: INEXACT_NUMBER,
+            });
+        }
+        self.add_token(TokenType::Number, Some(format_literal(float)));
+    }
+}
+
+// 解析 \u 之后的 {XXXX}（1 到 6 位十六进制数，必须是 Unicode 标量值，不能是代理项）。
+// 出错时返回错误信息和已读取的转义文本，文本原样保留在字符串中
+fn unicode_escape(chars: &mut std::str::Chars) -> Result<char, (String, String)> {
+    let mut text = String::from("\\u");
+    if chars.clone().next() != Some('{') {
+        return Err((
+            "Invalid unicode escape '\\u': expected '{' after '\\u'.".to_string(),
+            text,
+        ));
+    }
+    chars.next();
+    text.push('{');
+    let mut digits = String::new();
+    loop {
+        match chars.clone().next() {
+            Some('}') => {
+                chars.next();
+                text.push('}');
+                break;
+            }
+            Some(c) if c.is_ascii_alphanumeric() => {
+                chars.next();
+                text.push(c);
+                digits.push(c);
+            }
+            _ => {
+                return Err((
+                    format!("Unterminated unicode escape '{}'.", text),
+                    text,
+                ))
+            }
+        }
+    }
+    if digits.is_empty() || digits.len() > 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err((
+            format!("Invalid unicode escape '{}': expected 1 to 6 hex digits.", text),
+            text,
+        ));
+    }
+    let code = u32::from_str_radix(&digits, 16).expect("hex digits should parse");
+    char::from_u32(code).ok_or_else(|| {
+        (
+            format!(
+                "Invalid unicode escape '{}': U+{:04X} is not a Unicode scalar value.",
+                text, code
+            ),
+            text,
+        )
+    })
+}
+
+// 标识符规则（近似 Unicode 的 XID_Start / XID_Continue）：
+// 以字母（任何文字的字母，如 é、数）或 `_` 开头，后面可以是字母、数字、`_`、
+// 组合附加符号（如 e 后面的 U+0301）以及零宽连接符 U+200C / U+200D。
+// emoji 和标点符号不是字母，不能出现在标识符中
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    c.is_alphanumeric()
+        || c == '_'
+        || matches!(
+            c,
+            '\u{0300}'..='\u{036F}'
+                | '\u{1AB0}'..='\u{1AFF}'
+                | '\u{1DC0}'..='\u{1DFF}'
+                | '\u{20D0}'..='\u{20FF}'
+                | '\u{FE20}'..='\u{FE2F}'
+                | '\u{200C}'
+                | '\u{200D}'
+        )
+}
+
+// 十进制数的有效数字（去掉首尾的 0）和小数点相对第一位有效数字的位置，例如 "012.50" -> ("125", 2)，
+// 0.001 -> ("1", -2)；值为 0 时是 ("", 0)
+fn significant_digits(number: &str) -> (String, isize) {
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits = format!("{}{}", integer, fraction);
+    let significant = digits.trim_matches('0').to_string();
+    if significant.is_empty() {
+        return (significant, 0);
+    }
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    (significant, integer.len() as isize - leading as isize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Snapshot = (
+        Vec<(TokenType, String, usize, usize, Option<String>, usize)>,
+        Vec<(String, usize, usize)>,
+    );
+
+    fn snapshot(tokens: &[Token], errors: &[Error]) -> Snapshot {
+        (
+            tokens
+                .iter()
+                .map(|t| {
+                    (t.token_type, t.lexeme.clone(), t.line, t.offset, t.literal.clone(), t.column)
+                })
+                .collect(),
+            errors
+                .iter()
+                .map(|e| (e.message.clone(), e.line, e.offset))
+                .collect(),
+        )
+    }
+
+    // 先扫描 source，再按 edit 增量扫描，结果必须和直接扫描编辑后的源码相同
+    fn assert_rescan(source: &str, start: usize, removed_len: usize, inserted: &str) {
+        let edit = TextEdit {
+            start,
+            removed_len,
+            inserted,
+        };
+        let edited = edit.apply(source);
+        let mut old = Scanner::new(source);
+        let (old_tokens, old_errors) = old.scan_tokens();
+        let (old_tokens, old_errors) = (old_tokens.clone(), old_errors.clone());
+        let mut scanner = Scanner::new(&edited);
+        let (tokens, errors) = scanner.rescan(&old_tokens, &old_errors, &edit);
+        let mut full = Scanner::new(&edited);
+        let (full_tokens, full_errors) = full.scan_tokens();
+        assert_eq!(
+            snapshot(&tokens, &errors),
+            snapshot(full_tokens, full_errors),
+            "{:?} -> {:?}",
+            source,
+            edited
+        );
+    }
+
+    #[test]
+    fn rescan_matches_a_full_scan() {
+        let source = "var a = 1;\nprint a + 2;\nvar s = \"two\nlines\";\n@ print s;\n";
+        // 行内替换、跨行删除、在开头和结尾插入
+        assert_rescan(source, 8, 1, "42");
+        assert_rescan(source, 4, 14, "");
+        assert_rescan(source, 0, 0, "// comment\n");
+        assert_rescan(source, source.len(), 0, "print 3;");
+        // 插入换行，后面所有 token 的行号都要变
+        assert_rescan(source, 10, 0, "\n\n");
+        // 打开一个未闭合的字符串，后面的源码都成了字符串内容
+        assert_rescan(source, 11, 0, "\"");
+        // 编辑跨行字符串的内部
+        assert_rescan(source, 37, 2, "x\ny");
+    }
+
+    #[test]
+    fn offsets_are_byte_offsets_into_the_source() {
+        let mut scanner = Scanner::new("\"é\" x\n @");
+        let (tokens, errors) = scanner.scan_tokens();
+        let offsets: Vec<usize> = tokens.iter().map(|t| t.offset).collect();
+        assert_eq!(offsets, vec![0, 5, 9]);
+        assert_eq!(tokens[1].end(), 6);
+        assert_eq!(errors[0].offset, 8);
+    }
+
+    // 测试用的伪随机数（xorshift），种子固定，失败时可以复现
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    // 程序和插入的文本都由这些片段拼成，包括跨行字符串、注释、非 ASCII 字符、
+    // 未闭合的字符串和 #line 指令这些需要从更早的位置重新扫描的情况
+    const PIECES: &[&str] = &[
+        "var", " ", "x", "héllo", "数", "=", "==", "!=", "<=", "(", ")", "{", "}", ";", "\n",
+        "\t", "1", "2.5", "123abc", "\"", "\"str\"", "\"a\nb\"", "\"\\n\\u{41}\"", "\"\\q\"",
+        "// 注释\n", "/", "print ", "#line 10\n", "?.", "@", "and", "xor", "1e9",
+    ];
+
+    // 源码中随机的字符边界
+    fn boundary(rng: &mut Rng, source: &str) -> usize {
+        let mut offset = rng.below(source.len() + 1);
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    #[test]
+    fn rescan_matches_full_scan_for_random_edits() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..3000 {
+            let source: String = (0..rng.below(40)).map(|_| rng.pick(PIECES)).collect();
+            let start = boundary(&mut rng, &source);
+            let end = start + boundary(&mut rng, &source[start..]);
+            let inserted: String = (0..rng.below(4)).map(|_| rng.pick(PIECES)).collect();
+            assert_rescan(&source, start, end - start, &inserted);
+        }
+    }
+
+    // 列号从 1 开始按字符计算，非 ASCII 字符也只占一列
+    #[test]
+    fn columns_count_characters_from_the_line_start() {
+        let mut scanner = Scanner::new("var x;\n  \"é\" +\n数 @");
+        let (tokens, errors) = scanner.scan_tokens();
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, vec![(1, 1), (1, 5), (1, 6), (2, 3), (2, 7), (3, 1), (3, 4)]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    // 制表符占 tab_width 列；列号在扫描时逐字符累加，跳过未闭合的字符串之后从行首重新数
+    #[test]
+    fn tab_indented_columns() {
+        let source = "fun f() {\n\tif (x) {\n\t\treturn\t1;\n\t}\n}\n\t\"open\n\tnil";
+        for (tab_width, expected) in [
+            (1, [(2, 2), (2, 9), (3, 3), (3, 10), (4, 2), (5, 1), (7, 2), (7, 5)]),
+            (4, [(2, 5), (2, 12), (3, 9), (3, 19), (4, 5), (5, 1), (7, 5), (7, 8)]),
+        ] {
+            let mut scanner = Scanner::new(source);
+            scanner.tab_width = tab_width;
+            let (tokens, errors) = scanner.scan_tokens();
+            assert_eq!(errors.len(), 1);
+            // 每行的第一个 token 和后面跟在制表符或空格之后的 token
+            let kept = ["if", "{", "return", "1", "}", "nil", ""];
+            let positions: Vec<(usize, usize)> = tokens
+                .iter()
+                .filter(|t| t.line > 1 && kept.contains(&t.lexeme.as_str()))
+                .map(|t| (t.line, t.column))
+                .collect();
+            assert_eq!(positions, expected, "tab_width {}", tab_width);
+        }
+    }
+
+    #[test]
+    fn significant_digits_ignore_leading_and_trailing_zeros() {
+        assert_eq!(significant_digits("012.50"), ("125".to_string(), 2));
+        assert_eq!(significant_digits("0.001"), ("1".to_string(), -2));
+        assert_eq!(significant_digits("1200"), ("12".to_string(), 4));
+        assert_eq!(significant_digits("0.0"), (String::new(), 0));
+        assert_eq!(significant_digits("1.5"), significant_digits("1.50"));
+    }
+
+    // 超出 f64 精度的字面量只是警告，溢出到无穷大是错误
+    #[test]
+    fn imprecise_and_overflowing_literals() {
+        let mut scanner = Scanner::new("0.1 1.50 123456789.125");
+        assert!(scanner.scan_tokens().1.is_empty());
+        assert!(scanner.warnings().is_empty());
+
+        let mut scanner = Scanner::new("9007199254740993");
+        assert!(scanner.scan_tokens().1.is_empty());
+        let warnings = scanner.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "Number literal 9007199254740993 can't be represented exactly; \
+             it becomes 9007199254740992."
+        );
+
+        let source = format!("1{}", "0".repeat(400));
+        let mut scanner = Scanner::new(&source);
+        let (_, errors) = scanner.scan_tokens();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Number literal overflows to infinity.");
+    }
+
+    // conformance 目录下的所有 .lox 文件
+    fn corpus() -> Vec<String> {
+        let mut sources = Vec::new();
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/conformance");
+        let mut dirs = vec![std::path::PathBuf::from(root)];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "lox") {
+                    sources.push(std::fs::read_to_string(path).unwrap());
+                }
+            }
+        }
+        sources
+    }
+
+    #[test]
+    fn scan_kinds_agrees_with_scan_tokens() {
+        let mut sources = corpus();
+        assert!(!sources.is_empty());
+        // 扫描出错的源码也要一致：出错的字符不产生 token
+        sources.extend(
+            ["", "a @ b", "\"open", "1.5.x", "\u{e9} = \"\u{fc}\";"].map(String::from),
+        );
+        for source in &sources {
+            let mut scanner = Scanner::new(source);
+            let (tokens, _) = scanner.scan_tokens();
+            let expected: Vec<_> = tokens.iter().map(|t| (t.token_type, t.span())).collect();
+            assert_eq!(Scanner::new(source).scan_kinds(), expected, "{:?}", source);
         }
+    }
 
-        self.add_token(TokenType::Number, Some(value));
+    // #line 指令之后的行号从指令给出的数开始，文件名可选
+    #[test]
+    fn line_directives_reset_the_line() {
+        let mut scanner = Scanner::new("a\n#line 40\nb\nc\n#line 7 \"gen.lox\"\nd");
+        let (tokens, errors) = scanner.scan_tokens();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let lines: Vec<(&str, usize)> =
+            tokens.iter().map(|t| (t.lexeme.as_str(), t.line)).collect();
+        assert_eq!(lines, vec![("a", 1), ("b", 40), ("c", 41), ("d", 7), ("", 7)]);
+        assert_eq!(scanner.file(), Some("gen.lox"));
+        assert_eq!(Scanner::new("a").file(), None);
+    }
+
+    #[test]
+    fn invalid_line_directives() {
+        for (source, text) in [
+            ("#line\nx", "#line"),
+            ("#line 0\nx", "#line 0"),
+            ("#line x\nx", "#line x"),
+            ("#line 3 gen.lox\nx", "#line 3 gen.lox"),
+        ] {
+            let mut scanner = Scanner::new(source);
+            let (tokens, errors) = scanner.scan_tokens();
+            let message = format!(
+                "Invalid directive '{}': expected '#line N' or '#line N \"file\"'.",
+                text
+            );
+            assert_eq!(errors.len(), 1, "{:?}", source);
+            assert_eq!(errors[0].message, message);
+            // 出错的指令整行忽略，行号照常增加
+            assert_eq!((tokens[0].lexeme.as_str(), tokens[0].line), ("x", 2), "{:?}", source);
+        }
+        // 不在行首的 # 仍然是非法字符
+        let mut scanner = Scanner::new("a #line 3");
+        let (_, errors) = scanner.scan_tokens();
+        assert_eq!(errors[0].message, "Unexpected character: #");
+    }
+
+    #[test]
+    fn rescan_after_line_directives() {
+        let source = "a\n#line 10\nb\nc\n";
+        assert_rescan(source, 0, 0, "x\n");
+        assert_rescan(source, source.len(), 0, "d\ne\n");
+        assert_rescan(source, 2, 9, "");
+    }
+
+    // 再次调用 scan_tokens 返回同样的结果，不会多出第二个 EOF
+    #[test]
+    fn scan_tokens_twice_returns_same_tokens() {
+        let mut scanner = Scanner::new("var a = \"s\";\nprint a + 1; @");
+        let (tokens, errors) = scanner.scan_tokens();
+        let first = snapshot(tokens, errors);
+        let (tokens, errors) = scanner.scan_tokens();
+        let second = snapshot(tokens, errors);
+        assert_eq!(first, second);
+        assert_eq!(second.1.len(), 1);
+        let eofs = second.0.iter().filter(|t| t.0 == TokenType::Eof).count();
+        assert_eq!(eofs, 1);
+        assert_eq!(second.0.last().map(|t| t.0), Some(TokenType::Eof));
+    }
+
+    // 未闭合的字符串只吞掉开头所在的那一行，后面的 token 和错误照常报告
+    #[test]
+    fn unterminated_string_resumes_on_the_next_line() {
+        let mut scanner = Scanner::new("var s = \"broken;\nprint a;\nvar b = @;");
+        let (tokens, errors) = scanner.scan_tokens();
+        let (tokens, errors) = snapshot(tokens, errors);
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.0).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Print,
+                TokenType::Identifier,
+                TokenType::Semicolon,
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Semicolon,
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(tokens[3].2, 2);
+        assert_eq!(
+            errors,
+            vec![
+                ("Unterminated string starting at line 1.".to_string(), 1, 8),
+                ("Unexpected character: @".to_string(), 3, 34),
+            ]
+        );
+        // 只有一行时和 codecrafters 的信息一致
+        let mut scanner = Scanner::new("print \"abc");
+        let (_, errors) = scanner.scan_tokens();
+        assert_eq!(errors[0].message, "Unterminated string.");
+        assert_eq!(errors[0].line, 1);
+    }
+
+    // 数字后面紧跟标识符字符时整体报错，不拆成数字和标识符
+    #[test]
+    fn number_followed_by_identifier() {
+        let scan = |source: &str| {
+            let mut scanner = Scanner::new(source);
+            let (tokens, errors) = scanner.scan_tokens();
+            snapshot(tokens, errors)
+        };
+        let invalid = |offset| vec![("Invalid number literal.".to_string(), 1, offset)];
+        let (tokens, errors) = scan("123abc");
+        assert_eq!(errors, invalid(0));
+        assert_eq!(tokens.len(), 1);
+        let (tokens, errors) = scan("x = 1.5e3;");
+        assert_eq!(errors, invalid(4));
+        let kinds: Vec<_> = tokens.iter().map(|t| t.0).collect();
+        assert_eq!(
+            kinds,
+            vec![TokenType::Identifier, TokenType::Equal, TokenType::Semicolon, TokenType::Eof]
+        );
+        let (tokens, errors) = scan("123 abc");
+        assert!(errors.is_empty());
+        let tokens: Vec<_> = tokens.iter().map(|t| (t.0, t.1.as_str(), t.3)).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenType::Number, "123", 0),
+                (TokenType::Identifier, "abc", 4),
+                (TokenType::Eof, "", 7)
+            ]
+        );
     }
 }
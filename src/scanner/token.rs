@@ -9,6 +9,8 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -16,11 +18,20 @@ pub enum TokenType {
     Semicolon,
     Star,
     Slash,
+    // 整除运算符 div；div 不是关键字，由解析器在运算符位置识别后改为这个类型
+    Div,
+    Question,
+    QuestionDot,
+    // ?[ 和 ?(：问号和括号之间不能有空白，"? [" 仍然是两个 token
+    QuestionBracket,
+    QuestionParen,
+    Colon,
     // One or two character tokens
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    Arrow,
     Greater,
     GreaterEqual,
     Less,
@@ -31,12 +42,16 @@ pub enum TokenType {
     Identifier,
     // Keywords
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    Loop,
+    Match,
     Nil,
     Or,
     Print,
@@ -46,53 +61,132 @@ pub enum TokenType {
     True,
     Var,
     While,
+    // 逻辑异或，两边都会求值
+    Xor,
     // End of file
     Eof,
 }
 
-impl ToString for TokenType {
-    fn to_string(&self) -> String {
-        match self {
-            TokenType::LeftParen => "LEFT_PAREN".to_string(),
-            TokenType::RightParen => "RIGHT_PAREN".to_string(),
-            TokenType::LeftBrace => "LEFT_BRACE".to_string(),
-            TokenType::RightBrace => "RIGHT_BRACE".to_string(),
-            TokenType::Comma => "COMMA".to_string(),
-            TokenType::Dot => "DOT".to_string(),
-            TokenType::Minus => "MINUS".to_string(),
-            TokenType::Plus => "PLUS".to_string(),
-            TokenType::Semicolon => "SEMICOLON".to_string(),
-            TokenType::Star => "STAR".to_string(),
-            TokenType::Slash => "SLASH".to_string(),
-            TokenType::Bang => "BANG".to_string(),
-            TokenType::BangEqual => "BANG_EQUAL".to_string(),
-            TokenType::Equal => "EQUAL".to_string(),
-            TokenType::EqualEqual => "EQUAL_EQUAL".to_string(),
-            TokenType::Greater => "GREATER".to_string(),
-            TokenType::GreaterEqual => "GREATER_EQUAL".to_string(),
-            TokenType::Less => "LESS".to_string(),
-            TokenType::LessEqual => "LESS_EQUAL".to_string(),
-            TokenType::String => "STRING".to_string(),
-            TokenType::Number => "NUMBER".to_string(),
-            TokenType::Identifier => "IDENTIFIER".to_string(),
-            TokenType::And => "AND".to_string(),
-            TokenType::Class => "CLASS".to_string(),
-            TokenType::Else => "ELSE".to_string(),
-            TokenType::False => "FALSE".to_string(),
-            TokenType::Fun => "FUN".to_string(),
-            TokenType::For => "FOR".to_string(),
-            TokenType::If => "IF".to_string(),
-            TokenType::Nil => "NIL".to_string(),
-            TokenType::Or => "OR".to_string(),
-            TokenType::Print => "PRINT".to_string(),
-            TokenType::Return => "RETURN".to_string(),
-            TokenType::Super => "SUPER".to_string(),
-            TokenType::This => "THIS".to_string(),
-            TokenType::True => "TRUE".to_string(),
-            TokenType::Var => "VAR".to_string(),
-            TokenType::While => "WHILE".to_string(),
-            TokenType::Eof => "EOF".to_string(),
-        }
+impl Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TokenType::LeftParen => "LEFT_PAREN",
+            TokenType::RightParen => "RIGHT_PAREN",
+            TokenType::LeftBrace => "LEFT_BRACE",
+            TokenType::RightBrace => "RIGHT_BRACE",
+            TokenType::LeftBracket => "LEFT_BRACKET",
+            TokenType::RightBracket => "RIGHT_BRACKET",
+            TokenType::Comma => "COMMA",
+            TokenType::Dot => "DOT",
+            TokenType::Minus => "MINUS",
+            TokenType::Plus => "PLUS",
+            TokenType::Semicolon => "SEMICOLON",
+            TokenType::Star => "STAR",
+            TokenType::Slash => "SLASH",
+            TokenType::Div => "DIV",
+            TokenType::Question => "QUESTION",
+            TokenType::QuestionDot => "QUESTION_DOT",
+            TokenType::QuestionBracket => "QUESTION_BRACKET",
+            TokenType::QuestionParen => "QUESTION_PAREN",
+            TokenType::Colon => "COLON",
+            TokenType::Bang => "BANG",
+            TokenType::BangEqual => "BANG_EQUAL",
+            TokenType::Equal => "EQUAL",
+            TokenType::EqualEqual => "EQUAL_EQUAL",
+            TokenType::Arrow => "ARROW",
+            TokenType::Greater => "GREATER",
+            TokenType::GreaterEqual => "GREATER_EQUAL",
+            TokenType::Less => "LESS",
+            TokenType::LessEqual => "LESS_EQUAL",
+            TokenType::String => "STRING",
+            TokenType::Number => "NUMBER",
+            TokenType::Identifier => "IDENTIFIER",
+            TokenType::And => "AND",
+            TokenType::Break => "BREAK",
+            TokenType::Class => "CLASS",
+            TokenType::Continue => "CONTINUE",
+            TokenType::Else => "ELSE",
+            TokenType::False => "FALSE",
+            TokenType::Fun => "FUN",
+            TokenType::For => "FOR",
+            TokenType::If => "IF",
+            TokenType::Loop => "LOOP",
+            TokenType::Match => "MATCH",
+            TokenType::Nil => "NIL",
+            TokenType::Or => "OR",
+            TokenType::Print => "PRINT",
+            TokenType::Return => "RETURN",
+            TokenType::Super => "SUPER",
+            TokenType::This => "THIS",
+            TokenType::True => "TRUE",
+            TokenType::Var => "VAR",
+            TokenType::While => "WHILE",
+            TokenType::Xor => "XOR",
+            TokenType::Eof => "EOF",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl TokenType {
+    // Display 的反向：按 tokenize 输出的名字查找，例如 "LEFT_PAREN"
+    pub fn from_name(name: &str) -> Option<TokenType> {
+        Some(match name {
+            "LEFT_PAREN" => TokenType::LeftParen,
+            "RIGHT_PAREN" => TokenType::RightParen,
+            "LEFT_BRACE" => TokenType::LeftBrace,
+            "RIGHT_BRACE" => TokenType::RightBrace,
+            "LEFT_BRACKET" => TokenType::LeftBracket,
+            "RIGHT_BRACKET" => TokenType::RightBracket,
+            "COMMA" => TokenType::Comma,
+            "DOT" => TokenType::Dot,
+            "MINUS" => TokenType::Minus,
+            "PLUS" => TokenType::Plus,
+            "SEMICOLON" => TokenType::Semicolon,
+            "STAR" => TokenType::Star,
+            "SLASH" => TokenType::Slash,
+            "DIV" => TokenType::Div,
+            "QUESTION" => TokenType::Question,
+            "QUESTION_DOT" => TokenType::QuestionDot,
+            "QUESTION_BRACKET" => TokenType::QuestionBracket,
+            "QUESTION_PAREN" => TokenType::QuestionParen,
+            "COLON" => TokenType::Colon,
+            "BANG" => TokenType::Bang,
+            "BANG_EQUAL" => TokenType::BangEqual,
+            "EQUAL" => TokenType::Equal,
+            "EQUAL_EQUAL" => TokenType::EqualEqual,
+            "ARROW" => TokenType::Arrow,
+            "GREATER" => TokenType::Greater,
+            "GREATER_EQUAL" => TokenType::GreaterEqual,
+            "LESS" => TokenType::Less,
+            "LESS_EQUAL" => TokenType::LessEqual,
+            "STRING" => TokenType::String,
+            "NUMBER" => TokenType::Number,
+            "IDENTIFIER" => TokenType::Identifier,
+            "AND" => TokenType::And,
+            "BREAK" => TokenType::Break,
+            "CLASS" => TokenType::Class,
+            "CONTINUE" => TokenType::Continue,
+            "ELSE" => TokenType::Else,
+            "FALSE" => TokenType::False,
+            "FUN" => TokenType::Fun,
+            "FOR" => TokenType::For,
+            "IF" => TokenType::If,
+            "LOOP" => TokenType::Loop,
+            "MATCH" => TokenType::Match,
+            "NIL" => TokenType::Nil,
+            "OR" => TokenType::Or,
+            "PRINT" => TokenType::Print,
+            "RETURN" => TokenType::Return,
+            "SUPER" => TokenType::Super,
+            "THIS" => TokenType::This,
+            "TRUE" => TokenType::True,
+            "VAR" => TokenType::Var,
+            "WHILE" => TokenType::While,
+            "XOR" => TokenType::Xor,
+            "EOF" => TokenType::Eof,
+            _ => return None,
+        })
     }
 }
 
@@ -102,6 +196,10 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<String>,
     pub line: usize,
+    // lexeme 在源码中开始的字节偏移，结束位置是 offset + lexeme.len()；不是扫描得到的 token 为 0
+    pub offset: usize,
+    // lexeme 开始的列号，从 1 开始，制表符按 Scanner::tab_width 计算；不是扫描得到的 token 为 0
+    pub column: usize,
 }
 
 impl Token {
@@ -116,27 +214,72 @@ impl Token {
             lexeme,
             literal,
             line,
+            offset: 0,
+            column: 0,
+        }
+    }
+    // lexeme 之后的字节偏移
+    pub fn end(&self) -> usize {
+        self.offset + self.lexeme.len()
+    }
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.offset,
+            end: self.end(),
         }
     }
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
-        format!(
+// token 在源码中的字节范围 [start, end)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Display for Token {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
             "{} {} {}",
-            self.token_type.to_string(),
+            self.token_type,
             self.lexeme,
             match self.literal {
-              Some(ref l) => l,
-              None => "null",
-          }
+                // 字符串字面量显示解码后的值，但控制字符和反斜杠要转义，保证每个 token 占一行
+                Some(ref l) if self.token_type == TokenType::String => escape(l),
+                Some(ref l) => l.clone(),
+                None => "null".to_string(),
+            }
         )
     }
 }
 
+// 把反斜杠、双引号和控制字符写成转义序列，结果不含换行，可以原样放回字符串字面量中。
+// tokenize 的字面量列和 parse 的语法树输出使用；运行时 print 输出的是解码后的原始字符
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => escaped.push_str(&c.escape_unicode().to_string()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, Clone)]
 pub struct Error {
     pub message: String,
     pub line: usize,
+    // 出错的 token（或字符）开始的字节偏移
+    pub offset: usize,
+    // 诊断代码，例如 diagnostics::SCAN_ERROR
+    pub code: &'static str,
 }
 
 impl Display for Error {
@@ -144,3 +287,5 @@ impl Display for Error {
         write!(fmt, "[line {}] Error: {}", self.line, self.message)
     }
 }
+
+impl std::error::Error for Error {}
@@ -0,0 +1,148 @@
+use std::{
+    cell::Cell,
+    fmt::{self, Debug},
+    ops::Deref,
+};
+
+// 存活对象的计数，用于观察内存占用（例如闭包造成的 Rc 循环引用）。
+// 值通过 Rc 共享，不会离开创建它的线程，所以计数按线程记录：不同线程中的解释器互不影响，
+// 同一线程中的解释器共享计数。普通的 Cell 加减，没有原子操作的开销；
+// 不需要计数时可以关闭 stats 特性（--no-default-features），计数的代码全部去掉
+thread_local! {
+    static COUNTS: [Cell<usize>; 4] = const { [const { Cell::new(0) }; 4] };
+}
+
+// COUNTS 中的下标
+#[derive(Clone, Copy)]
+pub(crate) struct Counter(usize);
+
+pub(crate) const ENVIRONMENTS: Counter = Counter(0);
+pub(crate) const FUNCTIONS: Counter = Counter(1);
+pub(crate) const LISTS: Counter = Counter(2);
+pub(crate) const MAPS: Counter = Counter(3);
+
+impl Counter {
+    fn get(self) -> usize {
+        if !cfg!(feature = "stats") {
+            return 0;
+        }
+        COUNTS.with(|counts| counts[self.0].get())
+    }
+    // 线程结束时 COUNTS 可能先于还存活的值被销毁，这时不再计数
+    fn add(self, delta: isize) {
+        if !cfg!(feature = "stats") {
+            return;
+        }
+        let _ = COUNTS.try_with(|counts| {
+            let count = &counts[self.0];
+            count.set(count.get().wrapping_add_signed(delta));
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub environments: usize,
+    pub functions: usize,
+    pub lists: usize,
+    pub maps: usize,
+}
+
+// 当前线程中存活的对象数
+pub fn current() -> Stats {
+    Stats {
+        environments: ENVIRONMENTS.get(),
+        functions: FUNCTIONS.get(),
+        lists: LISTS.get(),
+        maps: MAPS.get(),
+    }
+}
+
+// 放在被计数的结构体里：创建（包括 clone）时加一，drop 时减一
+pub(crate) struct Live(Counter);
+
+impl Live {
+    pub(crate) fn new(counter: Counter) -> Self {
+        counter.add(1);
+        Self(counter)
+    }
+}
+
+impl Clone for Live {
+    fn clone(&self) -> Self {
+        Self::new(self.0)
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        self.0.add(-1);
+    }
+}
+
+impl Debug for Live {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Live")
+    }
+}
+
+// 带计数的包装，解引用到内部的值；列表和映射的存储用它包一层
+pub struct Tracked<T> {
+    value: T,
+    _live: Live,
+}
+
+impl<T> Tracked<T> {
+    pub(crate) fn new(value: T, counter: Counter) -> Self {
+        Self {
+            value,
+            _live: Live::new(counter),
+        }
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Debug> Debug for Tracked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+// 关闭 stats 特性时计数总是 0，没有可测的内容
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    use super::*;
+
+    // 计数按线程记录：另一个线程中存活的对象不计入当前线程
+    #[test]
+    fn counts_are_per_thread() {
+        let before = current();
+        let list = Tracked::new(vec![1], LISTS);
+        let map = Tracked::new(vec![2], MAPS);
+        let copy = Live::new(FUNCTIONS).clone();
+        let other = std::thread::spawn(|| {
+            let start = current();
+            let _list = Tracked::new(vec![3], LISTS);
+            (start, current())
+        })
+        .join()
+        .unwrap();
+        let zero = Stats { environments: 0, functions: 0, lists: 0, maps: 0 };
+        assert_eq!(other, (zero, Stats { lists: 1, ..zero }));
+        let expected = Stats {
+            functions: before.functions + 1,
+            lists: before.lists + 1,
+            maps: before.maps + 1,
+            ..before
+        };
+        assert_eq!(current(), expected);
+        drop((list, map, copy));
+        assert_eq!(current(), before);
+    }
+}
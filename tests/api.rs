@@ -0,0 +1,394 @@
+// 通过库接口使用解释器（不经过命令行）
+use std::{
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use codecrafters_interpreter::{
+    environment::Environment,
+    interpreter::{Capabilities, Interpreter, Value},
+    natives::{self, time::FixedTime},
+    parser::{lower, Parser},
+    scanner::{
+        token::{Token, TokenType},
+        Scanner,
+    },
+};
+
+// 用给定的解释器运行程序，运行时错误转成错误信息
+fn run_in(interpreter: &mut Interpreter, source: &str) -> Result<(), String> {
+    let mut scanner = Scanner::new(source);
+    let (tokens, errors) = scanner.scan_tokens();
+    assert!(errors.is_empty(), "{:?}", source);
+    let stmts = Parser::new(tokens).parse().expect("source should parse");
+    interpreter
+        .interpret(lower::strip_groups_stmts(stmts))
+        .map_err(|error| error.to_string())
+}
+
+// 运行程序并返回解释器，之后可以检查它的状态
+fn interpret(source: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    run_in(&mut interpreter, source).expect("source should run");
+    interpreter
+}
+
+#[test]
+fn globals_are_sorted_by_name() {
+    let interpreter = interpret("var b = 2; var a = \"one\"; var unset; fun f() {}");
+    let globals: Vec<(String, String)> = interpreter
+        .globals()
+        .into_iter()
+        .map(|(name, value)| (name, value.to_string()))
+        .collect();
+    let names: Vec<&str> = globals.iter().map(|(name, _)| name.as_str()).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    for (name, value) in [("a", "one"), ("b", "2"), ("unset", "nil")] {
+        assert!(globals.contains(&(name.to_string(), value.to_string())), "{}", name);
+    }
+    assert!(names.contains(&"f") && names.contains(&"clock"));
+}
+
+#[test]
+fn globals_do_not_include_locals() {
+    let interpreter = interpret("var outer = 1; { var inner = 2; } fun g(param) { var local; }");
+    let names: Vec<String> = interpreter.globals().into_iter().map(|(name, _)| name).collect();
+    assert!(names.contains(&"outer".to_string()));
+    for local in ["inner", "param", "local"] {
+        assert!(!names.contains(&local.to_string()), "{}", local);
+    }
+}
+
+// 运行不受信任的脚本时可以关闭环境变量访问；setenv 不修改进程环境
+#[test]
+fn env_access_can_be_disabled() {
+    let mut interpreter = Interpreter::new();
+    interpreter.capabilities.env = false;
+    let error = run_in(&mut interpreter, "print getenv(\"HOME\");").unwrap_err();
+    assert!(error.contains("getenv: environment access is disabled."), "{}", error);
+    let error = run_in(&mut interpreter, "setenv(\"A\", \"b\");").unwrap_err();
+    assert!(error.contains("setenv: environment access is disabled."), "{}", error);
+
+    let mut interpreter = Interpreter::new();
+    run_in(&mut interpreter, "setenv(\"LOX_API_TEST_VAR\", \"x\");").unwrap();
+    assert!(std::env::var("LOX_API_TEST_VAR").is_err());
+}
+
+#[test]
+fn exec_can_be_disabled() {
+    let mut interpreter = Interpreter::new();
+    interpreter.capabilities = Capabilities::none();
+    let error = run_in(&mut interpreter, "exec(\"echo hi\");").unwrap_err();
+    assert!(error.contains("exec: process execution is disabled."), "{}", error);
+}
+
+#[test]
+fn exec_timeout_kills_the_command() {
+    let mut interpreter = Interpreter::new();
+    interpreter.exec_timeout = Some(Duration::from_millis(100));
+    let start = Instant::now();
+    let error = run_in(&mut interpreter, "exec(\"sleep 5\");").unwrap_err();
+    assert!(error.contains("exec: command timed out after 100 ms."), "{}", error);
+    assert!(start.elapsed() < Duration::from_secs(4));
+    run_in(&mut interpreter, "exec(\"true\");").unwrap();
+}
+
+fn name(lexeme: &str) -> Token {
+    Token::new(TokenType::Identifier, lexeme.to_string(), None, 1)
+}
+
+// assign_at 和 get_at 一样只访问向上 depth 层的作用域，不沿环境链按名字查找
+#[test]
+fn assign_at_writes_only_the_given_depth() {
+    let global = Rc::new(Environment::new(None));
+    global.define("x".to_string(), Some(Value::Number(1.0)));
+    let outer = Rc::new(Environment::new(Some(Rc::clone(&global))));
+    outer.define("x".to_string(), Some(Value::Number(2.0)));
+    let inner = Environment::new(Some(Rc::clone(&outer)));
+
+    inner.assign_at(2, &name("x"), Some(Value::Number(10.0))).unwrap();
+    let value = |env: &Environment, depth| env.get_at(depth, &name("x")).unwrap().unwrap();
+    assert_eq!(value(&inner, 2).to_string(), "10");
+    assert_eq!(value(&inner, 1).to_string(), "2");
+
+    inner.assign_at(1, &name("x"), Some(Value::Number(20.0))).unwrap();
+    assert_eq!(value(&global, 0).to_string(), "10");
+    assert_eq!(value(&outer, 0).to_string(), "20");
+
+    for depth in [0, 3] {
+        let error = inner.assign_at(depth, &name("x"), None).unwrap_err();
+        assert!(error.to_string().contains("Undefined variable 'x'."), "{}", error);
+    }
+}
+
+// 换成固定时间后，时间函数的输出是确定的
+#[test]
+fn time_natives_use_the_time_source() {
+    let mut interpreter = Interpreter::new();
+    interpreter.time_source = Box::new(FixedTime {
+        epoch: 1_700_000_000.75,
+        offset: 2 * 3600,
+    });
+    run_in(
+        &mut interpreter,
+        "var now = clock();
+         var local = timeNow();
+         var utc = timeUtcNow();
+         var localHour = local[\"hour\"];
+         var utcHour = utc[\"hour\"];
+         var day = utc[\"day\"];
+         var formatted = timeFormat(0, \"%Y-%m-%d %H:%M:%S\");",
+    )
+    .unwrap();
+    let globals = interpreter.globals();
+    let global = |name: &str| {
+        let (_, value) = globals.iter().find(|(global, _)| global == name).unwrap();
+        value.to_string()
+    };
+    assert_eq!(global("now"), "1700000000.75");
+    assert_eq!(global("localHour"), "0");
+    assert_eq!(global("utcHour"), "22");
+    assert_eq!(global("day"), "14");
+    assert_eq!(global("formatted"), "1970-01-01 02:00:00");
+}
+
+#[test]
+fn time_format_errors() {
+    let mut interpreter = Interpreter::new();
+    let error = run_in(&mut interpreter, "timeFormat(0, \"%Q\");").unwrap_err();
+    assert!(error.contains("timeFormat: Unknown directive '%Q'."), "{}", error);
+    let format = natives::all().into_iter().find(|native| native.name == "timeFormat").unwrap();
+    let args = [Value::Number(f64::INFINITY), Value::from("%Y")];
+    let error = (format.func)(&mut interpreter, &args, 1).unwrap_err().to_string();
+    assert!(error.contains("timeFormat: argument 1 must be a finite number."), "{}", error);
+}
+
+// 共用同一个环境的解释器互相能看到对方的全局变量
+#[test]
+fn interpreters_can_share_a_global_environment() {
+    let shared = Rc::new(Environment::new(None));
+    shared.define_natives();
+    let mut first = Interpreter::with_env(Rc::clone(&shared));
+    let mut second = Interpreter::with_env(Rc::clone(&shared));
+    run_in(&mut first, "var counter = 1;").unwrap();
+    run_in(&mut second, "counter = counter + 1; var late = clock() >= 0;").unwrap();
+    run_in(&mut first, "counter = counter * 10;").unwrap();
+    let value = |global: &str| shared.get(&name(global)).unwrap().unwrap().to_string();
+    assert_eq!(value("counter"), "20");
+    assert_eq!(value("late"), "true");
+}
+
+// 用 child() 隔开：新的 var 定义在各自的子环境中，给已有的全局变量赋值仍写到共享环境
+#[test]
+fn child_environments_keep_definitions_apart() {
+    let shared = Rc::new(Environment::new(None));
+    shared.define_natives();
+    shared.define("total".to_string(), Some(Value::Number(0.0)));
+    let mut first = Interpreter::with_env(shared.child());
+    let mut second = Interpreter::with_env(shared.child());
+    run_in(&mut first, "var mine = 1; total = total + 1;").unwrap();
+    run_in(&mut second, "var mine = 2; total = total + 1;").unwrap();
+    let error = run_in(&mut second, "print undefinedHere;").unwrap_err();
+    assert!(error.contains("Undefined variable 'undefinedHere'."), "{}", error);
+    assert!(shared.get(&name("mine")).is_err());
+    assert_eq!(shared.get(&name("total")).unwrap().unwrap().to_string(), "2");
+    let mine = |interpreter: &Interpreter| {
+        let globals = interpreter.globals();
+        let (_, value) = globals.iter().find(|(global, _)| global == "mine").unwrap();
+        value.to_string()
+    };
+    assert_eq!((mine(&first), mine(&second)), ("1".to_string(), "2".to_string()));
+}
+
+// 宿主准备的环境不会自动加入内置函数
+#[test]
+fn with_env_does_not_add_natives() {
+    let mut interpreter = Interpreter::with_env(Rc::new(Environment::new(None)));
+    let error = run_in(&mut interpreter, "clock();").unwrap_err();
+    assert!(error.contains("Undefined variable 'clock'."), "{}", error);
+}
+
+// 三种错误都实现了 std::error::Error，可以用 ? 转成 Box<dyn Error>
+fn run_boxed(source: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut scanner = Scanner::new(source);
+    let (tokens, errors) = scanner.scan_tokens();
+    if let Some(error) = errors.first() {
+        return Err(error.clone().into());
+    }
+    let stmts = Parser::new(tokens)
+        .parse()
+        .map_err(|errors| errors.into_iter().next().unwrap())?;
+    Interpreter::new().interpret(lower::strip_groups_stmts(stmts))?;
+    Ok(())
+}
+
+#[test]
+fn errors_convert_to_boxed_errors() {
+    assert!(run_boxed("print 1;").is_ok());
+    for (source, message) in [
+        ("@", "[line 1] Error: Unexpected character: @"),
+        ("print ;", "[line 1] Error: Expect expression."),
+        ("print -\"a\";", "Invalid operand for unary operator"),
+    ] {
+        let error = run_boxed(source).unwrap_err().to_string();
+        assert!(error.contains(message), "{:?}: {}", source, error);
+    }
+}
+
+// 包含自己的列表和映射打印时，回到外层容器的引用写成 [...] 和 {...}
+#[test]
+fn self_referential_values_print() {
+    let list = Value::list(vec![Value::Number(1.0)]);
+    let Value::List(elements) = list.clone() else {
+        unreachable!()
+    };
+    elements.borrow_mut().push(list.clone());
+    assert_eq!(list.to_string(), "[1, [...]]");
+
+    let map = Value::map(vec![("size".to_string(), Value::Number(3.0))]);
+    let Value::Map(entries) = map.clone() else {
+        unreachable!()
+    };
+    entries.borrow_mut().push(("self".to_string(), map.clone()));
+    entries.borrow_mut().push(("list".to_string(), list.clone()));
+    assert_eq!(map.to_string(), "{\"size\": 3, \"self\": {...}, \"list\": [1, [...]]}");
+
+    // 同一个列表出现两次但不是循环时完整打印
+    let inner = Value::list(vec![Value::Number(2.0)]);
+    let outer = Value::list(vec![inner.clone(), inner]);
+    assert_eq!(outer.to_string(), "[[2], [2]]");
+}
+
+// 输出和诊断信息都在返回值里，全局变量在多次调用之间保留
+#[test]
+fn run_and_capture_collects_output_and_diagnostics() {
+    let mut interpreter = Interpreter::new();
+    let captured = interpreter.run_and_capture("var a = 20;\nprint a + 1;\nprintf(\"{}!\", a);");
+    assert_eq!(captured.output, "21\n20!");
+    assert!(captured.diagnostics.is_empty());
+    assert_eq!(captured.exit_code, 0);
+
+    let captured = interpreter.run_and_capture("print a;\nprint -\"x\";");
+    assert_eq!(captured.output, "20\n");
+    assert_eq!(captured.exit_code, 70);
+    assert_eq!(captured.diagnostics.len(), 1);
+    assert!(captured.diagnostics[0].starts_with("[line 2] Error: "), "{:?}", captured.diagnostics);
+
+    let captured = interpreter.run_and_capture("print ;\nprint @;");
+    assert_eq!((captured.output.as_str(), captured.exit_code), ("", 65));
+    assert_eq!(captured.diagnostics, vec!["[line 2] Error: Unexpected character: @"]);
+    let captured = interpreter.run_and_capture("print ;");
+    assert_eq!(captured.diagnostics, vec!["[line 1] Error: Expect expression."]);
+}
+
+// 警告也在诊断信息中，程序照常执行
+#[test]
+fn run_and_capture_reports_warnings() {
+    let captured = Interpreter::new().run_and_capture("fun f() { return 1; print 2; }\nprint f();");
+    assert_eq!((captured.output.as_str(), captured.exit_code), ("1\n", 0));
+    assert_eq!(captured.diagnostics, vec!["[line 1] Warning: Unreachable code."]);
+}
+
+// 每层作用域的变量按名字排序输出，depth 0 是当前作用域；过长的值截断
+#[test]
+fn environments_print_sorted_by_depth() {
+    let globals = Rc::new(Environment::new(None));
+    globals.define("zeta".to_string(), Some(Value::Number(1.0)));
+    globals.define("alpha".to_string(), None);
+    let local = globals.child();
+    local.define("b".to_string(), Some(Value::Bool(true)));
+    local.define("a".to_string(), Some(Value::Nil));
+    assert_eq!(
+        local.to_string(),
+        "Environment (depth 0)\n  a = nil\n  b = true\n\
+         Environment (depth 1)\n  alpha = <uninitialized>\n  zeta = 1\n"
+    );
+    assert_eq!(format!("{:?}", local), local.to_string());
+
+    let env = Environment::new(None);
+    env.define("long".to_string(), Some(Value::list(vec![Value::Number(1.0); 40])));
+    let expected = format!("Environment (depth 0)\n  long = [{}1,...\n", "1, ".repeat(19));
+    assert_eq!(env.to_string(), expected);
+}
+
+// 闭包的环境包含函数自己，输出函数时不会循环
+#[test]
+fn environments_with_closures_print() {
+    let interpreter = interpret("fun f() { return 1; }");
+    let text = interpreter.env.to_string();
+    assert!(text.contains("  f = <fn f>\n"), "{}", text);
+}
+
+// 脚本运行后按名字调用其中定义的函数、内置函数或类
+#[test]
+fn call_function_from_rust() {
+    let mut interpreter = interpret(
+        "fun transform(x, y) { return x * 10 + y; }
+         class Point { init(x) { this.x = x; } }
+         var notAFunction = 1;",
+    );
+    let value = interpreter
+        .call_function("transform", vec![Value::Number(4.0), Value::Number(2.0)])
+        .unwrap();
+    assert_eq!(value.to_string(), "42");
+    let value = interpreter.call_function("Point", vec![Value::Number(1.0)]).unwrap();
+    assert_eq!(value.to_string(), "Point instance");
+    let value = interpreter.call_function("round", vec![Value::Number(2.5)]).unwrap();
+    assert_eq!(value.to_string(), "3");
+
+    let error = interpreter.call_function("transform", vec![]).unwrap_err().to_string();
+    assert!(error.contains("Expected 2 arguments but got 0."), "{}", error);
+    let error = interpreter.call_function("notAFunction", vec![]).unwrap_err().to_string();
+    assert!(error.contains("'notAFunction' is not a function."), "{}", error);
+    let error = interpreter.call_function("missing", vec![]).unwrap_err().to_string();
+    assert!(error.contains("Undefined variable 'missing'."), "{}", error);
+}
+
+// 记录写入和 flush 的顺序，"|" 表示一次 flush
+struct Recorder(Rc<std::cell::RefCell<String>>);
+
+impl std::io::Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().push_str(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.borrow_mut().push('|');
+        Ok(())
+    }
+}
+
+// print 和 printf 每次输出后都 flush，flush() 可以显式调用
+#[test]
+fn output_is_flushed_after_each_print() {
+    let log = Rc::new(std::cell::RefCell::new(String::new()));
+    let mut interpreter = Interpreter::new();
+    interpreter.output = Box::new(Recorder(Rc::clone(&log)));
+    run_in(&mut interpreter, "print 1;\nprintf(\"{}\", 2);\nflush();\nprint 3;").unwrap();
+    assert_eq!(log.borrow().as_str(), "1\n|2||3\n|");
+}
+
+// 打开驻留后内容相同的字面量和拼接结果共用一份存储；默认每次求值各自分配
+#[test]
+fn interned_strings_share_storage() {
+    let source = "var a = \"abc\";\nvar b = \"abc\";\nvar c = \"ab\" + \"c\";\nvar d = a;";
+    let strings = |interpreter: &Interpreter| {
+        ["a", "b", "c", "d"].map(|global| match interpreter.env.get(&name(global)) {
+            Ok(Some(Value::String(s))) => s,
+            other => panic!("{} should be a string: {:?}", global, other),
+        })
+    };
+    let mut interpreter = Interpreter::new();
+    interpreter.strings = Some(Default::default());
+    run_in(&mut interpreter, source).unwrap();
+    let [a, b, c, d] = strings(&interpreter);
+    assert!(Rc::ptr_eq(&a, &b) && Rc::ptr_eq(&a, &c) && Rc::ptr_eq(&a, &d));
+
+    let [a, b, c, d] = strings(&interpret(source));
+    assert_eq!((a.as_str(), b.as_str(), c.as_str()), ("abc", "abc", "abc"));
+    assert!(!Rc::ptr_eq(&a, &b) && !Rc::ptr_eq(&a, &c));
+    // 赋值只复制引用
+    assert!(Rc::ptr_eq(&a, &d));
+}
@@ -0,0 +1,165 @@
+// 测量用的测试：输出耗时，只断言结果正确，不对时间做断言。
+// 查看数字：cargo test --release --test bench -- --nocapture
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+use codecrafters_interpreter::{
+    interpreter::Interpreter,
+    parser::{lower, Parser},
+    resolver::Resolver,
+    scanner::Scanner,
+};
+
+// 统计当前线程的内存分配次数；测试并行运行时各自计数，互不影响
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+// 在几层嵌套作用域中循环调用内置函数：解析过的变量直接跳到全局环境，
+// 没有经过 Resolver 的语法树每次调用都沿环境链逐层按名字查找
+#[test]
+fn native_calls_in_loop() {
+    let source = "
+        fun run() {
+            var n = 0;
+            { { { {
+                for (var i = 0; i < 20000; i = i + 1) {
+                    if (clock() >= 0) n = n + 1;
+                }
+            } } } }
+            return n;
+        }
+        var count = run();";
+    let interpret = |resolve: bool| {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_tokens();
+        let stmts = Parser::new(tokens).parse().expect("source should parse");
+        let mut stmts = lower::strip_groups_stmts(stmts);
+        if resolve {
+            Resolver::new().resolve(&mut stmts);
+        }
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(stmts).expect("source should run");
+        let globals = interpreter.globals();
+        globals.into_iter().find(|(name, _)| name == "count").map(|(_, value)| value.to_string())
+    };
+    let (resolved, resolved_time) = time(|| interpret(true));
+    let (unresolved, unresolved_time) = time(|| interpret(false));
+    assert_eq!(resolved, Some("20000".to_string()));
+    assert_eq!(unresolved, Some("20000".to_string()));
+    println!(
+        "20000 native calls: resolved {:?}, by name {:?} ({:.2}x)",
+        resolved_time,
+        unresolved_time,
+        unresolved_time.as_secs_f64() / resolved_time.as_secs_f64()
+    );
+}
+
+// scan_kinds 不为每个 token 分配 lexeme 和 literal，分配次数只和结果数组扩容有关
+#[test]
+fn scan_kinds_allocations() {
+    let source = "var name = \"value\" + 12.5 * count; // comment\nprint name;\n".repeat(5000);
+    let (tokens, token_allocations) = allocations(|| {
+        let mut scanner = Scanner::new(&source);
+        scanner.scan_tokens().0.len()
+    });
+    let ((kinds, kind_allocations), kinds_time) =
+        time(|| allocations(|| Scanner::new(&source).scan_kinds().len()));
+    let (_, tokens_time) = time(|| Scanner::new(&source).scan_tokens().0.len());
+    assert_eq!(tokens, kinds);
+    assert!(kind_allocations * 100 < token_allocations);
+    println!(
+        "{} tokens: scan_tokens {} allocations in {:?}, scan_kinds {} allocations in {:?}",
+        tokens, token_allocations, tokens_time, kind_allocations, kinds_time
+    );
+}
+
+fn concatenate(pieces: usize, in_place: bool) -> usize {
+    let source = format!(
+        "var s = \"\"; for (var i = 0; i < {}; i = i + 1) s = s + \"ab\"; print s;",
+        pieces
+    );
+    let mut interpreter = Interpreter::new();
+    // 打开类型检查时赋值走一般的路径，每次拼接都复制整个字符串
+    interpreter.check_types = !in_place;
+    let captured = interpreter.run_and_capture(&source);
+    assert_eq!(captured.exit_code, 0);
+    captured.output.len()
+}
+
+// s = s + piece 在没有其他引用时原地追加，总耗时随片段数线性增长；
+// 每次复制整个字符串时是平方增长，只用较少的片段比较
+#[test]
+fn string_append_loop() {
+    let (length, large) = time(|| concatenate(100_000, true));
+    assert_eq!(length, 200_001);
+    let (_, in_place) = time(|| concatenate(20_000, true));
+    let (length, copying) = time(|| concatenate(20_000, false));
+    assert_eq!(length, 40_001);
+    println!(
+        "100000 pieces in place: {:?}; 20000 pieces in place: {:?}, copying: {:?}",
+        large, in_place, copying
+    );
+}
+
+// 存活对象计数的开销：创建和释放大量列表、映射和闭包的脚本，
+// 和关闭 stats 特性时比较：cargo test --release --no-default-features --test bench -- --nocapture
+#[test]
+fn live_object_counting() {
+    let source = "
+        fun make(n) { return fun() { return n; }; }
+        var total = 0;
+        for (var i = 0; i < 100000; i = i + 1) {
+            var list = [i, [i]];
+            var map = jsonParse(\"{}\");
+            total = total + make(list[0])();
+        }
+        print total;";
+    let before = codecrafters_interpreter::stats::current();
+    let (captured, elapsed) = time(|| Interpreter::new().run_and_capture(source));
+    assert_eq!(captured.output, "4999950000\n");
+    // 循环中创建的对象都已释放；只剩下全局环境和引用它的 make 形成的循环
+    let after = codecrafters_interpreter::stats::current();
+    assert!(after.lists == before.lists && after.maps == before.maps, "{:?}", after);
+    assert!(after.environments <= before.environments + 1, "{:?}", after);
+    assert!(after.functions <= before.functions + 1, "{:?}", after);
+    println!(
+        "100000 iterations with counting {}: {:?}",
+        if cfg!(feature = "stats") { "on" } else { "off" },
+        elapsed
+    );
+}
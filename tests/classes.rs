@@ -0,0 +1,139 @@
+mod common;
+
+use common::{output, run};
+
+fn assert_error(source: &str, code: i32, message: &str) {
+    let result = run(source);
+    assert_eq!(result.code, code, "{:?}", source);
+    assert!(result.stderr.contains(message), "{:?}: {}", source, result.stderr);
+}
+
+#[test]
+fn methods_fields_and_inheritance() {
+    assert_eq!(
+        output(
+            "class Point {
+                 init(x, y) { this.x = x; this.y = y; }
+                 sum() { return this.x + this.y; }
+             }
+             var p = Point(1, 2);
+             print p.sum();
+             var method = p.sum;
+             p.x = 10;
+             print method();
+             print p;
+             print Point;
+             class A { hi() { return \"A\"; } }
+             class B < A { hi() { return \"B\" + super.hi(); } }
+             print B().hi();"
+        ),
+        "3\n12\nPoint instance\nPoint\nBA\n"
+    );
+}
+
+// fields() 按第一次赋值的顺序列出字段，重新赋值不改变顺序
+#[test]
+fn fields_keep_insertion_order() {
+    assert_eq!(
+        output(
+            "class P { init(x, y) { this.y = y; this.x = x; } }
+             var p = P(1, 2);
+             p.z = 3;
+             p.y = 4;
+             print fields(p);
+             print fields(P(5, 6));"
+        ),
+        "[\"y\", \"x\", \"z\"]\n[\"y\", \"x\"]\n"
+    );
+}
+
+#[test]
+fn class_errors() {
+    assert_error(
+        "class C {}\nprint C().missing;",
+        70,
+        "[line 2] Error: Undefined property 'missing'.",
+    );
+    assert_error("fields(1);", 70, "[line 1] Error: fields: argument 1 must be an instance.");
+    assert_error("var x = 1; class B < x {}", 70, "Error: Superclass must be a class.");
+    assert_error("print this;", 65, "Error: Can't use 'this' outside of a class.");
+    assert_error("class A < A {}", 65, "Error: A class can't inherit from itself.");
+    assert_error(
+        "class A { init() { return 1; } }",
+        65,
+        "Error: Can't return a value from an initializer.",
+    );
+}
+
+// ?. 遇到 nil 时整条属性链的结果是 nil，后面的调用和实参都不再求值
+#[test]
+fn safe_navigation_short_circuits_the_chain() {
+    assert_eq!(
+        output(
+            "class Node { init(next) { this.next = next; } }
+             var list = Node(Node(nil));
+             print list?.next?.next;
+             print list.next?.next?.next.missing;
+             var calls = 0;
+             fun f() { calls = calls + 1; return 1; }
+             var none = nil;
+             print none?.method(f()).more;
+             print calls;
+             print list?.next == list.next;"
+        ),
+        "nil\nnil\nnil\n0\ntrue\n"
+    );
+    assert_error(
+        "var none = nil;\nprint none.field;",
+        70,
+        "[line 2] Error: Only instances have properties.",
+    );
+    assert_error("print 1?.field;", 70, "[line 1] Error: Only instances have properties.");
+}
+
+// 字段默认值在 init 之前设置，每个实例各自求值一次
+#[test]
+fn field_defaults() {
+    assert_eq!(
+        output(
+            "var made = 0;
+             fun next() { made = made + 1; return made; }
+             class Point {
+               var x = 0;
+               var y;
+               var id = next();
+               init(x) { print this.x; this.x = x; }
+             }
+             var p = Point(3);
+             var q = Point(4);
+             print p.x; print p.y; print p.id; print q.id;"
+        ),
+        "0\n0\n3\nnil\n1\n2\n"
+    );
+}
+
+// 父类的字段先设置，子类同名字段覆盖默认值
+#[test]
+fn subclass_fields_override_superclass_defaults() {
+    assert_eq!(
+        output(
+            "class A { var name = \"a\"; var kind = \"base\"; }
+             class B < A { var name = \"b\"; describe() { return this.name + \" \" + this.kind; } }
+             print B().describe();
+             print A().name;"
+        ),
+        "b base\na\n"
+    );
+}
+
+#[test]
+fn field_declaration_errors() {
+    assert_error("class A { var x; var x; }", 65, "Field 'x' is already declared in this class.");
+    assert_error("class A { var f; f() {} }", 65, "A field and a method can't both be named 'f'.");
+    assert_error("class A { f() {} var f; }", 65, "A field and a method can't both be named 'f'.");
+    assert_error("class A { var x = 1 }", 65, "Expect ';' after field declaration.");
+    assert_error("class A { var; }", 65, "Expect field name.");
+    // 默认值不能使用 this
+    assert_error("class A { var x = this; }", 65, "Can't use 'this' outside of a class.");
+    assert_error("class A { var x = nope; }\nA();", 70, "Undefined variable 'nope'.");
+}
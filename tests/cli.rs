@@ -0,0 +1,522 @@
+// 通过命令行运行解释器，检查标准输出、标准错误和退出码
+mod common;
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use common::lox;
+
+// run 模式下表达式语句只求值，不输出结果（和 REPL 回显不同）
+#[test]
+fn run_does_not_print_expression_statements() {
+    let result = lox(
+        "run",
+        "clock(); print \"done\";
+         1 + 1;
+         var n = 0;
+         fun f() { n = n + 1; return n; }
+         f();
+         f();
+         print n;",
+        &[],
+    );
+    // 表达式语句的副作用仍然发生
+    assert_eq!(result.stdout, "done\n2\n");
+    assert_eq!(result.code, 0);
+}
+
+// run --coverage <file>：执行结束后写入覆盖率报告，运行时出错也会写
+#[test]
+fn coverage_report_marks_uncovered_lines() {
+    let report = std::env::temp_dir().join(format!("lox-coverage-{}.txt", std::process::id()));
+    let source = "var a = 1;
+if (a > 2) {
+  print \"big\";
+}
+// comment
+for (var i = 0; i < 2; i = i + 1) print i;
+print nope;
+";
+    let result = lox("run", source, &["--coverage", report.to_str().unwrap()]);
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "0\n1\n");
+    let text = std::fs::read_to_string(&report).expect("report should be written");
+    let _ = std::fs::remove_file(&report);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[..3], ["Coverage: 4/5 lines (80.0%)", "Uncovered lines: 3", ""]);
+    // 左侧是执行次数，未覆盖的行是 #####，不可执行的行是 -
+    let counts: Vec<&str> = lines[3..]
+        .iter()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+    assert_eq!(counts[..5], ["1", "3", "#####", "-", "-"]);
+    assert_ne!(counts[5], "#####");
+    assert_eq!(lines[5], "  #####     3 |   print \"big\";");
+}
+
+#[test]
+fn coverage_needs_a_file_name() {
+    let result = lox("run", "print 1;", &["--coverage"]);
+    assert_eq!(result.code, 64);
+    assert!(result.stderr.contains("Missing value for --coverage"), "{}", result.stderr);
+}
+
+// 测试使用 debug 构建，其中有故意 panic 的 __panic()
+#[test]
+fn internal_panic_names_the_script_line() {
+    let result = lox("run", "print 1;\nprint 2;\n__panic();\nprint 3;", &[]);
+    assert_eq!(result.code, 101);
+    assert_eq!(result.stdout, "1\n2\n");
+    assert!(
+        result.stderr.contains("internal error while executing line 3 of "),
+        "{}",
+        result.stderr
+    );
+    assert!(result.stderr.contains("__panic() called"), "{}", result.stderr);
+}
+
+// repl 从标准输入逐行读取；不是终端时不输出提示符
+fn repl(input: &str) -> common::Run {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    common::Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        code: output.status.code().unwrap_or(-1),
+    }
+}
+
+#[test]
+fn repl_keeps_going_after_errors() {
+    let input = "var a = 1;\na + 1;\nprint a\nprint b;\n\"x\" - 1;\na = a + 1;\nprint a;\n@\n";
+    let result = repl(input);
+    assert_eq!(result.code, 0);
+    // 只有一个表达式语句的行输出它的值；全局变量在各行之间保留
+    assert_eq!(result.stdout, "2\n2\n2\n");
+    assert_eq!(
+        result.stderr,
+        "[line 2] Error: Expect ';' after value.\n\
+         [line 1] Error: Undefined variable 'b'.\n\
+         [line 1] Error: Operands must be numbers.\n\
+         [line 1] Error: Unexpected character: @\n"
+    );
+}
+
+// 不经过源文件，直接用给定的命令行参数运行
+fn interpreter(args: &[&str]) -> common::Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .args(args)
+        .output()
+        .expect("interpreter should start");
+    common::Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        code: output.status.code().unwrap_or(-1),
+    }
+}
+
+// explain 的参数是表达式源码而不是文件名
+fn explain(expression: &str) -> common::Run {
+    interpreter(&["explain", expression])
+}
+
+#[test]
+fn explain_lists_steps_in_evaluation_order() {
+    let result = explain("(1 + 2) * 3");
+    assert_eq!(result.code, 0);
+    assert_eq!(
+        result.stdout,
+        "(* (group (+ 1.0 2.0)) 3.0)\n      1 => 1\n      2 => 2\n    1 + 2 => 3\n    3 => 3\n  \
+         (1 + 2) * 3 => 9\nResult: 9\n"
+    );
+    // 短路求值中没有计算的部分不出现
+    let result = explain("false and 1 / 0");
+    assert_eq!(
+        result.stdout,
+        "(and false (/ 1.0 0.0))\n    false => false\n  false and 1 / 0 => false\nResult: false\n"
+    );
+}
+
+#[test]
+fn explain_errors() {
+    let result = explain("\"a\" - 1");
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "(- a 1.0)\n    \"a\" => a\n    1 => 1\n");
+    assert!(result.stderr.contains("Operands must be numbers."), "{}", result.stderr);
+    let result = explain("1 +");
+    assert_eq!(result.code, 65);
+    assert_eq!(result.stdout, "");
+}
+
+// 依次运行几个文件，返回结果后删除文件
+fn run_files(sources: &[&str], flags: &[&str]) -> common::Run {
+    let paths: Vec<_> = sources.iter().map(|source| common::source_file(source)).collect();
+    let mut args: Vec<&str> = vec!["run"];
+    args.extend(paths.iter().map(|path| path.to_str().unwrap()));
+    args.extend(flags);
+    let result = interpreter(&args);
+    for path in paths {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+#[test]
+fn files_share_one_global_environment() {
+    let result = run_files(
+        &["fun twice(n) { return n * 2; }\nvar base = 20;", "print twice(base) + 2;"],
+        &[],
+    );
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, "42\n");
+}
+
+#[test]
+fn errors_in_any_file_stop_all_of_them() {
+    // 先检查全部文件：第二个文件有语法错误时第一个也不执行。有多个文件时错误前总是文件名
+    let result = run_files(&["print 1;", "print ;"], &[]);
+    assert_eq!(result.code, 65);
+    assert_eq!(result.stdout, "");
+    let expected = ".lox:[line 1] Error: Expect expression.";
+    assert!(result.stderr.contains(expected), "{}", result.stderr);
+    // 运行时错误时前面文件的输出保留，之后的文件不执行
+    let result = run_files(&["print 1;", "print -\"a\";", "print 3;"], &[]);
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "1\n");
+    let expected = ".lox:[line 1] Error: Invalid operand for unary operator";
+    assert!(result.stderr.contains(expected), "{}", result.stderr);
+}
+
+#[test]
+fn coverage_needs_a_single_file() {
+    let result = run_files(&["print 1;", "print 2;"], &["--coverage", "report.txt"]);
+    assert_eq!(result.code, 64);
+    assert_eq!(result.stderr, "--coverage can only be used with a single file\n");
+}
+
+// -e 和 run 一样检查、执行程序，退出码相同，后面可以跟 run 的选项
+#[test]
+fn inline_program() {
+    let result = interpreter(&["-e", "var a = 2;\nprint a * 3;"]);
+    assert_eq!((result.stdout.as_str(), result.code), ("6\n", 0));
+    let result = interpreter(&["-e", "print nope;"]);
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("Undefined variable 'nope'."), "{}", result.stderr);
+    let result = interpreter(&["-e", "print ;"]);
+    assert_eq!(result.code, 65);
+    let result = interpreter(&["-e", "var x: number = \"s\";", "--check-types"]);
+    assert_eq!(result.code, 70);
+    let result = interpreter(&["-e"]);
+    assert_eq!(result.code, 64);
+    assert!(result.stderr.contains("Missing value for -e"), "{}", result.stderr);
+}
+
+// 和 .codecrafters/run.sh 一样只传命令行参数：诊断信息使用 CodeCrafters 的格式，不带名字，也没有源码片段
+#[test]
+fn errors_use_the_codecrafters_format_by_default() {
+    let path = common::source_file("print 1;\nprint -\"a\";\n@");
+    let name = path.to_str().unwrap();
+    let run_sh = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+            .args(args)
+            .env_remove("LOX_PREFIX_ERRORS")
+            .output()
+            .expect("interpreter should start");
+        (String::from_utf8_lossy(&output.stderr).into_owned(), output.status.code())
+    };
+    assert_eq!(
+        run_sh(&["tokenize", name]),
+        ("[line 3] Error: Unexpected character: @\n".to_string(), Some(65))
+    );
+    let _ = std::fs::write(&path, "print 1;\nprint -\"a\";");
+    assert_eq!(
+        run_sh(&["run", name]),
+        ("[line 2] Error: Invalid operand for unary operator\n".to_string(), Some(70))
+    );
+    let _ = std::fs::remove_file(path);
+    assert_eq!(run_sh(&["-e", "print ;"]).0, "[line 1] Error: Expect expression.\n");
+}
+
+// --prefix-errors 或 LOX_PREFIX_ERRORS 时以来源的名字开头：文件路径、-e 的 <command-line>，
+// 带列号的错误后面显示源码行
+#[test]
+fn prefix_errors_start_with_the_source_name() {
+    let path = common::source_file("print 1;\nprint -\"a\";");
+    let name = path.to_str().unwrap();
+    let result = interpreter(&["run", name, "--prefix-errors"]);
+    assert_eq!(result.code, 70);
+    assert_eq!(
+        result.stderr,
+        format!(
+            "{}:[line 2] Error: Invalid operand for unary operator\n    print -\"a\";\n{}^\n",
+            name,
+            " ".repeat(10)
+        )
+    );
+    let result = interpreter(&["tokenize", name, "--prefix-errors"]);
+    assert_eq!(result.code, 0);
+    let _ = std::fs::remove_file(path);
+    let result = interpreter(&["-e", "print ;", "--prefix-errors"]);
+    assert_eq!(result.stderr, "<command-line>:[line 1] Error: Expect expression.\n");
+    // --plain-errors 和默认相同，同时给出时以它为准
+    let result = interpreter(&["-e", "print ;", "--prefix-errors", "--plain-errors"]);
+    assert_eq!(result.stderr, "[line 1] Error: Expect expression.\n");
+    let prefixed = |value: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+            .args(["-e", "print ;"])
+            .env("LOX_PREFIX_ERRORS", value)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stderr).starts_with("<command-line>:")
+    };
+    assert!(prefixed("1"));
+    assert!(!prefixed("0"));
+    assert!(!prefixed(""));
+}
+
+// 空文件、只有空白的文件和只有注释的文件：最后一行的行号
+const EMPTY_SOURCES: [(&str, &str, usize); 3] = [
+    ("empty", "", 1),
+    ("whitespace", "  \n\t\n", 3),
+    ("comments", "// only a comment\n// another", 2),
+];
+
+#[test]
+fn empty_sources_tokenize_to_eof() {
+    for (name, source, _) in EMPTY_SOURCES {
+        let result = lox("tokenize", source, &[]);
+        assert_eq!(result.stdout, "EOF  null\n", "{}", name);
+        assert_eq!(result.code, 0, "{}", name);
+    }
+}
+
+#[test]
+fn empty_sources_parse_and_evaluate_report_end_of_input() {
+    for command in ["parse", "evaluate"] {
+        for (name, source, line) in EMPTY_SOURCES {
+            let result = lox(command, source, &[]);
+            assert_eq!(result.stdout, "", "{} {}", command, name);
+            assert_eq!(result.code, 65, "{} {}", command, name);
+            let message = format!(
+                "[line {}] Error: Expected an expression but found end of input.",
+                line
+            );
+            assert!(result.stderr.contains(&message), "{} {}", command, name);
+        }
+    }
+}
+
+#[test]
+fn empty_sources_run_without_output() {
+    for (name, source, _) in EMPTY_SOURCES {
+        let result = lox("run", source, &[]);
+        assert_eq!(result.stdout, "", "{}", name);
+        assert_eq!(result.stderr, "", "{}", name);
+        assert_eq!(result.code, 0, "{}", name);
+    }
+}
+
+// --max-memory-kb 按累计分配的字节数限制，已经释放的部分不扣除
+#[test]
+fn memory_limit() {
+    let doubling = "var s = \"x\";\nfor (var i = 0; i < 40; i = i + 1) s = s + s;\nprint \"done\";";
+    let result = lox("run", doubling, &["--max-memory-kb", "64"]);
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "");
+    assert!(result.stderr.contains("[line 2] Error: Memory limit exceeded."), "{}", result.stderr);
+    let small = "fun f(n) { return n; }\nfor (var i = 0; i < 10; i = i + 1) f(i);\nprint \"done\";";
+    let result = lox("run", small, &["--max-memory-kb", "64"]);
+    assert_eq!((result.stdout.as_str(), result.code), ("done\n", 0));
+    // 调用和循环不断新建环境，总量超过预算也会报错
+    let calls = "fun f(n) { return n; }\nfor (var i = 0; i < 100000; i = i + 1) f(i);";
+    let result = lox("run", calls, &["--max-memory-kb", "64"]);
+    assert_eq!(result.code, 70);
+    let result = lox("run", "print 1;", &["--max-memory-kb", "lots"]);
+    assert_eq!(result.code, 64);
+    assert_eq!(result.stderr, "Invalid value for --max-memory-kb: lots\n");
+}
+
+// 仓库自带的 conformance 目录全部通过，skip.txt 里的测试计为跳过
+#[test]
+fn conformance_directory_passes() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/conformance");
+    let skip = concat!(env!("CARGO_MANIFEST_DIR"), "/conformance/skip.txt");
+    let result = interpreter(&["conformance", dir, "--skip", skip]);
+    assert_eq!(result.code, 0, "{}", result.stdout);
+    assert!(result.stdout.ends_with(", failed: 0, skipped: 2.\n"), "{}", result.stdout);
+    // 不带跳过列表时有意不同的两个测试失败
+    let result = interpreter(&["conformance", dir]);
+    assert_eq!(result.code, 1);
+    assert!(result.stdout.contains("FAIL operator/divide_by_zero.lox\n"), "{}", result.stdout);
+    assert!(result.stdout.contains("FAIL unexpected_character.lox\n"), "{}", result.stdout);
+    assert!(result.stdout.ends_with(", failed: 2, skipped: 0.\n"), "{}", result.stdout);
+    let result = interpreter(&["conformance", dir, "--skip", "missing-skip.txt"]);
+    assert_eq!(result.code, 64);
+    assert!(result.stderr.starts_with("Failed to read skip list: "), "{}", result.stderr);
+}
+
+#[test]
+fn evaluate_rejects_trailing_tokens() {
+    for cmd in ["parse", "evaluate"] {
+        let result = lox(cmd, "1 + 1;\n2 + 2;", &[]);
+        assert_eq!(result.code, 65, "{}", cmd);
+        assert_eq!(result.stdout, "");
+        assert!(
+            result.stderr.contains("[line 1] Error: Unexpected tokens after expression."),
+            "{}",
+            result.stderr
+        );
+    }
+}
+
+#[test]
+fn bench_reports_timings() {
+    let result = lox("bench", "print 1 + 2;", &["--runs", "3", "--warmup", "0"]);
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    // 程序本身的输出被丢弃，只有一行统计
+    assert!(result.stdout.starts_with("runs: 3, min: "), "{}", result.stdout);
+    assert!(result.stdout.contains(" ms, median: "), "{}", result.stdout);
+    assert!(result.stdout.ends_with(" ms\n"), "{}", result.stdout);
+    assert_eq!(result.stdout.lines().count(), 1);
+    // --runs 0 按 1 次计算
+    let result = lox("bench", "print 1;", &["--runs", "0"]);
+    assert!(result.stdout.starts_with("runs: 1, "), "{}", result.stdout);
+}
+
+#[test]
+fn bench_stops_on_errors() {
+    let result = lox("bench", "print nope;", &[]);
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "");
+    assert_eq!(result.stderr, "[line 1] Error: Undefined variable 'nope'.\n");
+    let result = lox("bench", "print ;", &[]);
+    assert_eq!(result.code, 65);
+    let result = lox("bench", "print 1;", &["--runs", "many"]);
+    assert_eq!(result.code, 64);
+    assert_eq!(result.stderr, "Invalid value for --runs: many\n");
+}
+
+// 输出超过 --max-output-kb 时报运行时错误；已经写出的内容保留，且不超过上限
+#[test]
+fn output_limit() {
+    let result = lox("run", "while (true) print \"0123456789\";", &["--max-output-kb", "1"]);
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("[line 1] Error: Output limit exceeded."), "{}", result.stderr);
+    // 每行 11 个字节，1024 字节以内最多 93 行
+    assert_eq!(result.stdout, "0123456789\n".repeat(93));
+    // printf 计入同一个预算
+    let result = lox("run", "loop { printf(\"{}\", 12345678); }", &["--max-output-kb", "1"]);
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "12345678".repeat(128));
+    let result = lox("run", "print 1;", &["--max-output-kb", "-1"]);
+    assert_eq!(result.code, 64);
+    assert_eq!(result.stderr, "Invalid value for --max-output-kb: -1\n");
+}
+
+// tokenize --json 的输出交给 parse --from-tokens，结果和直接 parse 相同
+#[test]
+fn parse_from_tokens() {
+    let source = "(1.50 + \"a\") * -x";
+    let tokens = lox("tokenize", source, &["--json"]);
+    assert_eq!(tokens.code, 0);
+    assert!(
+        tokens.stdout.starts_with(
+            "[{\"type\":\"LEFT_PAREN\",\"lexeme\":\"(\",\"literal\":null,\"line\":1,\"column\":1,\
+             \"offset\":0},{\"type\":\"NUMBER\",\"lexeme\":\"1.50\",\"literal\":\"1.5\","
+        ),
+        "{}",
+        tokens.stdout
+    );
+    let path = common::source_file(&tokens.stdout);
+    let result = interpreter(&["parse", "--from-tokens", &path.to_string_lossy()]);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, lox("parse", source, &[]).stdout);
+
+    let path = common::source_file("[]");
+    let result = interpreter(&["parse", "--from-tokens", &path.to_string_lossy()]);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(result.code, 65);
+    assert!(
+        result.stderr.contains("Token stream must end with an EOF token."),
+        "{}",
+        result.stderr
+    );
+    let result = interpreter(&["parse", "--from-tokens"]);
+    assert_eq!((result.code, result.stderr.as_str()), (64, "Missing value for --from-tokens\n"));
+}
+
+// 超出规模限制时以 66 退出，run --json 的 status 是 limit_exceeded
+#[test]
+fn source_limits() {
+    let source = "print 1 + 2 + 3;";
+    let result = lox("run", source, &["--max-tokens", "3"]);
+    assert_eq!((result.code, result.stdout.as_str()), (66, ""));
+    assert_eq!(result.stderr, "[line 1] Error: Too many tokens: limit is 3.\n");
+    let result = lox("check", source, &["--max-nodes", "3"]);
+    assert_eq!(result.code, 66);
+    assert_eq!(result.stderr, "[line 1] Error: Too many syntax tree nodes: limit is 3.\n");
+    let result = lox("run", source, &["--max-nodes", "4"]);
+    assert_eq!((result.code, result.stdout.as_str()), (0, "6\n"));
+    let result = lox("run", source, &["--max-source-kb", "0"]);
+    assert_eq!(result.code, 66);
+    assert!(
+        result.stderr.ends_with(": Source too large: limit is 0 bytes.\n"),
+        "{}",
+        result.stderr
+    );
+    let result = lox("run", source, &["--max-tokens", "3", "--json"]);
+    assert!(result.stderr.contains("\"status\":\"limit_exceeded\""), "{}", result.stderr);
+    assert!(result.stderr.contains("\"code\":\"E004-limit-exceeded\""), "{}", result.stderr);
+    let result = lox("run", source, &["--max-tokens", "many"]);
+    assert_eq!(result.code, 64);
+    assert_eq!(result.stderr, "Invalid value for --max-tokens: many\n");
+}
+
+// --json 在结束时向 stderr 输出一个 JSON 对象，stdout 只有程序自己的输出
+#[test]
+fn json_report() {
+    let source = "print 1;\nfun f() { return 1; print 2; }\nprint -\"x\";";
+    let result = lox("run", source, &["--json"]);
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "1\n");
+    assert_eq!(result.stderr.lines().count(), 1, "{}", result.stderr);
+    assert!(result.stderr.starts_with("{\"status\":\"runtime_error\",\"errors\":[{\"file\":\""));
+    // 没有记录列号时 column 是 0
+    let expected = "\"line\":3,\"column\":7,\"message\":\"Invalid operand for unary operator\",\
+                    \"code\":\"E003-runtime-error\"}],\"warnings\":[{\"file\":\"";
+    assert!(result.stderr.contains(expected), "{}", result.stderr);
+    let expected = "\"line\":2,\"column\":0,\"message\":\"Unreachable code.\",\
+                    \"code\":\"W001-unreachable-code\"}]}\n";
+    assert!(result.stderr.ends_with(expected), "{}", result.stderr);
+
+    let result = lox("run", "print ;", &["--json"]);
+    assert_eq!(result.code, 65);
+    assert!(result.stderr.starts_with("{\"status\":\"parse_error\","), "{}", result.stderr);
+    let result = lox("run", "print @;", &["--json"]);
+    assert!(result.stderr.starts_with("{\"status\":\"scan_error\","), "{}", result.stderr);
+    let result = lox("run", "print 1;", &["--json"]);
+    assert_eq!(result.stderr, "{\"status\":\"ok\",\"errors\":[],\"warnings\":[]}\n");
+}
+
+#[test]
+fn quiet_and_stats() {
+    let result = lox("run", "print 1;\nprint -\"x\";", &["--quiet"]);
+    assert_eq!((result.stdout.as_str(), result.stderr.as_str(), result.code), ("1\n", "", 70));
+    let result = lox("run", "print 1;", &["--stats"]);
+    assert_eq!(result.code, 0);
+    assert!(result.stderr.starts_with("time: "), "{}", result.stderr);
+    assert!(result.stderr.contains(" ms, environments: "), "{}", result.stderr);
+    let result = lox("run", "print 1;", &["--json", "--stats"]);
+    assert!(result.stderr.contains(",\"time_ms\":"), "{}", result.stderr);
+    assert!(result.stderr.contains(",\"stats\":{\"environments\":"), "{}", result.stderr);
+}
@@ -0,0 +1,53 @@
+// 集成测试共用的辅助函数：把源码写到临时文件，通过命令行运行解释器。
+// 每个测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: i32,
+}
+
+// 每次运行用自己的文件名，测试并行运行时不会互相覆盖
+static FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn source_file(source: &str) -> std::path::PathBuf {
+    let n = FILES.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("lox-test-{}-{}.lox", std::process::id(), n));
+    fs::write(&path, source).expect("temp file should be writable");
+    path
+}
+
+// 参数顺序和命令行一致：命令、源文件、选项
+pub fn lox(command: &str, source: &str, flags: &[&str]) -> Run {
+    let path = source_file(source);
+    let output = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .arg(command)
+        .arg(&path)
+        .args(flags)
+        .output()
+        .expect("interpreter should start");
+    let _ = fs::remove_file(path);
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        code: output.status.code().unwrap_or(-1),
+    }
+}
+
+pub fn run(source: &str) -> Run {
+    lox("run", source, &[])
+}
+
+// 运行程序，返回输出；程序必须正常结束
+pub fn output(source: &str) -> String {
+    let run = run(source);
+    assert_eq!(run.code, 0, "{:?}: {}", source, run.stderr);
+    run.stdout
+}
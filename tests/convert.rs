@@ -0,0 +1,66 @@
+// Rust 类型和 Value 之间的转换
+use std::collections::HashMap;
+
+use codecrafters_interpreter::{convert::ConversionError, interpreter::Value, lox_value};
+
+#[test]
+fn rust_values_convert_to_lox_values() {
+    assert_eq!(Value::from(1.5).to_string(), "1.5");
+    assert_eq!(Value::from(3).to_string(), "3");
+    assert_eq!(Value::from(-7i64).to_string(), "-7");
+    assert_eq!(Value::from("s").to_string(), "s");
+    assert_eq!(Value::from(true).to_string(), "true");
+    assert_eq!(Value::from(()).to_string(), "nil");
+    assert_eq!(Value::from(vec![1, 2]).to_string(), "[1, 2]");
+    // HashMap 按键排序后插入
+    let map = HashMap::from([("b".to_string(), 2), ("a".to_string(), 1)]);
+    assert_eq!(Value::from(map).to_string(), "{\"a\": 1, \"b\": 2}");
+}
+
+#[test]
+fn lox_values_convert_back() {
+    assert_eq!(f64::try_from(Value::from(2.5)), Ok(2.5));
+    assert_eq!(String::try_from(Value::from("s")), Ok("s".to_string()));
+    assert_eq!(bool::try_from(Value::from(false)), Ok(false));
+    assert_eq!(<()>::try_from(Value::Nil), Ok(()));
+    assert_eq!(Vec::<f64>::try_from(lox_value!([1, 2.5])), Ok(vec![1.0, 2.5]));
+    let prices: HashMap<String, Vec<f64>> =
+        lox_value!({ "apple": [1, 2], "pear": [] }).try_into().unwrap();
+    assert_eq!(prices["apple"], vec![1.0, 2.0]);
+    assert!(prices["pear"].is_empty());
+}
+
+// 错误信息指出嵌套的列表和映射中出错的位置
+#[test]
+fn conversion_errors_name_the_path() {
+    let error = f64::try_from(Value::from("s")).unwrap_err();
+    assert_eq!(error.to_string(), "Expected number but got string.");
+    let value = lox_value!([{ "price": 1 }, { "price": "free" }]);
+    let error: ConversionError = Vec::<HashMap<String, f64>>::try_from(value).unwrap_err();
+    assert_eq!(error.path, "[1].price");
+    assert_eq!(error.to_string(), "Expected number but got string at [1].price.");
+}
+
+// 宏写出的值和脚本中写出的同样的值打印结果相同
+#[test]
+fn lox_value_macro() {
+    let value = lox_value!({ "name": "apple", "tags": ["red", 1.5, nil], "ok": true, "n": (-1) });
+    assert_eq!(
+        value.to_string(),
+        "{\"name\": \"apple\", \"tags\": [\"red\", 1.5, nil], \"ok\": true, \"n\": -1}"
+    );
+}
+
+#[test]
+fn try_into_shorthands() {
+    assert_eq!(Value::from(2.5).try_into_f64(), Ok(2.5));
+    assert_eq!(Value::from("s").try_into_string(), Ok("s".to_string()));
+    assert_eq!(Value::from(true).try_into_bool(), Ok(true));
+    let error = Value::Nil.try_into_f64().unwrap_err();
+    assert_eq!(error.to_string(), "Expected number but got nil.");
+    assert_eq!(
+        Value::from(1).try_into_string().unwrap_err().to_string(),
+        "Expected string but got number."
+    );
+    assert!(Value::from("true").try_into_bool().is_err());
+}
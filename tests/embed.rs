@@ -0,0 +1,48 @@
+// 在宿主程序中用给定的变量计算表达式
+use std::collections::HashMap;
+
+use codecrafters_interpreter::{
+    embed::{evaluate_with_vars, evaluate_with_vars_and_natives, LoxError},
+    interpreter::Value,
+};
+
+fn vars(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+}
+
+#[test]
+fn evaluates_against_host_variables() {
+    let vars = vars(&[
+        ("price", Value::Number(2.5)),
+        ("qty", Value::Number(4.0)),
+        ("taxRate", Value::Number(0.5)),
+    ]);
+    let value = evaluate_with_vars("price * qty * (1 + taxRate)", &vars).unwrap();
+    assert_eq!(value.to_string(), "15");
+    let value = evaluate_with_vars("price > 2 and qty < 3", &vars).unwrap();
+    assert_eq!(value.to_string(), "false");
+}
+
+#[test]
+fn calls_need_natives() {
+    let error = evaluate_with_vars("clock() + 1", &HashMap::new()).unwrap_err();
+    assert!(matches!(error, LoxError::Parse(_)), "{:?}", error);
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Error: Function calls are not allowed in this expression."
+    );
+    let value = evaluate_with_vars_and_natives("clock() >= 0", &HashMap::new()).unwrap();
+    assert_eq!(value.to_string(), "true");
+}
+
+#[test]
+fn errors_keep_their_kind() {
+    let empty = HashMap::new();
+    let error = evaluate_with_vars("1 @ 2", &empty).unwrap_err();
+    assert!(matches!(error, LoxError::Scan(_)), "{:?}", error);
+    let error = evaluate_with_vars("1 +", &empty).unwrap_err();
+    assert!(matches!(error, LoxError::Parse(_)), "{:?}", error);
+    let error = evaluate_with_vars("missing + 1", &empty).unwrap_err();
+    assert!(matches!(error, LoxError::Runtime(_)), "{:?}", error);
+    assert!(error.to_string().contains("Undefined variable 'missing'."), "{}", error);
+}
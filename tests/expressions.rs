@@ -0,0 +1,358 @@
+mod common;
+
+use common::{lox, output};
+
+// 括号只影响语法树的形状：求值前去掉 Grouping 节点，结果不变
+#[test]
+fn grouping_changes_evaluation_order() {
+    let result = lox("evaluate", "(1+2)*3", &[]);
+    assert_eq!(result.stdout, "9\n");
+    assert_eq!(output("print (1 + 2) * 3 - -(4);"), "13\n");
+    assert_eq!(output("print 2 * (3 + 4) / (1 + 1);"), "7\n");
+}
+
+#[test]
+fn parse_keeps_or_strips_groups() {
+    let parse = |flags: &[&str]| lox("parse", "(1 + 2) * 3 - -(4)", flags).stdout;
+    assert_eq!(parse(&[]), "(- (* (group (+ 1.0 2.0)) 3.0) (- (group 4.0)))\n");
+    assert_eq!(parse(&["--no-groups"]), "(- (* (+ 1.0 2.0) 3.0) (- 4.0))\n");
+}
+
+// --pretty 按优先级重新推导括号：只保留改变求值顺序的括号
+#[test]
+fn pretty_printer_parenthesizes_by_precedence() {
+    let cases = [
+        ("(1+2)*3", "(1 + 2) * 3"),
+        ("1+(2*3)", "1 + 2 * 3"),
+        ("((1))", "1"),
+        ("-(1-2)", "-(1 - 2)"),
+        ("(1-2)-3", "1 - 2 - 3"),
+        ("1-(2-3)", "1 - (2 - 3)"),
+        ("1+(2+3)", "1 + 2 + 3"),
+        ("!(true == false)", "!(true == false)"),
+        ("(a or b) and c", "(a or b) and c"),
+        ("a = (b = 1)", "a = b = 1"),
+    ];
+    for (source, expected) in cases {
+        let result = lox("parse", source, &["--pretty"]);
+        assert_eq!(result.stdout, format!("{}\n", expected), "{}", source);
+    }
+}
+
+// 数字按最短往返表示输出，evaluate 和 print 一致
+#[test]
+fn number_formatting() {
+    let cases = [
+        ("0.1 + 0.2", "0.30000000000000004"),
+        ("1000000000000000000000", "1e21"),
+        ("100000000000000000000", "100000000000000000000"),
+        ("1 / 3", "0.3333333333333333"),
+        ("-0.0", "-0"),
+        ("0.00000001", "1e-8"),
+        ("7", "7"),
+        ("2.50", "2.5"),
+    ];
+    for (source, expected) in cases {
+        let expected = format!("{}\n", expected);
+        assert_eq!(lox("evaluate", source, &[]).stdout, expected, "{}", source);
+        assert_eq!(output(&format!("print {};", source)), expected, "{}", source);
+    }
+}
+
+// tokenize 和 parse 中的数字字面量保留 ".0"
+#[test]
+fn number_literals_keep_a_fraction() {
+    let result = lox("tokenize", "7 2.50", &[]);
+    assert_eq!(result.stdout, "NUMBER 7 7.0\nNUMBER 2.50 2.5\nEOF  null\n");
+}
+
+#[test]
+fn nested_groups() {
+    assert_eq!(lox("evaluate", "((((1))))", &[]).stdout, "1\n");
+    assert_eq!(
+        lox("parse", "((((1))))", &[]).stdout,
+        "(group (group (group (group 1.0))))\n"
+    );
+    assert_eq!(lox("parse", "((((1))))", &["--pretty"]).stdout, "1\n");
+    assert_eq!(output("print -((-(2)));"), "2\n");
+}
+
+// 几千层括号和前缀运算符在命令行的栈上也不会溢出
+#[test]
+fn thousands_of_nested_groups() {
+    let source = format!("{}1{}", "(".repeat(3000), ")".repeat(3000));
+    assert_eq!(lox("evaluate", &source, &[]).stdout, "1\n");
+    assert_eq!(lox("parse", &source, &["--pretty"]).stdout, "1\n");
+    let parsed = lox("parse", &source, &[]);
+    assert_eq!(parsed.code, 0);
+    assert_eq!(parsed.stdout.len(), "(group ".len() * 3000 + "1.0".len() + 3000 + 1);
+    assert_eq!(output(&format!("print {};", source)), "1\n");
+    assert_eq!(output(&format!("print {}1;", "-".repeat(5000))), "1\n");
+    assert_eq!(output(&format!("print {}true;", "!".repeat(5001))), "false\n");
+}
+
+// fun f(p0, p1, ...) { return p<last>; } print f(0, 1, ...);
+fn call_with(params: usize, args: usize) -> common::Run {
+    let names: Vec<String> = (0..params).map(|i| format!("p{}", i)).collect();
+    let values: Vec<String> = (0..args).map(|i| i.to_string()).collect();
+    let source = format!(
+        "fun f({}) {{ return p{}; }}\nprint f({});",
+        names.join(", "),
+        params.saturating_sub(1),
+        values.join(", ")
+    );
+    lox("run", &source, &[])
+}
+
+#[test]
+fn calls_and_functions_allow_255_arguments() {
+    let result = call_with(255, 255);
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, "254\n");
+}
+
+#[test]
+fn more_than_255_arguments_or_parameters_is_a_parse_error() {
+    let result = call_with(255, 256);
+    assert_eq!(result.code, 65);
+    assert!(
+        result.stderr.contains("[line 2] Error: Can't have more than 255 arguments."),
+        "{}",
+        result.stderr
+    );
+    let result = call_with(256, 255);
+    assert_eq!(result.code, 65);
+    assert!(
+        result.stderr.contains("[line 1] Error: Can't have more than 255 parameters."),
+        "{}",
+        result.stderr
+    );
+}
+
+// 字符串按源码形式输出，换行、引号和反斜杠写成转义，重新解析后得到同样的表达式
+#[test]
+fn pretty_strings_parse_back_to_the_same_value() {
+    let sources = [
+        "\"a\nb\" + \"c\"",
+        "\"q\\\"\\\\\"",
+        "(\"x\" + \"\") == \"\"",
+        "\"\"",
+        "\"  spaced  \"",
+    ];
+    for source in sources {
+        let pretty = lox("parse", source, &["--pretty"]);
+        assert_eq!(pretty.code, 0, "{:?}", source);
+        let printed = pretty.stdout.strip_suffix('\n').unwrap();
+        assert_eq!(lox("parse", printed, &["--pretty"]).stdout, pretty.stdout, "{:?}", source);
+        assert_eq!(
+            lox("evaluate", printed, &[]).stdout,
+            lox("evaluate", source, &[]).stdout,
+            "{:?}",
+            source
+        );
+    }
+    assert_eq!(lox("parse", "\"a\nb\"", &["--pretty"]).stdout, "\"a\\nb\"\n");
+}
+
+// 不同类型的值不做隐式转换，== 一律是 false
+#[test]
+fn equality_across_types_is_false() {
+    for source in ["1 == true", "0 == false", "\"1\" == 1", "nil == false", "\"\" == nil"] {
+        assert_eq!(output(&format!("print {};", source)), "false\n", "{}", source);
+        assert_eq!(
+            output(&format!("print !({});", source.replace("==", "!="))),
+            "false\n",
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn div_is_floor_division() {
+    assert_eq!(output("print 7 div 2;\nprint -7 div 2;\nprint 7.5 div 2;"), "3\n-4\n3\n");
+    // 和 * / 同一优先级，从左到右结合
+    assert_eq!(output("print 1 + 7 div 2 * 3;"), "10\n");
+    assert_eq!(lox("parse", "a div b * c", &[]).stdout, "(* (div a b) c)\n");
+    let result = lox("evaluate", "7 div 0", &[]);
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("Division by zero."), "{}", result.stderr);
+}
+
+// div 只在运算符的位置是关键字
+#[test]
+fn div_can_still_be_a_name() {
+    assert_eq!(
+        output("var div = 7;\nprint div div 2;\nfun f(div) { return div; }\nprint f(1);"),
+        "3\n1\n"
+    );
+}
+
+#[test]
+fn assignment_yields_assigned_value() {
+    assert_eq!(output("var x; print (x = 5) + 1; print x;"), "6\n5\n");
+    assert_eq!(output("var x = 1; print x = \"s\";"), "s\n");
+    assert_eq!(output("var a; var b; if ((a = b = true) and b) print a;"), "true\n");
+}
+
+// 赋值是右结合的：a = b = c 先给 b 赋值，再把结果赋给 a
+#[test]
+fn assignment_chains_right_to_left() {
+    assert_eq!(
+        output("var a = 1; var b = 2; var c = 3; a = b = c; print a; print b; print c;"),
+        "3\n3\n3\n"
+    );
+    assert_eq!(
+        output(
+            "var log = \"\";
+             fun note(name, value) { log = log + name; return value; }
+             var a; var b;
+             a = b = note(\"c\", 1) + note(\"d\", 2);
+             print log; print a; print b;"
+        ),
+        "cd\n3\n3\n"
+    );
+    assert_eq!(
+        output("var o; class C {} o = C(); var x; o.f = x = 4; print o.f; print x;"),
+        "4\n4\n"
+    );
+}
+
+#[test]
+fn assignment_to_undefined_variable_is_an_error() {
+    let result = lox("run", "var a = 1;\n\nx = a;", &[]);
+    assert_eq!(result.code, 70);
+    assert!(
+        result.stderr.ends_with("[line 3] Error: Undefined variable 'x'.\n"),
+        "{}",
+        result.stderr
+    );
+}
+
+// 除以 0 是运行时错误，用 inf - inf 得到 NaN
+const NAN: &str = "var inf = 1;
+    for (var i = 0; i < 400; i = i + 1) inf = inf * 10;
+    var nan = inf - inf;";
+
+#[test]
+fn nan_is_not_equal_to_itself() {
+    assert_eq!(
+        output(&format!("{} print nan; print nan == nan; print nan != nan;", NAN)),
+        "NaN\nfalse\ntrue\n"
+    );
+    assert_eq!(output(&format!("{} var x = nan; print x == x;", NAN)), "false\n");
+    assert_eq!(output(&format!("{} print inf == inf; print inf == -inf;", NAN)), "true\nfalse\n");
+}
+
+#[test]
+fn comparisons_with_nan_are_false() {
+    for op in ["<", ">", "<=", ">="] {
+        let source = format!(
+            "{} print nan {op} 1; print 1 {op} nan; print nan {op} nan; print nan {op} inf;",
+            NAN
+        );
+        assert_eq!(output(&source), "false\nfalse\nfalse\nfalse\n", "{}", op);
+    }
+}
+
+// xs?[i] 和 f?(...) 在 nil 处短路：下标、实参和链中后面的部分都不求值
+#[test]
+fn nil_safe_index_and_call() {
+    assert_eq!(
+        output(
+            "var calls = 0;
+             fun f() { calls = calls + 1; return 0; }
+             var xs = nil;
+             var g = nil;
+             print xs?[f()];
+             print g?(f());
+             print xs?[0][1];
+             print calls;
+             print [1, 2]?[1];
+             fun id(x) { return x; }
+             print id?(3);"
+        ),
+        "nil\nnil\nnil\n0\n2\n3\n"
+    );
+    assert_eq!(lox("parse", "a?[0]", &[]).stdout, "(index? a 0.0)\n");
+    // 不是 nil 时和普通的下标、调用一样报错
+    let result = lox("run", "var n = 1;\nprint n?[0];", &[]);
+    assert_eq!(result.code, 70);
+    let result = lox("run", "var n = 1;\nprint n?();", &[]);
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("Can only call functions."), "{}", result.stderr);
+}
+
+// xor 的两边总是都求值，结果是布尔值
+#[test]
+fn xor_is_not_short_circuit() {
+    assert_eq!(
+        output(
+            "var calls = 0;
+             fun f(x) { calls = calls + 1; return x; }
+             print true xor f(true);
+             print f(nil) xor 1;
+             print false xor f(false);
+             print calls;"
+        ),
+        "false\ntrue\nfalse\n3\n"
+    );
+    assert_eq!(lox("tokenize", "xor", &[]).stdout, "XOR xor null\nEOF  null\n");
+}
+
+// xor 的优先级在 or 和 and 之间，格式化输出只保留需要的括号
+#[test]
+fn xor_precedence_in_pretty_output() {
+    let cases = [
+        ("(a xor b) and c or (d or e) xor f", "(a xor b) and c or (d or e) xor f"),
+        ("(a and b) xor (c or d)", "a and b xor (c or d)"),
+        // xor 满足结合律，右边同级的括号也可以去掉
+        ("a xor (b xor c)", "a xor b xor c"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(lox("parse", source, &["--pretty"]).stdout, format!("{}\n", expected));
+    }
+}
+
+#[test]
+fn number_literal_diagnostics() {
+    let result = lox("run", "print 9007199254740993;", &[]);
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "9007199254740992\n");
+    assert!(
+        result.stderr.contains("[line 1] Warning: Number literal 9007199254740993 can't be"),
+        "{}",
+        result.stderr
+    );
+    let result = lox("run", &format!("print 1;\nprint 1{};", "0".repeat(400)), &[]);
+    assert_eq!(result.code, 65);
+    assert_eq!(result.stdout, "");
+    assert!(
+        result.stderr.contains("[line 2] Error: Number literal overflows to infinity."),
+        "{}",
+        result.stderr
+    );
+}
+
+// 匿名函数是表达式，求值时捕获当前环境
+#[test]
+fn anonymous_functions() {
+    assert_eq!(
+        output(
+            "var add = fun (a, b) { return a + b; };
+             print add(1, 2);
+             print add;
+             fun (x) { print x; }(3);
+             fun counter() { var n = 0; return fun () { n = n + 1; return n; }; }
+             var next = counter();
+             next();
+             print next();"
+        ),
+        "3\n<fn anonymous>\n3\n2\n"
+    );
+    assert_eq!(lox("parse", "fun (a, b) { return a + b; }", &[]).stdout, "(fun (a b))\n");
+    assert_eq!(lox("parse", "fun () {}", &["--pretty"]).stdout, "fun () { ... }\n");
+    let result = lox("run", "var f = fun x;", &[]);
+    assert_eq!(result.code, 65);
+    assert!(result.stderr.contains("Expect '(' after 'fun'."), "{}", result.stderr);
+}
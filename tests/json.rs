@@ -0,0 +1,142 @@
+// jsonParse/jsonStringify：直接调用内置函数，JSON 文本可以包含 Lox 字符串写不出的引号
+use codecrafters_interpreter::{
+    interpreter::{Interpreter, Value},
+    natives,
+};
+
+fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let native = natives::all()
+        .into_iter()
+        .find(|native| native.name == name)
+        .expect("native should exist");
+    (native.func)(&mut Interpreter::new(), args, 1).map_err(|error| error.to_string())
+}
+
+fn string(s: &str) -> Value {
+    Value::from(s)
+}
+
+fn parse(text: &str) -> Result<Value, String> {
+    call("jsonParse", &[string(text)])
+}
+
+fn stringify(value: Value) -> Result<String, String> {
+    call("jsonStringify", &[value]).map(|value| value.to_string())
+}
+
+fn round_trip(text: &str) -> String {
+    stringify(parse(text).unwrap()).unwrap()
+}
+
+#[test]
+fn values_round_trip() {
+    assert_eq!(
+        round_trip("{\"a\": [1, 2.5, -3, true, null, \"s\\n\\\"q\\\"\"], \"b\": {}, \"c\": []}"),
+        "{\"a\":[1,2.5,-3,true,null,\"s\\n\\\"q\\\"\"],\"b\":{},\"c\":[]}"
+    );
+    assert_eq!(round_trip(" [ \"\\u00e9\\ud83d\\ude00\" ] "), "[\"é😀\"]");
+    // 重复的键以最后一次出现为准
+    assert_eq!(round_trip("{\"k\": 1, \"k\": 2}"), "{\"k\":2}");
+}
+
+// 整数形式的数字没有小数部分，大数和小数按最短往返表示
+#[test]
+fn integer_looking_numbers_round_trip() {
+    assert_eq!(round_trip("[1.0, 100, 1e3, -0, 0.5, 1E21, 1e-8]"), "[1,100,1000,-0,0.5,1e21,1e-8]");
+    assert_eq!(parse("12").unwrap().to_string(), "12");
+    assert_eq!(parse("12.0").unwrap().to_string(), "12");
+}
+
+// 位置是从 0 开始的字符下标
+#[test]
+fn parse_errors_report_positions() {
+    for (text, message) in [
+        ("[1, }", "Unexpected character '}' at position 4."),
+        ("[1", "Unexpected end of input at position 2."),
+        ("{\"a\" 1}", "Unexpected character '1' at position 5."),
+        ("{1: 2}", "Unexpected character '1' at position 1."),
+        ("\"abc", "Unterminated string at position 4."),
+        ("\"a\\x\"", "Invalid escape at position 2."),
+        ("[1] 2", "Unexpected character '2' at position 4."),
+        ("tru", "Unexpected end of input at position 3."),
+        ("", "Unexpected end of input at position 0."),
+    ] {
+        let error = parse(text).unwrap_err();
+        assert!(error.ends_with(&format!("Error: jsonParse: {}", message)), "{}: {}", text, error);
+    }
+}
+
+#[test]
+fn stringify_errors() {
+    let clock = natives::all().into_iter().find(|native| native.name == "clock").unwrap();
+    let error = stringify(Value::NativeFunction(clock)).unwrap_err();
+    assert!(error.ends_with("jsonStringify: Cannot serialize function to JSON."), "{}", error);
+    let error = stringify(Value::Number(f64::NAN)).unwrap_err();
+    assert!(error.ends_with("jsonStringify: Cannot serialize NaN to JSON."), "{}", error);
+
+    // 列表包含自己时报错，不会无限递归；同一个列表出现两次不是循环
+    let shared = Value::list(vec![Value::Number(1.0)]);
+    let Value::List(list) = shared.clone() else {
+        unreachable!()
+    };
+    assert_eq!(
+        stringify(Value::list(vec![shared.clone(), shared.clone()])),
+        Ok("[[1],[1]]".to_string())
+    );
+    list.borrow_mut().push(shared.clone());
+    let error = stringify(shared).unwrap_err();
+    assert!(
+        error.ends_with("jsonStringify: Cannot serialize a cyclic structure to JSON."),
+        "{}",
+        error
+    );
+    list.borrow_mut().clear();
+}
+
+#[test]
+fn stringify_with_indent() {
+    let value = parse("{\"a\": [1, {}], \"b\": null}").unwrap();
+    assert_eq!(
+        call("jsonStringify", &[value.clone(), Value::Number(2.0)]).unwrap().to_string(),
+        "{\n  \"a\": [\n    1,\n    {}\n  ],\n  \"b\": null\n}"
+    );
+    // 0 换行但不缩进，10 是允许的最大缩进
+    assert_eq!(
+        call("jsonStringify", &[value.clone(), Value::Number(0.0)]).unwrap().to_string(),
+        "{\n\"a\": [\n1,\n{}\n],\n\"b\": null\n}"
+    );
+    let ten = call("jsonStringify", &[value.clone(), Value::Number(10.0)]).unwrap();
+    assert!(ten.to_string().starts_with("{\n          \"a\""), "{}", ten);
+    for indent in [-1.0, 1.5, 11.0, 1e300, f64::INFINITY, f64::NAN] {
+        let error = call("jsonStringify", &[value.clone(), Value::Number(indent)]).unwrap_err();
+        assert!(
+            error.ends_with("jsonStringify: indent must be an integer between 0 and 10."),
+            "{}",
+            error
+        );
+    }
+}
+
+// to_json/from_json 和 jsonStringify（不缩进）/jsonParse 的结果一致，错误信息带上各自的名字
+#[test]
+fn to_json_and_from_json_match_the_long_names() {
+    for text in ["[1,\"a\",null]", "{\"k\":[true,{}]}", "3.5"] {
+        let value = call("from_json", &[string(text)]).unwrap();
+        assert_eq!(value.to_string(), parse(text).unwrap().to_string());
+        assert_eq!(call("to_json", std::slice::from_ref(&value)).unwrap().to_string(), text);
+        assert_eq!(stringify(value), Ok(text.to_string()));
+    }
+    // 除了名字，别名的错误信息和原来的函数完全相同
+    let cases = [
+        ("from_json", "jsonParse", string("[1,")),
+        ("from_json", "jsonParse", Value::Number(1.0)),
+        ("to_json", "jsonStringify", Value::Number(f64::INFINITY)),
+    ];
+    for (alias, name, arg) in cases {
+        let error = call(alias, std::slice::from_ref(&arg)).unwrap_err();
+        assert!(error.contains(&format!("{}: ", alias)), "{}", error);
+        assert_eq!(error.replace(alias, name), call(name, &[arg]).unwrap_err());
+    }
+    let error = call("to_json", &[Value::Number(f64::INFINITY)]).unwrap_err();
+    assert!(error.ends_with("to_json: Cannot serialize Infinity to JSON."), "{}", error);
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use common::{lox, output, run};
+
+#[test]
+fn loop_with_conditional_break() {
+    assert_eq!(
+        output(
+            "var i = 0;
+             loop {
+                 i = i + 1;
+                 if (i == 2) continue;
+                 print i;
+                 if (i >= 4) break;
+             }
+             print \"done\";"
+        ),
+        "1\n3\n4\ndone\n"
+    );
+}
+
+// break 只跳出最内层的循环
+#[test]
+fn nested_breaks_leave_the_innermost_loop() {
+    assert_eq!(
+        output(
+            "var outer = 0;
+             loop {
+                 outer = outer + 1;
+                 var inner = 0;
+                 while (true) {
+                     inner = inner + 1;
+                     if (inner == 3) break;
+                 }
+                 print inner;
+                 if (outer == 2) break;
+             }
+             print outer;"
+        ),
+        "3\n3\n2\n"
+    );
+    assert_eq!(
+        output(
+            "var n = 0;
+             while (n < 3) {
+                 n = n + 1;
+                 loop { break; }
+                 for (;;) { loop { break; } break; }
+             }
+             print n;"
+        ),
+        "3\n"
+    );
+}
+
+#[test]
+fn break_outside_a_loop_is_an_error() {
+    let result = run("break;");
+    assert_eq!(result.code, 65);
+    assert!(
+        result.stderr.contains("Error: Can't use 'break' outside of a loop."),
+        "{}",
+        result.stderr
+    );
+    let result = lox("run", "loop print 1;", &[]);
+    assert_eq!(result.code, 65);
+    assert!(result.stderr.contains("Error: Expect '{' after 'loop'."), "{}", result.stderr);
+}
+
+// 没有 break 的 loop 在运行前给出警告；内层循环的 break 不算
+#[test]
+fn loop_without_break_is_denied() {
+    let source = "print \"start\";\nloop {\n  while (true) { break; }\n}";
+    let result = lox("run", source, &["--deny-warnings"]);
+    assert_eq!(result.code, 65);
+    assert_eq!(result.stdout, "");
+    assert!(
+        result.stderr.contains("[line 2] Warning: 'loop' without 'break' never terminates."),
+        "{}",
+        result.stderr
+    );
+
+    let result = lox("run", "loop { if (true) break; }\nprint 1;", &["--deny-warnings"]);
+    assert_eq!((result.code, result.stdout.as_str(), result.stderr.as_str()), (0, "1\n", ""));
+}
+
+#[test]
+fn for_in_over_lists_and_strings() {
+    assert_eq!(
+        output(
+            "for (x in jsonParse(\"[1, 2, 3]\")) { if (x == 2) continue; print x; }
+             for (var c in \"hey\") print c;
+             for (x in \"\") print \"never\";"
+        ),
+        "1\n3\nh\ne\ny\n"
+    );
+}
+
+// 每次迭代都有自己的循环变量，闭包捕获的是各自的值；循环结束后变量不可见
+#[test]
+fn for_in_variable_is_fresh_each_iteration() {
+    assert_eq!(
+        output(
+            "var first;
+             var second;
+             for (x in jsonParse(\"[1, 2]\")) {
+                 fun f() { return x; }
+                 if (x == 1) first = f; else second = f;
+             }
+             print first();
+             print second();"
+        ),
+        "1\n2\n"
+    );
+    let result = run("for (x in \"ab\") print x;\nprint x;");
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("[line 2] Error: Undefined variable 'x'."), "{}", result.stderr);
+}
+
+#[test]
+fn for_in_uses_the_iterator_protocol_for_instances() {
+    assert_eq!(
+        output(
+            "class Range { init(n) { this.n = n; } iter() { return RangeIter(this.n); } }
+             class RangeIter {
+                 init(n) { this.i = 0; this.n = n; }
+                 next() {
+                     if (this.i >= this.n) return nil;
+                     this.i = this.i + 1;
+                     return this.i;
+                 }
+             }
+             for (i in Range(5)) { if (i == 3) break; print i; }"
+        ),
+        "1\n2\n"
+    );
+    for (source, message) in [
+        (
+            "for (x in 1) print x;",
+            "Can only iterate over lists, strings, maps and instances, got number.",
+        ),
+        (
+            "class A {} for (x in A()) print x;",
+            "A instance is not iterable: it needs an 'iterator' or 'iter' method.",
+        ),
+        (
+            "class A { iter() { return 1; } } for (x in A()) print x;",
+            "'iter' must return an instance with a 'next' method.",
+        ),
+    ] {
+        let result = run(source);
+        assert_eq!(result.code, 70, "{:?}", source);
+        assert!(result.stderr.contains(message), "{:?}: {}", source, result.stderr);
+    }
+}
+
+// 映射按插入顺序遍历键
+#[test]
+fn for_in_over_map_keys() {
+    assert_eq!(
+        output("for (k in jsonParse(\"{\\\"b\\\": 1, \\\"a\\\": 2}\")) print k;"),
+        "b\na\n"
+    );
+}
+
+#[test]
+fn for_in_uses_has_next_when_there_is_an_iterator_method() {
+    assert_eq!(
+        output(
+            "class Countdown {
+                 init(n) { this.n = n; }
+                 iterator() { return this; }
+                 has_next() { return this.n > 0; }
+                 next() { this.n = this.n - 1; return this.n + 1; }
+             }
+             for (i in Countdown(3)) print i;
+             // next() 可以返回 nil，由 has_next() 决定何时结束
+             class Nils {
+                 init() { this.left = 2; }
+                 iterator() { return this; }
+                 has_next() { return this.left > 0; }
+                 next() { this.left = this.left - 1; return nil; }
+             }
+             for (x in Nils()) print x;"
+        ),
+        "3\n2\n1\nnil\nnil\n"
+    );
+    let result = run("class A { iterator() { return 1; } } for (x in A()) print x;");
+    assert_eq!(result.code, 70);
+    assert!(
+        result
+            .stderr
+            .contains("'iterator' must return an instance with 'has_next' and 'next' methods."),
+        "{}",
+        result.stderr
+    );
+}
+
+// 带标签的 break/continue 作用于标签所在的循环，不带标签时仍是最内层循环
+#[test]
+fn labeled_break_and_continue() {
+    assert_eq!(
+        output(
+            "outer: for (var i = 0; i < 3; i = i + 1) {
+               for (var j = 0; j < 3; j = j + 1) {
+                 if (j == 1) continue outer;
+                 if (i == 2) break outer;
+                 print i * 10 + j;
+               }
+             }
+             rows: while (true) {
+               loop {
+                 break rows;
+               }
+               print \"unreachable\";
+             }
+             var n = 0;
+             outer: loop {
+               inner: for (x in [1, 2, 3]) {
+                 n = n + x;
+                 if (x == 2) break inner;
+               }
+               if (n > 5) break outer;
+             }
+             print n;"
+        ),
+        "0\n10\n6\n"
+    );
+}
+
+#[test]
+fn label_errors() {
+    let cases = [
+        ("while (true) { break nowhere; }", "[line 1] Error: Undefined label 'nowhere'."),
+        (
+            "a: while (true) { a: loop { break a; } }",
+            "Label 'a' is already in use by an enclosing loop.",
+        ),
+        ("a: print 1;", "[line 1] Error: Expect a loop after label 'a'."),
+        // 函数体不能跳出外层的循环
+        ("a: loop { fun f() { loop { break a; } } }", "Undefined label 'a'."),
+    ];
+    for (source, message) in cases {
+        let result = lox("run", source, &[]);
+        assert_eq!(result.code, 65, "{}", source);
+        assert!(result.stderr.contains(message), "{}: {}", source, result.stderr);
+    }
+    // 前面的循环用过的标签可以再用
+    assert_eq!(output("a: loop { break a; }\na: loop { break a; }\nprint 1;"), "1\n");
+}
@@ -0,0 +1,284 @@
+mod common;
+
+use common::{lox, output, run};
+
+// setenv 只写入解释器自己的覆盖表，getenv 先查覆盖表再查进程环境变量
+#[test]
+fn getenv_and_setenv() {
+    assert_eq!(
+        output(
+            "print getenv(\"CARGO_MANIFEST_DIR\");
+             print getenv(\"LOX_TEST_SURELY_UNSET\");
+             print setenv(\"LOX_TEST_SURELY_UNSET\", \"set\");
+             print getenv(\"LOX_TEST_SURELY_UNSET\");
+             setenv(\"CARGO_MANIFEST_DIR\", \"overridden\");
+             print getenv(\"CARGO_MANIFEST_DIR\");"
+        ),
+        format!("{}\nnil\nnil\nset\noverridden\n", env!("CARGO_MANIFEST_DIR"))
+    );
+}
+
+// exec 返回 [退出码, stdout, stderr]
+#[test]
+fn exec_captures_status_and_output() {
+    assert_eq!(
+        output(
+            "var r = exec(\"echo hi; echo err >&2; exit 3\");
+             print r[0]; print r[1] + r[2];"
+        ),
+        "3\nhi\nerr\n\n"
+    );
+}
+
+#[test]
+fn list_literals_and_indexing() {
+    assert_eq!(
+        output("var l = [1, \"a\", nil, [true]]; print l; print l[1]; print l[3][0];"),
+        "[1, \"a\", nil, [true]]\na\ntrue\n"
+    );
+    for (source, message) in [
+        ("print [1][1];", "Index out of bounds."),
+        ("print [1][-1];", "Index out of bounds."),
+        ("print [1][0.5];", "Index must be an integer."),
+        ("print 1[0];", "can be indexed."),
+    ] {
+        let result = run(source);
+        assert_eq!(result.code, 70, "{}", source);
+        assert!(result.stderr.contains(message), "{}: {}", source, result.stderr);
+    }
+}
+
+// 下标的错误指向下标结尾的 `]` 所在的列
+#[test]
+fn index_errors_point_at_the_bracket() {
+    for (source, position) in [
+        ("print [1][1];", "\"line\":1,\"column\":12"),
+        ("var l = [1];\nprint l[0.5];", "\"line\":2,\"column\":12"),
+        ("print   1[0];", "\"line\":1,\"column\":12"),
+        ("var m = jsonParse(\"{}\");\nprint m[\"a\"] + m[1];", "\"line\":2,\"column\":19"),
+    ] {
+        let result = lox("run", source, &["--json"]);
+        assert_eq!(result.code, 70, "{}", source);
+        assert!(result.stderr.contains(position), "{}: {}", source, result.stderr);
+    }
+    let result = lox("run", "var l = [];\nprint l[0];", &["--prefix-errors"]);
+    assert!(result.stderr.ends_with("\n    print l[0];\n             ^\n"), "{}", result.stderr);
+}
+
+// 内置函数的参数类型错误统一为 "<fn>: argument <n> must be a <type>."
+#[test]
+fn native_argument_errors_name_the_argument() {
+    for (source, message) in [
+        ("getenv(1);", "getenv: argument 1 must be a string."),
+        ("setenv(1, \"v\");", "setenv: argument 1 must be a string."),
+        ("setenv(\"k\", nil);", "setenv: argument 2 must be a string."),
+        ("exec([\"ls\"]);", "exec: argument 1 must be a string."),
+    ] {
+        let result = run(source);
+        assert_eq!(result.code, 70, "{}", source);
+        assert!(
+            result.stderr.contains(&format!("[line 1] Error: {}", message)),
+            "{}: {}",
+            source,
+            result.stderr
+        );
+    }
+}
+
+// 映射按插入顺序打印，用字符串下标取值，不存在的键是 nil
+#[test]
+fn maps_from_json() {
+    assert_eq!(
+        output(
+            "var m = jsonParse(\"[1, [2.5, null], {}]\");
+             print m; print m[1][0]; print jsonStringify(m);"
+        ),
+        "[1, [2.5, nil], {}]\n2.5\n[1,[2.5,null],{}]\n"
+    );
+    let result = run("var m = jsonParse(\"{}\"); print m[\"missing\"]; print m[1];");
+    assert_eq!(result.stdout, "nil\n");
+    assert!(result.stderr.contains("Map keys must be strings."), "{}", result.stderr);
+}
+
+#[test]
+fn memoize_caches_by_argument_values() {
+    assert_eq!(
+        output(
+            "var calls = 0;
+             fun square(x) { calls = calls + 1; return x * x; }
+             var f = memoize(square);
+             print f(3);
+             print f(3);
+             print f(4);
+             print f(0);
+             print f(-0);
+             print calls;
+             var stats = cacheStats(f);
+             print stats[\"size\"];
+             print stats[\"hits\"];
+             print stats[\"misses\"];
+             print f;"
+        ),
+        "9\n9\n16\n0\n0\n3\n3\n2\n3\n<fn square>\n"
+    );
+}
+
+#[test]
+fn memoize_errors() {
+    for (source, message) in [
+        (
+            "var f = memoize(clock);\nf(jsonParse(\"[1]\"));",
+            "[line 2] Error: memoize: can't use a list as a cache key.",
+        ),
+        ("memoize(1);", "[line 1] Error: memoize: argument 1 must be a function."),
+        (
+            "cacheStats(clock);",
+            "[line 1] Error: cacheStats: argument 1 must be a memoized function.",
+        ),
+    ] {
+        let result = run(source);
+        assert_eq!(result.code, 70, "{:?}", source);
+        assert!(result.stderr.contains(message), "{:?}: {}", source, result.stderr);
+    }
+}
+
+// 离开作用域的环境和不再被引用的列表、映射、函数会被释放，计数随之减少
+#[test]
+#[cfg(feature = "stats")]
+fn live_counts_drop_when_values_are_released() {
+    assert_eq!(
+        output(
+            "var before = envCount();
+             { var a = 1; print envCount() - before; }
+             print envCount() - before;
+             var s = valueStats();
+             print s[\"lists\"];
+             var l = jsonParse(\"[1, [2], {}]\");
+             s = valueStats();
+             print s[\"lists\"];
+             print s[\"maps\"];
+             l = nil;
+             s = valueStats();
+             print s[\"lists\"];
+             print s[\"maps\"];"
+        ),
+        "1\n0\n0\n2\n2\n0\n1\n"
+    );
+}
+
+// 函数内声明的函数保存在它捕获的环境里，形成 Rc 循环：去掉外部引用后两者都不会被释放，
+// 这正是计数用来发现的问题
+#[test]
+#[cfg(feature = "stats")]
+fn closure_cycles_show_up_in_the_counts() {
+    assert_eq!(
+        output(
+            "fun make() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }
+             var before = envCount();
+             var functions = valueStats()[\"functions\"];
+             var counter = make();
+             print envCount() - before;
+             print valueStats()[\"functions\"] - functions;
+             counter = nil;
+             print envCount() - before;
+             print valueStats()[\"functions\"] - functions;"
+        ),
+        "2\n1\n2\n1\n"
+    );
+}
+
+#[test]
+fn printf_writes_without_a_newline() {
+    assert_eq!(
+        output("printf(\"{:<6}|{:>6.1}|\\n\", \"name\", 2.25); printf(\"{{}}\"); print \"\";"),
+        "name  |   2.2|\n{}\n"
+    );
+    let result = run("printf(\"{} {}\", 1);");
+    assert_eq!(result.code, 70);
+    assert!(
+        result.stderr.contains("printf: Missing argument for placeholder at position 3."),
+        "{}",
+        result.stderr
+    );
+}
+
+#[test]
+fn repr_quotes_strings() {
+    assert_eq!(
+        output(
+            "print repr(\"a\");
+             print repr(\"say \\\"hi\\\"\\n\");
+             print repr(1.5);
+             print repr(nil);
+             print repr([1, \"b\"]);
+             print repr(repr(\"x\"));"
+        ),
+        "\"a\"\n\"say \\\"hi\\\"\\n\"\n1.5\nnil\n[1, \"b\"]\n\"\\\"x\\\"\"\n"
+    );
+}
+
+// eval 和 evalStatements 需要 --allow-eval
+fn run_with_eval(source: &str) -> common::Run {
+    lox("run", source, &["--allow-eval"])
+}
+
+#[test]
+fn eval_runs_code_in_the_global_environment() {
+    let result = run_with_eval(
+        "var a = 20;
+         print eval(\"a * 2 + 2\");
+         evalStatements(\"var b = a + 1; fun twice(n) { return n * 2; }\");
+         print twice(b);",
+    );
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, "42\n42\n");
+}
+
+#[test]
+fn eval_is_disabled_by_default() {
+    let result = run("print eval(\"1\");");
+    assert_eq!(result.code, 70);
+    let expected = "eval: evaluating code from strings is disabled.";
+    assert!(result.stderr.contains(expected), "{}", result.stderr);
+}
+
+// 被求值代码的错误作为调用处的运行时错误报告，带上它在字符串里的行号
+#[test]
+fn eval_syntax_errors() {
+    for (source, message) in [
+        ("print eval(\"1 +\");", "eval: syntax error at line 1: Expect expression."),
+        ("print eval(\"@\");", "eval: syntax error at line 1: Unexpected character: @"),
+        (
+            "\nevalStatements(\"var a = 1;\\nprint ;\");",
+            "[line 2] Error: evalStatements: syntax error at line 2: Expect expression.",
+        ),
+    ] {
+        let result = run_with_eval(source);
+        assert_eq!(result.code, 70, "{:?}", source);
+        assert!(result.stderr.contains(message), "{:?}: {}", source, result.stderr);
+    }
+}
+
+#[test]
+fn rounding_natives() {
+    assert_eq!(
+        output(
+            "print floorDiv(-7, 2);
+             print truncDiv(-7, 2);
+             print round(2.5);
+             print round(-2.5);
+             print roundTo(1.23456, 3);
+             print trunc(-2.7);"
+        ),
+        "-4\n-3\n3\n-3\n1.235\n-2\n"
+    );
+    for (source, message) in [
+        ("floorDiv(1, 0);", "floorDiv: division by zero."),
+        ("truncDiv(1, 0);", "truncDiv: division by zero."),
+        ("roundTo(1, 0.5);", "roundTo: argument 2 must be an integer from 0 to 15."),
+    ] {
+        let result = run(source);
+        assert_eq!(result.code, 70, "{}", source);
+        assert!(result.stderr.contains(message), "{}: {}", source, result.stderr);
+    }
+}
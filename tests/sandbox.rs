@@ -0,0 +1,36 @@
+use codecrafters_interpreter::interpreter::Interpreter;
+
+// 无限输出的循环在上限处干净地报错结束，不会一直写下去
+#[test]
+fn print_loop_stops_at_output_limit() {
+    let mut interpreter = Interpreter::new();
+    interpreter.max_output = Some(100);
+    let captured = interpreter.run_and_capture("while (true) print 1;");
+    assert_eq!(captured.exit_code, 70);
+    assert_eq!(
+        captured.diagnostics,
+        vec!["[line 1] Error: Output limit exceeded.".to_string()]
+    );
+    assert_eq!(captured.output, "1\n".repeat(50));
+}
+
+#[test]
+fn printf_counts_toward_output_limit() {
+    let mut interpreter = Interpreter::new();
+    interpreter.max_output = Some(10);
+    let captured = interpreter.run_and_capture("print 1234;\nwhile (true) printf(\"ab\");");
+    assert_eq!(captured.exit_code, 70);
+    assert_eq!(
+        captured.diagnostics,
+        vec!["[line 2] Error: Output limit exceeded.".to_string()]
+    );
+    assert_eq!(captured.output, "1234\nabab");
+}
+
+#[test]
+fn output_is_unlimited_by_default() {
+    let captured = Interpreter::new()
+        .run_and_capture("for (var i = 0; i < 20000; i = i + 1) print \"0123456789\";");
+    assert_eq!(captured.exit_code, 0);
+    assert_eq!(captured.output.len(), 220_000);
+}
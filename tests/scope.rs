@@ -0,0 +1,199 @@
+mod common;
+
+use common::{lox, output, run};
+
+#[test]
+fn local_shadows_native() {
+    assert_eq!(
+        output(
+            "{ var clock = \"local\"; print clock; }
+             fun f(clock) { return clock + 1; }
+             print f(1);
+             print clock() >= 0;"
+        ),
+        "local\n2\ntrue\n"
+    );
+}
+
+#[test]
+fn native_called_in_loop_after_shadowing_local_goes_out_of_scope() {
+    assert_eq!(
+        output(
+            "var n = 0;
+             for (var i = 0; i < 3; i = i + 1) {
+                 { var clock = i; n = n + clock; }
+                 if (clock() >= 0) n = n + 10;
+             }
+             print n;"
+        ),
+        "33\n"
+    );
+}
+
+// 每个名字只有一个静态绑定：函数体中的 b 在解析时还没有局部声明，绑定到全局的 b，
+// 和调用时块中是否已经定义了同名局部变量无关
+#[test]
+fn forward_reference_binds_to_global() {
+    assert_eq!(
+        output(
+            "var b = \"global\";
+             { fun show() { print b; } var b = \"local\"; show(); }"
+        ),
+        "global\n"
+    );
+}
+
+#[test]
+fn forward_reference_without_global_is_an_error() {
+    let result = run("{ fun show() { print b; } var b = \"local\"; show(); }");
+    assert_eq!(result.stdout, "");
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("[line 1] Error: Undefined variable 'b'."), "{}", result.stderr);
+}
+
+// 块中的函数名在整个块中可见，不会解析到同名的全局函数
+#[test]
+fn block_function_refers_to_later_sibling_not_global() {
+    assert_eq!(
+        output(
+            "fun isOdd(n) { return \"global\"; }
+             {
+                 fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+                 fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+                 print isEven(3);
+             }"
+        ),
+        "false\n"
+    );
+}
+
+#[test]
+fn nested_assignment_updates_the_resolved_scope() {
+    assert_eq!(
+        output(
+            "var a = \"global\";
+             {
+                 var a = \"outer\";
+                 {
+                     var a = \"inner\";
+                     fun set() { a = \"changed\"; }
+                     set();
+                     print a;
+                 }
+                 print a;
+                 fun setOuter() { a = \"outer changed\"; }
+                 { var a = \"shadow\"; setOuter(); print a; }
+                 print a;
+             }
+             print a;"
+        ),
+        "changed\nouter\nshadow\nouter changed\nglobal\n"
+    );
+}
+
+#[test]
+fn assignment_to_forward_reference_does_not_write_the_local() {
+    assert_eq!(
+        output(
+            "var b = 1;
+             { fun set() { b = 2; } var b = 10; set(); print b; }
+             print b;"
+        ),
+        "10\n2\n"
+    );
+    let result = run("{ fun set() { c = 2; } var c = 10; set(); }");
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("Undefined variable 'c'."), "{}", result.stderr);
+}
+
+const EVEN_ODD: &str = "
+    fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+    fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+    print isEven(10);
+    print isOdd(7);
+    print isEven(3);";
+
+#[test]
+fn mutual_recursion_at_top_level() {
+    assert_eq!(output(EVEN_ODD), "true\ntrue\nfalse\n");
+}
+
+#[test]
+fn mutual_recursion_in_block() {
+    assert_eq!(output(&format!("{{ {} }}", EVEN_ODD)), "true\ntrue\nfalse\n");
+    assert_eq!(output(&format!("fun main() {{ {} }} main();", EVEN_ODD)), "true\ntrue\nfalse\n");
+}
+
+// 函数仍然按顺序定义，声明之前调用是运行时错误
+#[test]
+fn calling_block_function_before_declaration_is_an_error() {
+    let result = run(
+        "{
+             fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+             print isEven(2);
+             fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+         }",
+    );
+    assert_eq!(result.code, 70);
+    assert!(
+        result.stderr.contains("[line 2] Error: Undefined variable 'isOdd'."),
+        "{}",
+        result.stderr
+    );
+}
+
+// for 的循环变量只在循环中可见，可以和外面的变量同名
+#[test]
+fn for_initializer_is_scoped_to_the_loop() {
+    assert_eq!(
+        output(
+            "var i = \"outer\";
+             for (var i = 0; i < 2; i = i + 1) print i;
+             print i;
+             fun f() {
+                 for (var j = 0; j < 1; j = j + 1) {}
+                 var j = \"after\";
+                 return j;
+             }
+             print f();"
+        ),
+        "0\n1\nouter\nafter\n"
+    );
+    let result = run("for (var k = 0; k < 1; k = k + 1) {}\nprint k;");
+    assert_eq!(result.code, 70);
+    assert!(result.stderr.contains("[line 2] Error: Undefined variable 'k'."), "{}", result.stderr);
+}
+
+// 每次迭代都有自己的循环变量，闭包捕获当次迭代的值；循环体中的赋值带到下一次迭代
+#[test]
+fn for_loop_variable_per_iteration() {
+    assert_eq!(
+        output(
+            "var f0; var f1; var f2;
+             for (var i = 0; i < 3; i = i + 1) {
+               var f = fun () { return i; };
+               if (i == 0) f0 = f; else if (i == 1) f1 = f; else f2 = f;
+             }
+             print f0(); print f1(); print f2();
+             for (var j = 0; j < 6; j = j + 1) { print j; j = j + 2; }
+             var k;
+             var first;
+             for (k = 0; k < 3; k = k + 1) if (k == 0) first = fun () { return k; };
+             print first();"
+        ),
+        "0\n1\n2\n0\n3\n3\n"
+    );
+    // 类型标注也复制到每次迭代
+    let result = lox(
+        "run",
+        "for (var j: number = 0; j < 2; j = j + 1) { print j; j = \"x\"; }",
+        &["--check-types"],
+    );
+    assert_eq!(result.code, 70);
+    assert_eq!(result.stdout, "0\n");
+    assert!(
+        result.stderr.contains("Expected variable 'j' to be number but got string."),
+        "{}",
+        result.stderr
+    );
+}
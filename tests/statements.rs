@@ -0,0 +1,130 @@
+mod common;
+
+use common::{lox, output, run};
+
+// 按顺序尝试每个分支，执行第一个匹配的分支
+#[test]
+fn match_literal_type_and_wildcard_patterns() {
+    let source = "
+        fun describe(v) {
+            match v {
+                0 => print \"zero\";
+                -1 => print \"minus one\";
+                \"hi\" => print \"greeting\";
+                true => print \"yes\";
+                nil => print \"nothing\";
+                number n => print n * 2;
+                string s => print s + \"!\";
+                function f => print \"fn\";
+                _ => print \"other\";
+            }
+        }
+        describe(0); describe(-1); describe(\"hi\"); describe(true); describe(nil);
+        describe(21); describe(\"x\"); describe(clock); describe(false);";
+    assert_eq!(
+        output(source),
+        "zero\nminus one\ngreeting\nyes\nnothing\n42\nx!\nfn\nother\n"
+    );
+}
+
+// 没有分支匹配时什么都不做；类型模式的绑定只在分支内可见
+#[test]
+fn match_without_matching_arm_and_binding_scope() {
+    assert_eq!(output("match 5 { 1 => print 1; } print \"after\";"), "after\n");
+    assert_eq!(
+        output("var n = \"outer\"; match 3 { number n => print n; } print n;"),
+        "3\nouter\n"
+    );
+    assert_eq!(output("match 1 { 1 => { print \"a\"; }, _ => print \"b\"; }"), "a\n");
+}
+
+#[test]
+fn match_pattern_errors() {
+    let result = run("match 1 { widget w => print w; }");
+    assert_eq!(result.code, 65);
+    assert!(
+        result.stderr.contains("Error: Unknown type 'widget' in pattern."),
+        "{}",
+        result.stderr
+    );
+    let result = lox("tokenize", "match => _", &[]);
+    assert_eq!(result.stdout, "MATCH match null\nARROW => null\nIDENTIFIER _ null\nEOF  null\n");
+}
+
+#[test]
+fn return_several_values_and_destructure_them() {
+    assert_eq!(
+        output(
+            "fun minmax(a, b) { if (a < b) return a, b; return b, a; }
+             var lo, hi = minmax(9, 4);
+             print lo;
+             print hi;
+             var x, y, z = jsonParse(\"[1, true, null]\");
+             print y;
+             print z;"
+        ),
+        "4\n9\ntrue\nnil\n"
+    );
+}
+
+// 只有 var q, r = f(); 会解开多个返回值，其他地方调用的值都是第一个值
+#[test]
+fn several_return_values_outside_destructuring_use_the_first() {
+    assert_eq!(
+        output(
+            "fun divmod(a, b) { return a div b, a - (a div b) * b; }
+             var q, r = divmod(7, 2);
+             print q; print r;
+             print divmod(7, 2) + 1;
+             var x = divmod(7, 2);
+             print x;
+             print divmod(7, 2);
+             fun twice(n) { return n * 2; }
+             print twice(divmod(9, 4));"
+        ),
+        "3\n1\n4\n3\n3\n4\n"
+    );
+    // 第一个值之后的值不会变成列表
+    assert_eq!(output("fun two() { return 1, 2; } print two() + 1;"), "2\n");
+}
+
+#[test]
+fn destructuring_needs_a_list_of_the_same_length() {
+    for (source, message) in [
+        (
+            "fun two() { return 1, 2; }\nvar a, b, c = two();",
+            "[line 2] Error: Expected 3 values to destructure but got 2.",
+        ),
+        (
+            "fun three() { return 1, 2, 3; }\nvar a, b = three();",
+            "[line 2] Error: Expected 2 values to destructure but got 3.",
+        ),
+        // return f(); 只转发 f 的第一个值
+        (
+            "fun two() { return 1, 2; }\nfun forward() { return two(); }\nvar a, b = forward();",
+            "[line 3] Error: Can only destructure a list into 2 variables, got number.",
+        ),
+        (
+            "var a, b = 1;",
+            "[line 1] Error: Can only destructure a list into 2 variables, got number.",
+        ),
+    ] {
+        let result = run(source);
+        assert_eq!(result.code, 70, "{:?}", source);
+        assert!(result.stderr.contains(message), "{:?}: {}", source, result.stderr);
+    }
+    let result = run("var a, = 1;");
+    assert_eq!(result.code, 65);
+    assert!(result.stderr.contains("Error: Expect variable name."), "{}", result.stderr);
+}
+
+#[test]
+fn destructured_variables_are_type_checked() {
+    let result = lox("run", "var a, b: string = jsonParse(\"[1, 2]\");", &["--check-types"]);
+    assert_eq!(result.code, 70);
+    assert!(
+        result.stderr.contains("Expected variable 'b' to be string but got number."),
+        "{}",
+        result.stderr
+    );
+}
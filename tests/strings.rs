@@ -0,0 +1,212 @@
+mod common;
+
+use common::{lox, output};
+
+// 相邻的字符串字面量：tokenize 输出独立的 STRING token，解析时合并成一个字面量
+#[test]
+fn adjacent_strings_are_separate_tokens() {
+    let result = lox("tokenize", "\"part one \" \"part two\"", &[]);
+    assert_eq!(result.code, 0);
+    assert_eq!(
+        result.stdout,
+        "STRING \"part one \" part one \nSTRING \"part two\" part two\nEOF  null\n"
+    );
+}
+
+#[test]
+fn adjacent_strings_merge_when_parsed() {
+    let result = lox("parse", "\"part one \" \"part two\" \"!\"", &[]);
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "part one part two!\n");
+    assert_eq!(output("print \"a\" \"b\" + \"c\";"), "abc\n");
+}
+
+// 跨行的未闭合字符串在错误信息中写出开头引号所在的行
+#[test]
+fn unterminated_string_names_its_start_line() {
+    let result = lox("tokenize", "var a = 1;\nvar s = \"one\ntwo\nthree;", &[]);
+    assert_eq!(result.code, 65);
+    assert!(
+        result.stderr.contains("Error: Unterminated string starting at line 2."),
+        "{}",
+        result.stderr
+    );
+    assert!(result.stdout.starts_with("VAR var null\nIDENTIFIER a null\n"));
+
+    let result = lox("tokenize", "print \"open", &[]);
+    assert_eq!(result.code, 65);
+    assert!(result.stderr.contains("[line 1] Error: Unterminated string.\n"), "{}", result.stderr);
+}
+
+#[test]
+fn escapes_are_decoded() {
+    assert_eq!(
+        output("print \"say \\\"hi\\\"\\tnow\\\\\";\nprint \"two\\nlines\";"),
+        "say \"hi\"\tnow\\\ntwo\nlines\n"
+    );
+}
+
+// tokenize 显示解码后的值，控制字符、引号和反斜杠写成转义，每个 token 仍占一行
+#[test]
+fn tokenize_escapes_string_values() {
+    let result = lox("tokenize", "\"a\\\"b\\\\c\\n\\td\" \"x\ny\"", &[]);
+    assert_eq!(result.code, 0);
+    assert_eq!(
+        result.stdout,
+        "STRING \"a\\\"b\\\\c\\n\\td\" a\\\"b\\\\c\\n\\td\nSTRING \"x\ny\" x\\ny\nEOF  null\n"
+    );
+}
+
+#[test]
+fn invalid_escapes_are_errors() {
+    let result = lox("tokenize", "\"\\q\"\n\"a\\\nb\"", &[]);
+    assert_eq!(result.code, 65);
+    assert_eq!(
+        result.stderr,
+        "[line 1] Error: Invalid escape sequence '\\q'.\n\
+         [line 2] Error: Invalid escape sequence '\\\\n'.\n"
+    );
+    assert!(result.stdout.starts_with("STRING \"\\q\" \\\\q\n"), "{}", result.stdout);
+}
+
+#[test]
+fn non_ascii_source() {
+    assert_eq!(
+        output(
+            "var café = \"é\";
+             var 数 = 2;
+             print café;
+             print 数 * 2;
+             for (c in \"héy\") print c;"
+        ),
+        "é\n4\nh\né\ny\n"
+    );
+}
+
+#[test]
+fn unicode_escapes() {
+    assert_eq!(
+        output("print \"caf\\u{e9} \\u{1F600} \\u{41}\";\nprint \"\\u{0}\" == \"\\u{000000}\";"),
+        "café 😀 A\ntrue\n"
+    );
+}
+
+#[test]
+fn invalid_unicode_escapes() {
+    let cases = [
+        ("\"\\u41\"", "Invalid unicode escape '\\u': expected '{' after '\\u'."),
+        ("\"\\u{41\"", "Unterminated unicode escape '\\u{41'."),
+        ("\"\\u{}\"", "Invalid unicode escape '\\u{}': expected 1 to 6 hex digits."),
+        ("\"\\u{1234567}\"", "Invalid unicode escape '\\u{1234567}': expected 1 to 6 hex digits."),
+        ("\"\\u{xyz}\"", "Invalid unicode escape '\\u{xyz}': expected 1 to 6 hex digits."),
+        (
+            "\"\\u{D800}\"",
+            "Invalid unicode escape '\\u{D800}': U+D800 is not a Unicode scalar value.",
+        ),
+        (
+            "\"\\u{110000}\"",
+            "Invalid unicode escape '\\u{110000}': U+110000 is not a Unicode scalar value.",
+        ),
+    ];
+    for (source, message) in cases {
+        let result = lox("tokenize", source, &[]);
+        assert_eq!(result.code, 65, "{}", source);
+        assert_eq!(result.stderr, format!("[line 1] Error: {}\n", message), "{}", source);
+    }
+    // 出错的转义原样保留在字符串中
+    let result = lox("tokenize", "\"a\\u{D800}b\"", &[]);
+    assert!(
+        result.stdout.starts_with("STRING \"a\\u{D800}b\" a\\\\u{D800}b\n"),
+        "{}",
+        result.stdout
+    );
+}
+
+// 拼接在左操作数的值上追加，不能改变变量中保存的字符串
+#[test]
+fn concatenation_leaves_operands_unchanged() {
+    assert_eq!(
+        output(
+            "var a = \"ab\";
+             var b = a + \"c\";
+             var c = a + a + a;
+             print a;
+             print b;
+             print c;
+             var s = \"\";
+             for (var i = 0; i < 3; i = i + 1) s = s + \"x\";
+             print s;"
+        ),
+        "ab\nabc\nababab\nxxx\n"
+    );
+    let result = lox("run", "print \"a\" + 1;", &[]);
+    assert_eq!(result.code, 70);
+    let expected = "Operands must be two numbers or two strings.";
+    assert!(result.stderr.contains(expected), "{}", result.stderr);
+}
+
+// 语法树输出中的字符串和 tokenize 一样转义，引号和换行不会破坏一行一个结果的格式
+#[test]
+fn parse_escapes_string_literals() {
+    let result = lox("parse", "\"say \\\"hi\\\"\" + \"a\nb\\t\"", &[]);
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "(+ say \\\"hi\\\" a\\nb\\t)\n");
+    // 运行时输出的仍然是原始字符
+    assert_eq!(output("print \"say \\\"hi\\\"\";"), "say \"hi\"\n");
+}
+
+// 驻留只影响存储，输出和比较结果不变
+#[test]
+fn interning_does_not_change_results() {
+    let source = "var a = \"ab\";
+                  var b = a + \"c\";
+                  print b == \"abc\";
+                  print a + a;
+                  print \"x\" == \"y\";";
+    let result = lox("run", source, &["--intern-strings"]);
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, "true\nabab\nfalse\n");
+    assert_eq!(result.stdout, output(source));
+}
+
+// 字符串是值语义：原地追加（s = s + piece）只在没有其他引用时发生，
+// 修改一个变量不会改变引用同一个字符串的其他变量、列表元素、参数或闭包
+const ALIASES: &str = "
+    var a = \"x\";
+    var b = a;
+    a = a + \"y\";
+    print a; print b;
+
+    var s = \"\";
+    var snapshot;
+    for (var i = 0; i < 5; i = i + 1) {
+        s = s + \"p\";
+        if (i == 1) snapshot = s;
+    }
+    print s; print snapshot;
+
+    var list = [s];
+    s = s + \"!\";
+    print list[0]; print s;
+
+    fun append(x) { x = x + \"?\"; return x; }
+    var arg = \"arg\";
+    print append(arg); print arg;
+
+    var captured = \"c\";
+    fun reader() { return captured; }
+    var copy = captured;
+    captured = captured + \"d\";
+    print reader(); print copy;
+
+    { var local = b; local = local + \"z\"; print local; }
+    print b;";
+
+const EXPECTED: &str = "xy\nx\nppppp\npp\nppppp\nppppp!\narg?\narg\ncd\nc\nxz\nx\n";
+
+#[test]
+fn appending_never_changes_aliases() {
+    assert_eq!(output(ALIASES), EXPECTED);
+    let result = lox("run", ALIASES, &["--intern-strings"]);
+    assert_eq!((result.stdout.as_str(), result.code), (EXPECTED, 0));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::lox;
+
+// tokenize 的输出，要求扫描没有错误
+fn tokens(source: &str) -> String {
+    let result = lox("tokenize", source, &[]);
+    assert_eq!(result.code, 0, "{:?}: {}", source, result.stderr);
+    result.stdout
+}
+
+#[test]
+fn question_and_colon_are_tokens() {
+    assert_eq!(
+        tokens("a ? b : c"),
+        "IDENTIFIER a null\nQUESTION ? null\nIDENTIFIER b null\nCOLON : null\n\
+         IDENTIFIER c null\nEOF  null\n"
+    );
+    assert_eq!(tokens("?:"), "QUESTION ? null\nCOLON : null\nEOF  null\n");
+}
+
+#[test]
+fn question_dot_is_one_token() {
+    assert_eq!(
+        tokens("a?.b ? ."),
+        "IDENTIFIER a null\nQUESTION_DOT ?. null\nIDENTIFIER b null\nQUESTION ? null\n\
+         DOT . null\nEOF  null\n"
+    );
+}
+
+// 任何文字的字母都可以组成标识符，组合附加符号可以跟在字母后面；emoji 不是字母
+#[test]
+fn unicode_identifiers() {
+    assert_eq!(
+        tokens("café 数 e\u{301}x _ü1"),
+        "IDENTIFIER café null\nIDENTIFIER 数 null\nIDENTIFIER e\u{301}x null\n\
+         IDENTIFIER _ü1 null\nEOF  null\n"
+    );
+    let result = lox("tokenize", "naïve 😀 x", &[]);
+    assert_eq!(result.code, 65);
+    assert_eq!(result.stderr, "[line 1] Error: Unexpected character: 😀\n");
+    assert_eq!(result.stdout, "IDENTIFIER naïve null\nIDENTIFIER x null\nEOF  null\n");
+}
+
+// 非 ASCII 字符前后的 token 位置都正确
+#[test]
+fn non_ascii_strings_and_numbers() {
+    assert_eq!(
+        tokens("\"ü\" 1.5 \"数\"x"),
+        "STRING \"ü\" ü\nNUMBER 1.5 1.5\nSTRING \"数\" 数\nIDENTIFIER x null\nEOF  null\n"
+    );
+}
+
+// ?[ 和 ?( 是一个 token，中间有空白时仍然是 ? 加括号
+#[test]
+fn question_bracket_and_paren() {
+    assert_eq!(
+        tokens("a?[0] f?() x ? [1]"),
+        "IDENTIFIER a null\nQUESTION_BRACKET ?[ null\nNUMBER 0 0.0\nRIGHT_BRACKET ] null\n\
+         IDENTIFIER f null\nQUESTION_PAREN ?( null\nRIGHT_PAREN ) null\n\
+         IDENTIFIER x null\nQUESTION ? null\nLEFT_BRACKET [ null\nNUMBER 1 1.0\n\
+         RIGHT_BRACKET ] null\nEOF  null\n"
+    );
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{lox, output, run};
+
+// run --check-types，返回运行结果
+fn checked(source: &str) -> common::Run {
+    lox("run", source, &["--check-types"])
+}
+
+fn assert_type_error(source: &str, message: &str) {
+    let result = checked(source);
+    assert_eq!(result.code, 70, "{:?}", source);
+    assert!(result.stderr.contains(message), "{:?}: {}", source, result.stderr);
+}
+
+#[test]
+fn annotations_are_ignored_without_the_flag() {
+    assert_eq!(
+        output("var x: number = 1; x = \"a\"; fun f(a: string): number { return a; } print f(x);"),
+        "a\n"
+    );
+}
+
+#[test]
+fn well_typed_program_runs() {
+    let result = checked(
+        "var x: number = 1;
+         x = x + 1;
+         { var x = \"shadow\"; print x; }
+         fun g(): nil {}
+         var h: function = g;
+         var anything: any = nil;
+         anything = \"text\";
+         fun id(s: string): string { return s; }
+         print id(\"ok\");
+         print x;",
+    );
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, "shadow\nok\n2\n");
+}
+
+#[test]
+fn mismatches_are_runtime_errors() {
+    assert_type_error(
+        "var x: number = \"a\";",
+        "[line 1] Error: Expected variable 'x' to be number but got string.",
+    );
+    assert_type_error(
+        "var x: number = 1;\nx = true;",
+        "[line 2] Error: Expected variable 'x' to be number but got bool.",
+    );
+    assert_type_error(
+        "fun f(a: string) {}\nf(1);",
+        "[line 2] Error: Expected argument 'a' of 'f' to be string but got number.",
+    );
+    assert_type_error(
+        "fun f(): number { return \"s\"; }\nf();",
+        "[line 2] Error: Expected return value of 'f' to be number but got string.",
+    );
+    // 不是内置类型的名字按类名检查，子类的实例也符合
+    assert_type_error(
+        "var y: widget = 1;",
+        "[line 1] Error: Expected variable 'y' to be widget but got number.",
+    );
+    assert_type_error(
+        "class P {}\nclass Q < P {}\nvar p: P = Q();\np = 1;",
+        "[line 4] Error: Expected variable 'p' to be P but got number.",
+    );
+}
+
+#[test]
+fn redeclaring_drops_the_annotation() {
+    let result = checked("var x: number = 1; var x = 2; x = \"s\"; print x;");
+    assert_eq!(result.code, 0, "{}", result.stderr);
+    assert_eq!(result.stdout, "s\n");
+}
+
+#[test]
+fn annotation_needs_a_type_name() {
+    let result = run("var y: = 1;");
+    assert_eq!(result.code, 65);
+    assert!(result.stderr.contains("Error: Expect type name after ':'."), "{}", result.stderr);
+}
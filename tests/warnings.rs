@@ -0,0 +1,101 @@
+mod common;
+
+use common::lox;
+
+// check 的警告，每行一条；只检查不执行
+fn warnings(source: &str) -> Vec<String> {
+    let result = lox("check", source, &[]);
+    assert_eq!(result.code, 0, "{:?}: {}", source, result.stderr);
+    assert_eq!(result.stdout, "");
+    result.stderr.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn code_after_return_break_and_continue_is_unreachable() {
+    let source = "fun f() {
+                      return 1;
+                      print \"dead\";
+                      print \"also dead\";
+                  }
+                  while (true) {
+                      break;
+                      print 1;
+                  }
+                  for (var i = 0; i < 3; i = i + 1) {
+                      { continue; }
+                      print i;
+                  }";
+    assert_eq!(
+        warnings(source),
+        [
+            "[line 3] Warning: Unreachable code.",
+            "[line 8] Warning: Unreachable code.",
+            "[line 12] Warning: Unreachable code.",
+        ]
+    );
+}
+
+#[test]
+fn branches_must_all_exit() {
+    let source = "fun both(x) {
+                      if (x) { return 1; } else { return 2; }
+                      print \"dead\";
+                  }
+                  fun one(x) {
+                      if (x) return 1;
+                      print \"live\";
+                  }
+                  fun wildcard(x) {
+                      match (x) {
+                          number n => return 1;
+                          _ => return 2;
+                      }
+                      print \"dead\";
+                  }
+                  fun partial(x) {
+                      match (x) {
+                          number n => return 1;
+                      }
+                      print \"live\";
+                  }";
+    assert_eq!(
+        warnings(source),
+        ["[line 3] Warning: Unreachable code.", "[line 14] Warning: Unreachable code."]
+    );
+}
+
+#[test]
+fn warnings_do_not_stop_run_unless_denied() {
+    let source = "fun f() { return 1; print 2; }\nprint f();";
+    let result = lox("run", source, &[]);
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "1\n");
+    assert_eq!(result.stderr, "[line 1] Warning: Unreachable code.\n");
+    for command in ["run", "check"] {
+        let result = lox(command, source, &["--deny-warnings"]);
+        assert_eq!(result.code, 65, "{}", command);
+        assert_eq!(result.stdout, "");
+    }
+}
+
+// 只有打开 --warn-shadow-native 时才检查；局部变量和参数不算
+#[test]
+fn globals_shadowing_natives() {
+    let source = "var clock = 1;
+                  fun round(x) { return x; }
+                  class eval {}
+                  { var exec = 2; }
+                  fun f(getenv) { var trunc = getenv; return trunc; }
+                  print clock;";
+    assert_eq!(warnings(source), Vec::<String>::new());
+    let result = lox("check", source, &["--warn-shadow-native"]);
+    assert_eq!(result.code, 0);
+    assert_eq!(
+        result.stderr,
+        "[line 1] Warning: Shadowing built-in 'clock'.\n\
+         [line 2] Warning: Shadowing built-in 'round'.\n\
+         [line 3] Warning: Shadowing built-in 'eval'.\n"
+    );
+    let result = lox("run", "var a, clock = jsonParse(\"[1, 2]\");", &["--warn-shadow-native"]);
+    assert_eq!(result.stderr, "[line 1] Warning: Shadowing built-in 'clock'.\n");
+}
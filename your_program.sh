@@ -21,4 +21,6 @@ set -e # Exit early if any commands fail
 #
 # - Edit this to change how your program runs locally
 # - Edit .codecrafters/run.sh to change how your program runs remotely
+# The CodeCrafters tests expect diagnostics without the file name prefix
+export LOX_PLAIN_ERRORS=1
 exec /tmp/codecrafters-build-interpreter-rust/release/codecrafters-interpreter "$@"